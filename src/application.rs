@@ -1,11 +1,11 @@
 use {
-  crate::editor::Editor,
+  crate::{editor::Editor, tabline::TabLine},
   as_any::{AsAny, Downcast},
   crossterm::event::{Event as TuiEvent, EventStream},
   futures::StreamExt,
   std::collections::VecDeque,
   thiserror::Error,
-  tokio::sync::mpsc::{error::SendError, UnboundedSender},
+  tokio::sync::mpsc::{error::SendError, UnboundedReceiver, UnboundedSender},
   tui::{
     backend::CrosstermBackend,
     buffer::Buffer as TuiBuffer,
@@ -13,8 +13,6 @@ use {
     Terminal,
   },
 };
-
-
 #[derive(Debug, Error)]
 pub enum PluginError {
   #[error("Could not initialize plugin {0}")]
@@ -40,6 +38,12 @@ pub trait Plugin: AsAny {
     event: &TuiEvent,
   ) -> Result<ProcessEvent, PluginError>;
 
+  /// Pick up work done in the background, called after a [`Waker`] woke
+  /// the application.
+  fn poll(&mut self, _app: &mut Application) -> Result<(), PluginError> {
+    Ok(())
+  }
+
   /// Get cursor position and cursor kind.
   fn cursor(&self, _area: Rect) -> Option<(u16, u16)> {
     None
@@ -68,6 +72,20 @@ pub enum ApplicationError {
 #[derive(Debug)]
 pub enum Command {
   Quit,
+  /// Poll the plugins for background work and render.
+  Wake,
+}
+
+/// Wakes up the application from any thread, so plugins can act on work
+/// finished in the background.
+#[derive(Clone)]
+pub struct Waker(UnboundedSender<Command>);
+
+impl Waker {
+  pub fn wake(&self) {
+    // the application is gone when this fails, nothing left to wake
+    let _ = self.0.send(Command::Wake);
+  }
 }
 
 pub struct Application {
@@ -75,20 +93,30 @@ pub struct Application {
   active_plugins: VecDeque<Box<dyn Plugin>>,
   terminal: Option<TuiTerminal>,
   cmd: Option<UnboundedSender<Command>>,
+  cmd_rx: Option<UnboundedReceiver<Command>>,
 }
 
 impl Application {
   pub fn new(terminal: TuiTerminal) -> Application {
+    let (cmd_tx, cmd_rx) = tokio::sync::mpsc::unbounded_channel();
+    let editor = Editor::with_waker(Waker(cmd_tx.clone()));
+
     Self {
       terminal: Some(terminal),
       plugins: Vec::new(),
       active_plugins: VecDeque::from_iter(vec![
-        Box::<Editor>::default() as Box<dyn Plugin>
+        Box::new(editor) as Box<dyn Plugin>,
+        Box::new(TabLine),
       ]),
-      cmd: None,
+      cmd: Some(cmd_tx),
+      cmd_rx: Some(cmd_rx),
     }
   }
 
+  pub fn waker(&self) -> Option<Waker> {
+    self.cmd.clone().map(Waker)
+  }
+
   /// helper to find the editor plugin, it asssumes it is present
   /// and will return the first instance found
   pub fn editor(&mut self) -> &mut Editor {
@@ -128,19 +156,41 @@ impl Application {
     &mut self,
     events: &mut EventStream,
   ) -> Result<(), ApplicationError> {
-    let (cmd_tx, mut cmd_rx) = tokio::sync::mpsc::unbounded_channel();
-    self.cmd = Some(cmd_tx);
+    let mut cmd_rx = match self.cmd_rx.take() {
+      Some(cmd_rx) => cmd_rx,
+      None => {
+        let (cmd_tx, cmd_rx) = tokio::sync::mpsc::unbounded_channel();
+        self.cmd = Some(cmd_tx);
+        cmd_rx
+      }
+    };
+
+    // show the editor before the first key press
+    self.render()?;
 
     let mut fused_events = events.fuse();
     loop {
       tokio::select! {
         cmd = cmd_rx.recv() => {
-          #[allow(clippy::single_match)]
           match cmd {
             Some(Command::Quit) => {
               return Ok(());
             }
-            _ => {}
+            Some(Command::Wake) => {
+              let mut processed_plugins = VecDeque::new();
+              while let Some(mut plugin) = self.active_plugins.pop_front() {
+                let result = plugin.poll(self);
+                processed_plugins.push_back(plugin);
+                if let Err(e) = result {
+                  self.active_plugins.append(&mut processed_plugins);
+                  return Err(e.into());
+                }
+              }
+              self.active_plugins.append(&mut processed_plugins);
+
+              self.render()?;
+            }
+            None => {}
           }
         }
 
@@ -161,39 +211,51 @@ impl Application {
 
           // now after we processed all the events
           // lets render the plugins
-          if let Some(mut terminal) = self.terminal.take() {
-            let area = terminal.size()?;
-            let surface = terminal.current_buffer_mut();
-
-            // process the plugins
-            let mut processed_plugins = VecDeque::new();
-            while let Some(mut plugin) = self.active_plugins.pop_front() {
-              plugin.render(self, &area, surface);
-              processed_plugins.push_back(plugin);
-            }
-            self.active_plugins.append(&mut processed_plugins);
+          self.render()?;
+        }
+      }
+    }
+  }
 
-            // set the cursor position, first one wins
-            let cursor = self.active_plugins
-              .iter()
-              .rev()
-              .find_map(|p| p.cursor(area));
+  fn render(&mut self) -> Result<(), ApplicationError> {
+    if let Some(mut terminal) = self.terminal.take() {
+      let area = terminal.size()?;
+      let surface = terminal.current_buffer_mut();
 
-            // set the cursor
-            let (line,pos) = cursor.unwrap_or((0,0));
+      // process the plugins, one at a time is taken out so it can look up
+      // the others
+      for i in 0..self.active_plugins.len() {
+        let mut plugin = self
+          .active_plugins
+          .remove(i)
+          .expect("plugin index in range");
+        plugin.render(self, &area, surface);
+        self.active_plugins.insert(i, plugin);
+      }
 
-            surface.set_stringn(area.width-5, area.height-1,
-                                format!("{}:{}", line, pos),
-                                20,
-                                tui::style::Style::default());
+      // set the cursor position, first one wins
+      let cursor = self
+        .active_plugins
+        .iter()
+        .rev()
+        .find_map(|p| p.cursor(area));
 
-            terminal.draw(|f| f.set_cursor(pos, line))?;
+      // set the cursor
+      let (line, pos) = cursor.unwrap_or((0, 0));
 
-            self.terminal = Some(terminal);
-          }
-        }
-      }
+      surface.set_stringn(
+        area.width - 5,
+        area.height - 1,
+        format!("{}:{}", line, pos),
+        20,
+        tui::style::Style::default(),
+      );
+
+      terminal.draw(|f| f.set_cursor(pos, line))?;
+
+      self.terminal = Some(terminal);
     }
+    Ok(())
   }
 }
 
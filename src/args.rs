@@ -0,0 +1,290 @@
+//! Command line arguments of the `blasted` binary.
+
+use {
+  crate::{
+    document::Document,
+    editor::{Editor, EditorError, EditorResult},
+    layout::Axis,
+    movement::{find, first_non_blank, last_line},
+  },
+  std::{ffi::OsString, path::PathBuf},
+  thiserror::Error,
+};
+
+pub const USAGE: &str = "\
+Usage: blasted [options] [file ...]
+
+Every file is opened in a buffer of its own, files that do not exist yet
+are created on the first write. A directory shows a listing of its entries
+and `-` reads the text from stdin.
+
+Options:
+  +LINE       Put the cursor on line LINE of the first file
+  +           Put the cursor on the last line of the first file
+  +/TEXT      Put the cursor on the first TEXT in the first file, taken
+              literally
+  -R          Open the files read-only
+  -o          Show every file in a view, stacked
+  -O          Show every file in a view, side by side
+  -h, --help  Show this help
+  --version   Show the version
+  --          Take the arguments after it as files
+";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ArgsError {
+  #[error("Unknown option: {0}")]
+  UnknownOption(String),
+  #[error("Invalid line number: {0}")]
+  InvalidLine(String),
+}
+
+pub type ArgsResult<T> = Result<T, ArgsError>;
+
+/// Where the cursor starts in the first file. Line jumps go to the first
+/// non-blank of the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Jump {
+  /// Line number, `1` for the first line.
+  Line(usize),
+  LastLine,
+  /// First occurrence of the text, taken literally rather than as a
+  /// pattern.
+  Text(String),
+}
+
+/// A file to open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileArg {
+  Path(PathBuf),
+  Stdin,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Args {
+  pub files: Vec<FileArg>,
+  pub jump: Option<Jump>,
+  pub read_only: bool,
+  /// Show every file in a view of its own, split along this axis.
+  pub split: Option<Axis>,
+  pub help: bool,
+  pub version: bool,
+}
+
+impl Args {
+  /// Parse the arguments after the name of the binary.
+  pub fn parse(args: impl IntoIterator<Item = OsString>) -> ArgsResult<Self> {
+    let mut parsed = Self::default();
+    let mut options = true;
+    for arg in args {
+      let Some(text) = arg.to_str().filter(|_| options) else {
+        parsed.files.push(FileArg::Path(arg.into()));
+        continue;
+      };
+      match text {
+        "--" => options = false,
+        "-" => parsed.files.push(FileArg::Stdin),
+        "-R" => parsed.read_only = true,
+        "-o" => parsed.split = Some(Axis::Horizontal),
+        "-O" => parsed.split = Some(Axis::Vertical),
+        "-h" | "--help" => parsed.help = true,
+        "--version" => parsed.version = true,
+        "+" => parsed.jump = Some(Jump::LastLine),
+        _ if text.starts_with("+/") => {
+          parsed.jump = Some(Jump::Text(text[2..].to_string()));
+        }
+        _ if text.starts_with('+') => {
+          let line = text[1..]
+            .parse()
+            .map_err(|_| ArgsError::InvalidLine(text[1..].to_string()))?;
+          parsed.jump = Some(Jump::Line(line));
+        }
+        _ if text.starts_with('-') => {
+          return Err(ArgsError::UnknownOption(text.to_string()));
+        }
+        _ => parsed.files.push(FileArg::Path(arg.into())),
+      }
+    }
+    Ok(parsed)
+  }
+
+  /// True when a file is read from stdin, which has to happen before the
+  /// terminal is claimed.
+  pub fn reads_stdin(&self) -> bool {
+    self.files.contains(&FileArg::Stdin)
+  }
+
+  /// Open the files in `editor`, with `stdin` for the `-` file. The first
+  /// file ends up in the active view, an empty document when there are no
+  /// files. Files that fail to open are reported in the status line.
+  pub fn open(&self, editor: &mut Editor, stdin: &[u8]) -> EditorResult<()> {
+    let mut documents = Vec::new();
+    let mut errors = Vec::new();
+    for file in &self.files {
+      let document_id = match file {
+        FileArg::Stdin => editor.add_document(Document::from_bytes(stdin)),
+        FileArg::Path(path) => match editor.load(path, None) {
+          Ok(document_id) => document_id,
+          Err(e) => {
+            errors.push(format!("{}: {}", path.display(), e));
+            continue;
+          }
+        },
+      };
+      if self.read_only {
+        editor.documents[document_id].read_only = true;
+      }
+      documents.push(document_id);
+    }
+    if documents.is_empty() {
+      documents.push(editor.create_document());
+    }
+
+    match self.split {
+      // new views go before the active one, so the first file is split off
+      // last and ends up first
+      Some(axis) => {
+        let (last, rest) = documents.split_last().expect("no documents");
+        editor.create_view(*last)?;
+        for document_id in rest.iter().rev() {
+          let view_id = editor.split_view(axis)?;
+          editor.show_document(view_id, *document_id)?;
+        }
+        editor.tabs.active_mut().layout.equalize();
+      }
+      None => {
+        editor.create_view(documents[0])?;
+      }
+    }
+
+    if let Some(jump) = &self.jump {
+      let (_view_id, document_id) =
+        editor.active_view().ok_or(EditorError::ViewNotPresent)?;
+      let text = editor.documents[document_id].text().slice(..);
+      let line_start = |line| (line, first_non_blank(&text, line));
+      let cursor = match jump {
+        Jump::Line(line) => {
+          Some(line_start(line.saturating_sub(1).min(last_line(&text))))
+        }
+        Jump::LastLine => Some(line_start(last_line(&text))),
+        Jump::Text(wanted) => {
+          editor.registers.set_search(wanted.clone());
+          find(&text, wanted).or_else(|| {
+            errors.push(format!("Text not found: {wanted}"));
+            None
+          })
+        }
+      };
+      if let Some(cursor) = cursor {
+        editor.set_cursor(cursor)?;
+      }
+    }
+
+    if !errors.is_empty() {
+      editor.status = Some(errors.join("\n"));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::util::fs::TempDir};
+
+  fn parse(args: &[&str]) -> ArgsResult<Args> {
+    Args::parse(args.iter().map(OsString::from))
+  }
+
+  #[test]
+  fn test_parse_args() {
+    assert_eq!(
+      parse(&["-R", "+12", "-O", "a.txt", "-", "--", "-b"]),
+      Ok(Args {
+        files: vec![
+          FileArg::Path("a.txt".into()),
+          FileArg::Stdin,
+          FileArg::Path("-b".into()),
+        ],
+        jump: Some(Jump::Line(12)),
+        read_only: true,
+        split: Some(Axis::Vertical),
+        ..Default::default()
+      })
+    );
+    assert_eq!(
+      parse(&["+/fn main"]).unwrap().jump,
+      Some(Jump::Text("fn main".into()))
+    );
+    assert_eq!(parse(&["+"]).unwrap().jump, Some(Jump::LastLine));
+    assert_eq!(parse(&["+x"]), Err(ArgsError::InvalidLine("x".into())));
+    assert_eq!(parse(&["-x"]), Err(ArgsError::UnknownOption("-x".into())));
+  }
+
+  #[test]
+  fn test_open_files() {
+    let dir = TempDir::new("args");
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.txt"), "one\n  two\n").unwrap();
+
+    let mut editor = Editor::default();
+    editor.config.undo_dir = Some(dir.join("undo"));
+    let args = parse(&["-o", "-R", "+2"]).unwrap();
+    let files = ["a.txt", "new.txt", ""].map(|name| dir.join(name));
+    Args {
+      files: files.iter().cloned().map(FileArg::Path).collect(),
+      ..args
+    }
+    .open(&mut editor, b"")
+    .unwrap();
+
+    // the first file is on top and active, with its cursor on line 2
+    let views = editor.tabs.active().layout.views();
+    assert_eq!(views.len(), 3);
+    let (view_id, document_id) = editor.active_view().unwrap();
+    assert_eq!(views[0], view_id);
+    let document = &editor.documents[document_id];
+    assert_eq!(document.cursor(&view_id), Some((1, 2)));
+    assert!(document.read_only);
+
+    let new = editor.find_document(&files[1]).unwrap();
+    assert_eq!(editor.documents[new].text().len_chars(), 0);
+    let listing = editor.documents[editor.views[views[2]].document_id].text();
+    assert_eq!(
+      listing.line(0).to_string(),
+      format!(
+        "{}{}\n",
+        dir.join("sub").display(),
+        std::path::MAIN_SEPARATOR
+      )
+    );
+  }
+
+  #[test]
+  fn test_jump_to_text() {
+    let dir = TempDir::new("args-jump");
+    let path = dir.join("a.txt");
+    std::fs::write(&path, "a.c\nabc\n").unwrap();
+    let open = |jump: &str| {
+      let mut editor = Editor::default();
+      editor.config.undo_dir = Some(dir.join("undo"));
+      let args = parse(&[jump]).unwrap();
+      Args {
+        files: vec![FileArg::Path(path.clone())],
+        ..args
+      }
+      .open(&mut editor, b"")
+      .unwrap();
+      let (view_id, document_id) = editor.active_view().unwrap();
+      let cursor = editor.documents[document_id].cursor(&view_id);
+      (cursor, editor.status)
+    };
+
+    assert_eq!(open("+/bc"), (Some((1, 1)), None));
+    // the text is not a pattern, `.` only matches itself
+    assert_eq!(open("+/.c"), (Some((0, 1)), None));
+    assert_eq!(
+      open("+/b.c"),
+      (Some((0, 0)), Some("Text not found: b.c".into()))
+    );
+  }
+}
@@ -0,0 +1,405 @@
+use {
+  crate::{
+    history::UndoKind,
+    util::{encoding::Encoding, line_endings::LineEnding},
+  },
+  std::{path::PathBuf, str::FromStr, time::Duration},
+  thiserror::Error,
+};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CommandError {
+  #[error("Not an editor command: {0}")]
+  UnknownCommand(String),
+  #[error("Invalid argument: {0}")]
+  InvalidArgument(String),
+  #[error("Argument required")]
+  MissingArgument,
+}
+
+/// A buffer named in `:buffer` and `:bdelete`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BufferRef {
+  Number(usize),
+  /// Part of the path of its file.
+  Name(String),
+}
+
+impl BufferRef {
+  fn parse(arg: &str) -> Option<Self> {
+    if arg.is_empty() {
+      return None;
+    }
+    Some(match arg.parse() {
+      Ok(number) => Self::Number(number),
+      Err(_) => Self::Name(arg.to_string()),
+    })
+  }
+}
+
+/// Where `:tabmove` puts the active tab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabMove {
+  /// To this position, `0` is first.
+  To(usize),
+  /// This many places right, or left when negative.
+  By(isize),
+  Last,
+}
+
+/// A command typed on the `:` command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExCommand {
+  /// Quit, `force` drops unsaved changes.
+  Quit {
+    force: bool,
+  },
+  /// Save the active document, in another encoding when given one. Given
+  /// a file, a copy is written there instead. `force` writes read-only
+  /// documents.
+  Write {
+    force: bool,
+    path: Option<PathBuf>,
+    encoding: Option<&'static Encoding>,
+  },
+  /// Save the active document to another file, which becomes its file.
+  SaveAs {
+    path: PathBuf,
+    encoding: Option<&'static Encoding>,
+  },
+  /// Open a file, or read the active one again. `force` drops unsaved
+  /// changes, `encoding` overrides the detected encoding.
+  Edit {
+    force: bool,
+    path: Option<PathBuf>,
+    encoding: Option<&'static Encoding>,
+  },
+  WriteQuit,
+  Undo,
+  Redo,
+  Earlier(UndoKind),
+  Later(UndoKind),
+  /// Convert the line endings of the active document, `unix` or `dos`.
+  /// Without one the current line ending is shown.
+  FileFormat(Option<LineEnding>),
+  /// Set the tab width of the active document, or show it without one.
+  TabStop(Option<usize>),
+  /// Set the lines kept in view around the cursor, or show them.
+  ScrollOff(Option<usize>),
+  /// Set the columns kept in view beside the cursor, or show them.
+  SideScrollOff(Option<usize>),
+  /// Turn soft wrapping of long lines on or off, `wrap` and `nowrap`.
+  Wrap(bool),
+  /// Set what is drawn in front of the rows a wrapped line continues on.
+  ShowBreak(String),
+  /// Continue wrapped lines at their indent or at the left edge,
+  /// `breakindent` and `nobreakindent`.
+  BreakIndent(bool),
+  /// Show absolute line numbers or not, `number` and `nonumber`.
+  Number(bool),
+  /// Show relative line numbers or not, `relativenumber` and
+  /// `norelativenumber`. With `number` as well the cursor line is absolute.
+  RelativeNumber(bool),
+  /// Open a tab with the file at the path, or an empty document.
+  TabNew(Option<PathBuf>),
+  TabClose,
+  TabNext,
+  TabPrevious,
+  TabMove(TabMove),
+  /// List the open documents.
+  Buffers,
+  /// Show the next document in the active view.
+  BufferNext,
+  /// Show the previous document in the active view.
+  BufferPrevious,
+  /// Show a document in the active view.
+  Buffer(BufferRef),
+  /// Close a document, the active one without a buffer. `force` drops
+  /// unsaved changes.
+  BufferDelete {
+    force: bool,
+    buffer: Option<BufferRef>,
+  },
+}
+
+/// Parse the argument of `:tabmove`, a position or `+N` and `-N` to move
+/// relative to the current one. Without one the tab moves to the end.
+fn parse_tab_move(arg: &str) -> Result<TabMove, CommandError> {
+  let invalid = || CommandError::InvalidArgument(arg.to_string());
+  if arg.is_empty() {
+    return Ok(TabMove::Last);
+  }
+  if let Some(offset) = arg.strip_prefix('+') {
+    return offset.parse().map(TabMove::By).map_err(|_| invalid());
+  }
+  if arg.starts_with('-') {
+    return arg.parse().map(TabMove::By).map_err(|_| invalid());
+  }
+  arg.parse().map(TabMove::To).map_err(|_| invalid())
+}
+
+/// Parse the argument of `:earlier` and `:later`, a number of steps or a
+/// time period like `10s`, `5m`, `1h` or `2d`.
+fn parse_undo_kind(arg: &str) -> Result<UndoKind, CommandError> {
+  if arg.is_empty() {
+    return Ok(UndoKind::Steps(1));
+  }
+
+  let invalid = || CommandError::InvalidArgument(arg.to_string());
+  let (count, unit) =
+    arg.split_at(arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len()));
+  let count: u64 = count.parse().map_err(|_| invalid())?;
+  let seconds = match unit {
+    "" => return Ok(UndoKind::Steps(count as usize)),
+    "s" => 1,
+    "m" => 60,
+    "h" => 60 * 60,
+    "d" => 24 * 60 * 60,
+    _ => return Err(invalid()),
+  };
+  Ok(UndoKind::TimePeriod(Duration::from_secs(count * seconds)))
+}
+
+fn parse_file_format(arg: &str) -> Result<Option<LineEnding>, CommandError> {
+  match arg {
+    "" => Ok(None),
+    "unix" | "lf" => Ok(Some(LineEnding::LF)),
+    "dos" | "crlf" => Ok(Some(LineEnding::Crlf)),
+    #[cfg(feature = "unicode-lines")]
+    "mac" | "cr" => Ok(Some(LineEnding::CR)),
+    _ => Err(CommandError::InvalidArgument(arg.to_string())),
+  }
+}
+
+/// Parse the argument of `:write` and `:edit`, `++enc=<label>` options
+/// followed by an optional file name.
+fn parse_file_arg(
+  mut arg: &str,
+) -> Result<(Option<PathBuf>, Option<&'static Encoding>), CommandError> {
+  let mut encoding = None;
+  while let Some(option) = arg.strip_prefix("++") {
+    let (option, rest) = option.split_once(' ').unwrap_or((option, ""));
+    let label = option
+      .strip_prefix("enc=")
+      .or_else(|| option.strip_prefix("encoding="))
+      .ok_or_else(|| CommandError::InvalidArgument(format!("++{option}")))?;
+    encoding = Some(
+      Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| CommandError::InvalidArgument(label.to_string()))?,
+    );
+    arg = rest.trim_start();
+  }
+
+  let path = (!arg.is_empty()).then(|| PathBuf::from(arg));
+  Ok((path, encoding))
+}
+
+impl FromStr for ExCommand {
+  type Err = CommandError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+    let (name, arg) = s.split_once(' ').unwrap_or((s, ""));
+    let arg = arg.trim();
+
+    match name {
+      "q" | "quit" => Ok(Self::Quit { force: false }),
+      "q!" | "quit!" => Ok(Self::Quit { force: true }),
+      "w" | "write" | "w!" | "write!" => {
+        let (path, encoding) = parse_file_arg(arg)?;
+        Ok(Self::Write {
+          force: name.ends_with('!'),
+          path,
+          encoding,
+        })
+      }
+      "sav" | "saveas" => match parse_file_arg(arg)? {
+        (Some(path), encoding) => Ok(Self::SaveAs { path, encoding }),
+        (None, _) => Err(CommandError::MissingArgument),
+      },
+      "e" | "edit" | "e!" | "edit!" => {
+        let (path, encoding) = parse_file_arg(arg)?;
+        Ok(Self::Edit {
+          force: name.ends_with('!'),
+          path,
+          encoding,
+        })
+      }
+      "wq" | "x" | "xit" => Ok(Self::WriteQuit),
+      "u" | "undo" => Ok(Self::Undo),
+      "red" | "redo" => Ok(Self::Redo),
+      "ea" | "earlier" => parse_undo_kind(arg).map(Self::Earlier),
+      "lat" | "later" => parse_undo_kind(arg).map(Self::Later),
+      "ff" | "fileformat" => parse_file_format(arg).map(Self::FileFormat),
+      "so" | "scrolloff" if arg.is_empty() => Ok(Self::ScrollOff(None)),
+      "so" | "scrolloff" => arg
+        .parse()
+        .map(|lines| Self::ScrollOff(Some(lines)))
+        .map_err(|_| CommandError::InvalidArgument(arg.to_string())),
+      "siso" | "sidescrolloff" if arg.is_empty() => {
+        Ok(Self::SideScrollOff(None))
+      }
+      "siso" | "sidescrolloff" => arg
+        .parse()
+        .map(|columns| Self::SideScrollOff(Some(columns)))
+        .map_err(|_| CommandError::InvalidArgument(arg.to_string())),
+      "wrap" => Ok(Self::Wrap(true)),
+      "nowrap" => Ok(Self::Wrap(false)),
+      "sbr" | "showbreak" => Ok(Self::ShowBreak(arg.to_string())),
+      "bri" | "breakindent" => Ok(Self::BreakIndent(true)),
+      "nobri" | "nobreakindent" => Ok(Self::BreakIndent(false)),
+      "nu" | "number" => Ok(Self::Number(true)),
+      "nonu" | "nonumber" => Ok(Self::Number(false)),
+      "rnu" | "relativenumber" => Ok(Self::RelativeNumber(true)),
+      "nornu" | "norelativenumber" => Ok(Self::RelativeNumber(false)),
+      "tabnew" | "tabe" | "tabedit" => {
+        Ok(Self::TabNew((!arg.is_empty()).then(|| PathBuf::from(arg))))
+      }
+      "tabc" | "tabclose" => Ok(Self::TabClose),
+      "tabn" | "tabnext" => Ok(Self::TabNext),
+      "tabp" | "tabprevious" | "tabN" | "tabNext" => Ok(Self::TabPrevious),
+      "tabm" | "tabmove" => parse_tab_move(arg).map(Self::TabMove),
+      "ls" | "buffers" | "files" => Ok(Self::Buffers),
+      "bn" | "bnext" => Ok(Self::BufferNext),
+      "bp" | "bprevious" | "bN" | "bNext" => Ok(Self::BufferPrevious),
+      "b" | "buffer" => BufferRef::parse(arg)
+        .map(Self::Buffer)
+        .ok_or_else(|| CommandError::InvalidArgument(arg.to_string())),
+      "bd" | "bdelete" | "bd!" | "bdelete!" => Ok(Self::BufferDelete {
+        force: name.ends_with('!'),
+        buffer: BufferRef::parse(arg),
+      }),
+      "ts" | "tabstop" if arg.is_empty() => Ok(Self::TabStop(None)),
+      "ts" | "tabstop" => match arg.parse() {
+        Ok(width) if width > 0 => Ok(Self::TabStop(Some(width))),
+        _ => Err(CommandError::InvalidArgument(arg.to_string())),
+      },
+      _ => Err(CommandError::UnknownCommand(name.to_string())),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    encoding_rs::{UTF_16LE, WINDOWS_1252},
+  };
+
+  #[test]
+  fn test_parse_commands() {
+    assert_eq!("q".parse(), Ok(ExCommand::Quit { force: false }));
+    assert_eq!("q!".parse(), Ok(ExCommand::Quit { force: true }));
+    assert_eq!(
+      "w".parse(),
+      Ok(ExCommand::Write {
+        force: false,
+        path: None,
+        encoding: None
+      })
+    );
+    assert_eq!(
+      "w  other.txt".parse(),
+      Ok(ExCommand::Write {
+        force: false,
+        path: Some("other.txt".into()),
+        encoding: None
+      })
+    );
+    assert_eq!(
+      "w ++enc=utf-16le my file.txt".parse(),
+      Ok(ExCommand::Write {
+        force: false,
+        path: Some("my file.txt".into()),
+        encoding: Some(UTF_16LE)
+      })
+    );
+    assert_eq!(
+      "sav new.txt".parse(),
+      Ok(ExCommand::SaveAs {
+        path: "new.txt".into(),
+        encoding: None
+      })
+    );
+    assert_eq!(
+      "saveas".parse::<ExCommand>(),
+      Err(CommandError::MissingArgument)
+    );
+    assert_eq!(
+      "e! ++enc=latin1".parse(),
+      Ok(ExCommand::Edit {
+        force: true,
+        path: None,
+        encoding: Some(WINDOWS_1252)
+      })
+    );
+    assert_eq!(
+      "e ++enc=klingon".parse::<ExCommand>(),
+      Err(CommandError::InvalidArgument("klingon".into()))
+    );
+    assert_eq!(
+      "earlier".parse(),
+      Ok(ExCommand::Earlier(UndoKind::Steps(1)))
+    );
+    assert_eq!(
+      "earlier 3".parse(),
+      Ok(ExCommand::Earlier(UndoKind::Steps(3)))
+    );
+    assert_eq!(
+      "later 10s".parse(),
+      Ok(ExCommand::Later(UndoKind::TimePeriod(Duration::from_secs(
+        10
+      ))))
+    );
+    assert_eq!(
+      "ea 2h".parse(),
+      Ok(ExCommand::Earlier(UndoKind::TimePeriod(
+        Duration::from_secs(7200)
+      )))
+    );
+    assert_eq!(
+      "later 5x".parse::<ExCommand>(),
+      Err(CommandError::InvalidArgument("5x".into()))
+    );
+    assert_eq!(
+      "ff dos".parse(),
+      Ok(ExCommand::FileFormat(Some(LineEnding::Crlf)))
+    );
+    assert_eq!("fileformat".parse(), Ok(ExCommand::FileFormat(None)));
+    assert_eq!("ts 8".parse(), Ok(ExCommand::TabStop(Some(8))));
+    assert_eq!("so 3".parse(), Ok(ExCommand::ScrollOff(Some(3))));
+    assert_eq!("siso".parse(), Ok(ExCommand::SideScrollOff(None)));
+    assert_eq!("nowrap".parse(), Ok(ExCommand::Wrap(false)));
+    assert_eq!("rnu".parse(), Ok(ExCommand::RelativeNumber(true)));
+    assert_eq!("tabnew".parse(), Ok(ExCommand::TabNew(None)));
+    assert_eq!("b 2".parse(), Ok(ExCommand::Buffer(BufferRef::Number(2))));
+    assert_eq!(
+      "b main".parse(),
+      Ok(ExCommand::Buffer(BufferRef::Name("main".into())))
+    );
+    assert_eq!(
+      "bd!".parse(),
+      Ok(ExCommand::BufferDelete {
+        force: true,
+        buffer: None
+      })
+    );
+    assert_eq!(
+      "tabe notes.md".parse(),
+      Ok(ExCommand::TabNew(Some("notes.md".into())))
+    );
+    assert_eq!("tabm".parse(), Ok(ExCommand::TabMove(TabMove::Last)));
+    assert_eq!("tabm 0".parse(), Ok(ExCommand::TabMove(TabMove::To(0))));
+    assert_eq!("tabm -1".parse(), Ok(ExCommand::TabMove(TabMove::By(-1))));
+    assert_eq!("tabm +2".parse(), Ok(ExCommand::TabMove(TabMove::By(2))));
+    assert_eq!("sbr >>".parse(), Ok(ExCommand::ShowBreak(">>".into())));
+    assert_eq!(
+      "tabstop 0".parse::<ExCommand>(),
+      Err(CommandError::InvalidArgument("0".into()))
+    );
+    assert_eq!(
+      "frobnicate".parse::<ExCommand>(),
+      Err(CommandError::UnknownCommand("frobnicate".into()))
+    );
+  }
+}
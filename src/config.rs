@@ -0,0 +1,39 @@
+use {
+  crate::{gutter::LineNumbers, history::persistent},
+  std::path::PathBuf,
+};
+
+/// Editor wide settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+  /// Lines kept visible above and below the cursor when scrolling.
+  pub scrolloff: usize,
+  /// Columns kept visible left and right of the cursor when scrolling
+  /// sideways.
+  pub sidescrolloff: usize,
+  /// Wrap lines wider than the view instead of scrolling sideways.
+  pub soft_wrap: bool,
+  /// Drawn in front of the rows a wrapped line continues on.
+  pub wrap_indicator: String,
+  /// Continue wrapped lines at the indent of their first row.
+  pub wrap_indent: bool,
+  /// Line numbers shown in the gutter.
+  pub line_numbers: LineNumbers,
+  /// Where the undo history of files is kept between sessions, `None` keeps
+  /// it only while the editor runs.
+  pub undo_dir: Option<PathBuf>,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      scrolloff: 5,
+      sidescrolloff: 5,
+      soft_wrap: false,
+      wrap_indicator: "↪ ".to_string(),
+      wrap_indent: true,
+      line_numbers: LineNumbers::default(),
+      undo_dir: persistent::default_undo_dir(),
+    }
+  }
+}
@@ -0,0 +1,128 @@
+use {
+  crate::transaction::{Change, Transaction},
+  ropey::Rope,
+  similar::{DiffTag, TextDiff},
+  std::time::Duration,
+};
+
+/// Replaced blocks up to this many chars are diffed again char by char.
+const MAX_CHAR_DIFF: usize = 10_000;
+
+/// Char offset of the start of every slice, followed by the total length.
+fn offsets(slices: &[&str]) -> Vec<usize> {
+  let mut offsets = Vec::with_capacity(slices.len() + 1);
+  let mut offset = 0;
+  offsets.push(offset);
+  for slice in slices {
+    offset += slice.chars().count();
+    offsets.push(offset);
+  }
+  offsets
+}
+
+/// Changes inside a replaced block of lines starting at char `start`, as
+/// small as a char diff makes them.
+fn compare_chars(
+  start: usize,
+  old: &str,
+  new: &str,
+  changes: &mut Vec<Change>,
+) {
+  let diff = TextDiff::configure()
+    .timeout(Duration::from_millis(100))
+    .diff_chars(old, new);
+  let new_chars: Vec<&str> = diff.iter_new_slices().collect();
+
+  for op in diff.ops() {
+    let (tag, old_range, new_range) = op.as_tag_tuple();
+    if tag == DiffTag::Equal {
+      continue;
+    }
+    let replacement = new_chars[new_range].concat();
+    changes.push((
+      start + old_range.start,
+      start + old_range.end,
+      (!replacement.is_empty()).then_some(replacement),
+    ));
+  }
+}
+
+/// The transaction turning `old` into `new`. Unchanged text is retained,
+/// so positions on it map through the transaction onto the same text.
+pub fn compare_ropes(old: &Rope, new: &Rope) -> Transaction {
+  let old_text = old.to_string();
+  let new_text = new.to_string();
+  let diff = TextDiff::configure()
+    .timeout(Duration::from_secs(1))
+    .diff_lines(old_text.as_str(), new_text.as_str());
+  let old_lines: Vec<&str> = diff.iter_old_slices().collect();
+  let new_lines: Vec<&str> = diff.iter_new_slices().collect();
+  let old_offsets = offsets(&old_lines);
+
+  let mut changes = Vec::new();
+  for op in diff.ops() {
+    let (tag, old_range, new_range) = op.as_tag_tuple();
+    let from = old_offsets[old_range.start];
+    let to = old_offsets[old_range.end];
+    let replacement = new_lines[new_range].concat();
+
+    match tag {
+      DiffTag::Equal => {}
+      DiffTag::Replace
+        if (to - from) + replacement.chars().count() <= MAX_CHAR_DIFF =>
+      {
+        let original = old_lines[old_range].concat();
+        compare_chars(from, &original, &replacement, &mut changes);
+      }
+      _ => changes.push((
+        from,
+        to,
+        (!replacement.is_empty()).then_some(replacement),
+      )),
+    }
+  }
+
+  Transaction::change(old, changes)
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::transaction::Assoc};
+
+  fn check(old: &str, new: &str) -> Transaction {
+    let old = Rope::from_str(old);
+    let new = Rope::from_str(new);
+    let transaction = compare_ropes(&old, &new);
+    let mut text = old.clone();
+    assert!(transaction.apply(&mut text));
+    assert_eq!(text, new);
+    transaction
+  }
+
+  #[test]
+  fn test_compare_ropes() {
+    check("", "");
+    check("", "new\ntext");
+    check("old\ntext\n", "");
+    check("one\ntwo\nthree\n", "one\nthree\nfour\n");
+    check("héllo wörld\r\n", "hello wörld!\r\n");
+  }
+
+  #[test]
+  fn test_positions_stay_on_the_same_text() {
+    let transaction = check(
+      "fn main() {\n  println!(\"hi\");\n}\n",
+      "// header\nfn main() {\n    println!(\"hi\");\n}\n",
+    );
+    let changes = transaction.changes();
+    // the `p` of println moves with its line
+    assert_eq!(changes.map_pos(14, Assoc::After), 26);
+    // so does the closing brace
+    assert_eq!(changes.map_pos(30, Assoc::After), 42);
+
+    // the block is measured in chars, wide ones included
+    let line = "é".repeat(4000);
+    let transaction = check(&format!("{line}a\n"), &format!("{line}b\n"));
+    assert_eq!(transaction.changes().map_pos(10, Assoc::After), 10);
+  }
+}
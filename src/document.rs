@@ -1,32 +1,130 @@
 use {
-  crate::{movement, view::ViewId},
+  crate::{
+    diff,
+    history::{
+      persistent::{self, ContentHash},
+      Cursors,
+      History,
+      Step,
+      UndoKind,
+    },
+    movement::{
+      self,
+      cursor,
+      first_non_blank,
+      last_line,
+      line_len,
+      textobject::TextObject,
+      Motion,
+    },
+    register::Register,
+    selection::{self, Position, Selection, SelectionKind},
+    text_layout::{char_offset, visual_column, TextFormat},
+    transaction::{Assoc, Transaction},
+    util::{
+      char::{next_grapheme_boundary, prev_grapheme_boundary},
+      encoding::FileEncoding,
+      fs,
+      line_endings::{
+        auto_detect_line_ending,
+        get_line_ending,
+        get_line_ending_of_str,
+        has_mixed_line_endings,
+        line_end_char_index,
+        normalize_line_endings,
+        LineEnding,
+      },
+    },
+    view::ViewId,
+  },
   ropey::Rope,
   slotmap::new_key_type,
-  std::{collections::HashMap, convert::Infallible, str::FromStr},
+  std::{
+    collections::HashMap,
+    convert::Infallible,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+  },
   thiserror::Error,
+  unicode_width::UnicodeWidthStr,
 };
 
 new_key_type! { pub struct DocumentId; }
 
 pub enum DocEvent {
-  MoveWordForward,
-  MoveCursorLeft,
-  MoveCursorDown,
-  MoveCursorUp,
-  MoveCursorRight,
-  MoveWordBackward,
-  MoveWordEnd,
-  MoveLineStart,
-  MoveLineEnd,
-  MoveDocumentEnd,
-  MoveDocumentStart,
+  /// Move every cursor a number of times, leaving nothing else selected.
+  Move(Motion, usize),
+  /// Move the head of every selection a number of times, its anchor stays.
+  Extend(Motion, usize),
+  /// Make every selection a cursor at its head.
+  CollapseSelections,
+  /// Swap the ends of every selection, like vim's `o` in visual mode.
+  FlipSelections,
+  /// Select the text object at every cursor, like `iw` in visual mode.
+  SelectObject {
+    object: TextObject,
+    around: bool,
+    count: usize,
+  },
+  /// Put a cursor on every line of the block of the primary selection,
+  /// at its left edge, for `I` in blockwise visual mode.
+  InsertBlock,
+  /// Put a cursor on every line of the block of the primary selection,
+  /// right of it, for `A` in blockwise visual mode.
+  AppendBlock,
+  /// Add a cursor a line above the topmost selection, as the primary one.
+  AddCursorAbove,
+  /// Add a cursor a line below the bottommost selection, as the primary
+  /// one.
+  AddCursorBelow,
+  /// Split every selection into a selection per line.
+  SplitSelectionLines,
+  /// Make the next selection primary.
+  RotatePrimaryForward,
+  /// Make the previous selection primary.
+  RotatePrimaryBackward,
+  /// Drop every selection but the primary one.
+  KeepPrimary,
+  InsertChar(char),
+  InsertNewline,
+  InsertTab,
+  /// Insert pasted text, with its line endings made to match the document.
+  Paste(String),
+  /// Delete the char under the cursor, never the line ending.
   DeleteChar,
+  /// Delete the char before the cursor, joining lines at the line start.
+  DeleteCharBackward,
+  /// Delete the char after the cursor, joining lines at the line end.
+  DeleteCharForward,
+  Undo,
+  Redo,
+  Earlier(UndoKind),
+  Later(UndoKind),
+}
+
+/// An action on the text a selection covers, like vim's `d` or `>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+  Delete,
+  /// Delete and insert text in its place.
+  Change,
+  Yank,
+  /// Put a tab in front of every line.
+  Indent,
+  /// Take a tab, or up to a tab width of spaces, off every line.
+  Outdent,
+  ToggleCase,
 }
 
 #[derive(Error, Debug)]
 pub enum DocumentError {
   #[error("Trying to access a non-existent view")]
   ViewNotPresent,
+  #[error("Transaction does not apply to the document")]
+  InvalidTransaction,
+  #[error("No file name")]
+  NoPath,
   #[error(transparent)]
   IoError(#[from] std::io::Error),
 }
@@ -35,47 +133,1257 @@ pub type DocumentResult<T> = Result<T, DocumentError>;
 
 #[derive(Default)]
 pub struct Document {
-  pub rope: Rope,
-  pub cursor: HashMap<ViewId, (usize, usize)>,
+  rope: Rope,
+  /// Selections of every view on the document, motions and edits apply to
+  /// all of a view's ranges.
+  pub selections: HashMap<ViewId, Selection>,
+  path: Option<PathBuf>,
+  history: History,
+  pending: Option<PendingChanges>,
+  /// Revision of the history that was last saved to or loaded from disk.
+  saved_revision: usize,
+  /// Hash of the text last saved to or loaded from disk.
+  saved_hash: Option<ContentHash>,
+  /// Revision count and current revision of the history when it was last
+  /// loaded from or written to the undo directory.
+  stored_history: Option<(usize, usize)>,
+  /// Encoding the file is read and written in.
+  encoding: FileEncoding,
+  /// Line ending of new lines.
+  line_ending: LineEnding,
+  pub text_format: TextFormat,
+  /// Only written to its file when forced.
+  pub read_only: bool,
+  /// Where the undo history is kept between sessions, `None` keeps it only
+  /// in memory.
+  pub undo_dir: Option<PathBuf>,
+}
+
+/// Outcome of comparing a document with its file after the file changed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExternalChange {
+  /// The file holds the text we know about.
+  Unchanged,
+  /// The file changed and the document was reloaded.
+  Reloaded,
+  /// The file changed while the document has unsaved changes.
+  Conflict,
+}
+
+/// Edits since the last commit, undone as one step.
+struct PendingChanges {
+  transaction: Transaction,
+  /// Text before the first of the edits.
+  original: Rope,
+  /// Selections before the first of the edits.
+  cursors: Cursors,
+}
+
+/// Decode the contents of the file at `path`, malformed bytes become
+/// replacement chars.
+fn decode(path: &Path, encoding: FileEncoding, bytes: &[u8]) -> Rope {
+  let (text, malformed) = encoding.decode(bytes);
+  if malformed {
+    tracing::warn!(
+      "{} is not valid {}, malformed bytes were replaced",
+      path.display(),
+      encoding.name()
+    );
+  }
+  text
 }
 
 impl Document {
   pub fn new_view(&mut self, view: ViewId) {
-    self.cursor.insert(view, Default::default());
+    self.selections.insert(view, Selection::default());
+  }
+
+  /// Where the cursor of the primary selection of `view_id` is.
+  pub fn cursor(&self, view_id: &ViewId) -> Option<Position> {
+    self.selections.get(view_id).map(Selection::cursor)
   }
 
-  pub fn from_reader(
-    path: impl AsRef<std::path::Path>,
+  /// Open the file at `path`, guessing its encoding.
+  pub fn from_reader(path: impl AsRef<Path>) -> DocumentResult<Self> {
+    Self::from_file(path, None)
+  }
+
+  /// Open the file at `path` in `encoding`, guessed when not given.
+  pub fn from_file(
+    path: impl AsRef<Path>,
+    encoding: Option<FileEncoding>,
   ) -> DocumentResult<Self> {
-    let rope = Rope::from_reader(std::fs::File::open(path)?)?;
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+    let encoding = encoding.unwrap_or_else(|| FileEncoding::detect(&bytes));
+    let rope = decode(path, encoding, &bytes);
     Ok(Self {
+      saved_hash: Some(persistent::content_hash(&rope)),
+      line_ending: auto_detect_line_ending(&rope).unwrap_or_default(),
       rope,
-      cursor: Default::default(),
+      path: Some(path.to_path_buf()),
+      encoding,
+      ..Default::default()
+    })
+  }
+
+  /// An empty document for a file that does not exist yet.
+  pub fn new_file(path: impl AsRef<Path>) -> Self {
+    Self {
+      path: Some(path.as_ref().to_path_buf()),
+      ..Default::default()
+    }
+  }
+
+  /// A document without a file holding `bytes`, read from stdin.
+  pub fn from_bytes(bytes: &[u8]) -> Self {
+    let encoding = FileEncoding::detect(bytes);
+    let rope = decode(Path::new("stdin"), encoding, bytes);
+    Self {
+      line_ending: auto_detect_line_ending(&rope).unwrap_or_default(),
+      rope,
+      encoding,
+      ..Default::default()
+    }
+  }
+
+  /// A read-only listing of the directory at `path`, a line per entry with
+  /// directories first and marked by a trailing `/`.
+  pub fn from_directory(path: impl AsRef<Path>) -> DocumentResult<Self> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(path.as_ref())? {
+      let entry = entry?;
+      let is_dir = entry.file_type()?.is_dir();
+      entries.push((!is_dir, path.as_ref().join(entry.file_name())));
+    }
+    entries.sort();
+
+    let mut text = String::new();
+    for (is_file, path) in entries {
+      text.push_str(&path.to_string_lossy());
+      if !is_file {
+        text.push(std::path::MAIN_SEPARATOR);
+      }
+      text.push('\n');
+    }
+    Ok(Self {
+      rope: Rope::from(text),
+      read_only: true,
+      ..Default::default()
     })
   }
 
+  /// Read the file again in the encoding of the document.
+  fn read_file(&self) -> DocumentResult<Rope> {
+    let path = self.path.as_ref().ok_or(DocumentError::NoPath)?;
+    Ok(decode(path, self.encoding, &std::fs::read(path)?))
+  }
+
+  /// Replace the history with the one stored on disk for this file, as long
+  /// as it was written for the current text.
+  pub fn load_undo_history(&mut self) -> DocumentResult<()> {
+    let (Some(path), Some(undo_dir)) = (&self.path, &self.undo_dir) else {
+      return Ok(());
+    };
+    let undo_file = persistent::undo_file_path(undo_dir, path)?;
+    if let Some(history) = persistent::read_undo_file(&undo_file, &self.rope)? {
+      self.history = history;
+      self.pending = None;
+      self.saved_revision = self.history.current_revision();
+      self.stored_history = Some(self.history_state());
+    }
+    Ok(())
+  }
+
+  /// Store the history on disk so it survives a restart. Only the history
+  /// of a saved document is stored, as it is loaded back for the text in
+  /// its file, and only when it has edits the undo directory lacks.
+  pub fn write_undo_history(&mut self) -> DocumentResult<()> {
+    self.commit();
+    let (Some(path), Some(undo_dir)) = (&self.path, &self.undo_dir) else {
+      return Ok(());
+    };
+    let state = self.history_state();
+    if self.is_modified()
+      || self.history.revision_count() == 1
+      || self.stored_history == Some(state)
+    {
+      return Ok(());
+    }
+    let undo_file = persistent::undo_file_path(undo_dir, path)?;
+    persistent::write_undo_file(&undo_file, &self.history, &self.rope)?;
+    self.stored_history = Some(state);
+    Ok(())
+  }
+
+  fn history_state(&self) -> (usize, usize) {
+    (
+      self.history.revision_count(),
+      self.history.current_revision(),
+    )
+  }
+
+  pub fn path(&self) -> Option<&Path> {
+    self.path.as_deref()
+  }
+
+  /// Name of the file to show, `[No Name]` for a document without one.
+  pub fn display_name(&self) -> String {
+    self.path.as_deref().and_then(Path::file_name).map_or_else(
+      || "[No Name]".to_string(),
+      |name| name.to_string_lossy().into_owned(),
+    )
+  }
+
+  /// True when the text differs from the last saved revision.
+  pub fn is_modified(&self) -> bool {
+    let pending = self
+      .pending
+      .as_ref()
+      .is_some_and(|pending| !pending.transaction.is_empty());
+    pending || self.history.current_revision() != self.saved_revision
+  }
+
+  /// Write the text back to its file.
+  pub fn save(&mut self) -> DocumentResult<()> {
+    let path = self.path.clone().ok_or(DocumentError::NoPath)?;
+    self.save_as(path)
+  }
+
+  /// Write the text to `path`, which becomes the file of this document
+  /// once it is written.
+  pub fn save_as(&mut self, path: impl AsRef<Path>) -> DocumentResult<()> {
+    self.commit();
+    self.write_to(path.as_ref())?;
+    self.path = Some(path.as_ref().to_path_buf());
+    self.saved_revision = self.history.current_revision();
+    self.saved_hash = Some(persistent::content_hash(&self.rope));
+
+    if let Err(e) = self.write_undo_history() {
+      tracing::warn!("Failed to write undo history: {}", e);
+    }
+    Ok(())
+  }
+
+  /// Write a copy of the text to `path`, the document keeps its own file
+  /// and unsaved changes.
+  pub fn write_to(&self, path: &Path) -> DocumentResult<()> {
+    fs::write_atomic(path, &self.encoding.encode(&self.rope)?)?;
+    Ok(())
+  }
+
+  /// Compare with the file after it changed on disk. A document without
+  /// unsaved changes is reloaded, as one undoable edit that keeps every
+  /// cursor on the same text.
+  pub fn sync_with_disk(&mut self) -> DocumentResult<ExternalChange> {
+    let text = self.read_file()?;
+    let hash = persistent::content_hash(&text);
+
+    // our own save, or the same text written again
+    if self.saved_hash == Some(hash) || text == self.rope {
+      return Ok(ExternalChange::Unchanged);
+    }
+    // only warn once for every version of the file
+    self.saved_hash = Some(hash);
+    if self.is_modified() {
+      return Ok(ExternalChange::Conflict);
+    }
+
+    self.replace_text(&text)?;
+    Ok(ExternalChange::Reloaded)
+  }
+
+  /// Read the file again, in `encoding` or else a freshly detected one.
+  /// Unsaved changes are dropped, the reload itself can be undone.
+  pub fn reload(
+    &mut self,
+    encoding: Option<FileEncoding>,
+  ) -> DocumentResult<()> {
+    let path = self.path.as_ref().ok_or(DocumentError::NoPath)?;
+    let bytes = std::fs::read(path)?;
+    let encoding = encoding.unwrap_or_else(|| FileEncoding::detect(&bytes));
+    let text = decode(path, encoding, &bytes);
+
+    self.encoding = encoding;
+    self.saved_hash = Some(persistent::content_hash(&text));
+    self.commit();
+    self.replace_text(&text)
+  }
+
+  /// Turn the text into `text` as one undo step that matches the file.
+  fn replace_text(&mut self, text: &Rope) -> DocumentResult<()> {
+    let transaction = diff::compare_ropes(&self.rope, text);
+    self.apply_and_record(&transaction, None)?;
+    self.commit();
+    self.saved_revision = self.history.current_revision();
+    if let Some(line_ending) = auto_detect_line_ending(&self.rope) {
+      self.line_ending = line_ending;
+    }
+    Ok(())
+  }
+
+  pub fn encoding(&self) -> FileEncoding {
+    self.encoding
+  }
+
+  /// Encoding for the next save.
+  pub fn set_encoding(&mut self, encoding: FileEncoding) {
+    self.encoding = encoding;
+  }
+
   pub fn lines(&'_ self) -> impl Iterator<Item = String> + '_ {
     self.rope.lines().map(|line| line.to_string())
   }
 
-  pub fn process(
+  /// Line ending used for new lines, the one the document already uses.
+  pub fn line_ending(&self) -> LineEnding {
+    self.line_ending
+  }
+
+  /// Whether the text uses more than one kind of line ending.
+  pub fn has_mixed_line_endings(&self) -> bool {
+    has_mixed_line_endings(&self.rope)
+  }
+
+  /// Make every line end in `line_ending`, as a single undo step. Cursors
+  /// stay on their line and column.
+  pub fn convert_line_endings(
+    &mut self,
+    line_ending: LineEnding,
+  ) -> DocumentResult<()> {
+    let changes = self
+      .rope
+      .lines()
+      .enumerate()
+      .filter_map(|(line, text)| {
+        let ending = get_line_ending(&text)
+          .filter(|e| *e != line_ending && !e.is_special_use())?;
+        let end = self.rope.line_to_char(line) + text.len_chars();
+        Some((
+          end - ending.len_chars(),
+          end,
+          Some(line_ending.as_str().into()),
+        ))
+      })
+      .collect::<Vec<_>>();
+    if changes.is_empty() {
+      return Ok(());
+    }
+
+    let cursors = self.selections.clone();
+    let transaction = Transaction::change(&self.rope, changes);
+    self.commit();
+    self.apply_and_record(&transaction, None)?;
+    self.restore_cursors(cursors);
+    self.commit();
+    self.line_ending = line_ending;
+    Ok(())
+  }
+
+  pub fn text(&self) -> &Rope {
+    &self.rope
+  }
+
+  /// Apply `transaction` to the text in one go. The selections of `view_id`
+  /// follow the edit, selections of other views stay in front of text
+  /// inserted at their position.
+  ///
+  /// The edit becomes part of the pending undo step, see [`Self::commit`].
+  pub fn apply(
+    &mut self,
+    transaction: &Transaction,
+    view_id: &ViewId,
+  ) -> DocumentResult<()> {
+    self.apply_and_record(transaction, Some(view_id))
+  }
+
+  fn apply_and_record(
+    &mut self,
+    transaction: &Transaction,
+    view_id: Option<&ViewId>,
+  ) -> DocumentResult<()> {
+    let original = self.rope.clone();
+    let cursors = self.selections.clone();
+    self.apply_to_text(transaction, view_id)?;
+
+    self.pending = Some(match self.pending.take() {
+      Some(pending) => PendingChanges {
+        transaction: pending.transaction.compose(transaction.clone()),
+        ..pending
+      },
+      None => PendingChanges {
+        transaction: transaction.clone(),
+        original,
+        cursors,
+      },
+    });
+
+    Ok(())
+  }
+
+  /// Apply without recording an undo step, selections of all views but
+  /// `view_id` stay in front of text inserted at their position. Without an
+  /// editing view, as for a reload, every selection stays on its chars.
+  fn apply_to_text(
+    &mut self,
+    transaction: &Transaction,
+    view_id: Option<&ViewId>,
+  ) -> DocumentResult<()> {
+    let mut rope = self.rope.clone();
+    if !transaction.apply(&mut rope) {
+      return Err(DocumentError::InvalidTransaction);
+    }
+
+    let old = self.rope.slice(..);
+    let new = rope.slice(..);
+    let changes = transaction.changes();
+    self.selections = self
+      .selections
+      .iter()
+      .map(|(id, selection)| {
+        let editing = view_id == Some(id);
+        let assoc = if editing || view_id.is_none() {
+          Assoc::After
+        } else {
+          Assoc::Before
+        };
+        let selection = match transaction.cursor() {
+          Some(cursor) if editing => {
+            Selection::point(movement::position(&new, cursor))
+          }
+          _ => selection.clone().transform(|range| {
+            let range = range.map(|pos| {
+              let idx = movement::char_index(&old, &pos);
+              movement::position(&new, changes.map_pos(idx, assoc))
+            });
+            // an edit forgets the column to keep
+            if editing {
+              selection::Range::new(range.anchor, range.head)
+            } else {
+              range
+            }
+          }),
+        };
+        (*id, selection)
+      })
+      .collect();
+
+    self.rope = rope;
+    Ok(())
+  }
+
+  /// Turn the edits made since the last commit into a single undo step.
+  pub fn commit(&mut self) {
+    if let Some(pending) = self.pending.take() {
+      if !pending.transaction.is_empty() {
+        self.history.commit_revision(
+          &pending.transaction,
+          &pending.original,
+          pending.cursors,
+          self.selections.clone(),
+        );
+      }
+    }
+  }
+
+  /// Put selections back where they were at a revision, views that did
+  /// not exist back then keep their mapped selections.
+  fn restore_cursors(&mut self, cursors: Cursors) {
+    let rope = self.rope.slice(..);
+    for (view_id, selection) in cursors {
+      if let Some(current) = self.selections.get_mut(&view_id) {
+        *current = selection.clamp(&rope);
+      }
+    }
+  }
+
+  fn replay(
+    &mut self,
+    view_id: &ViewId,
+    steps: Vec<Step>,
+  ) -> DocumentResult<()> {
+    for (transaction, cursors) in steps {
+      self.apply_to_text(&transaction, Some(view_id))?;
+      self.restore_cursors(cursors);
+    }
+    // the steps may have converted the line endings
+    if let Some(line_ending) = auto_detect_line_ending(&self.rope) {
+      self.line_ending = line_ending;
+    }
+    Ok(())
+  }
+
+  pub fn undo(&mut self, view_id: &ViewId) -> DocumentResult<()> {
+    self.commit();
+    let steps = self.history.undo().into_iter().collect();
+    self.replay(view_id, steps)
+  }
+
+  pub fn redo(&mut self, view_id: &ViewId) -> DocumentResult<()> {
+    self.commit();
+    let steps = self.history.redo().into_iter().collect();
+    self.replay(view_id, steps)
+  }
+
+  pub fn earlier(
+    &mut self,
+    view_id: &ViewId,
+    kind: UndoKind,
+  ) -> DocumentResult<()> {
+    self.commit();
+    let steps = self.history.earlier(kind);
+    self.replay(view_id, steps)
+  }
+
+  pub fn later(
+    &mut self,
+    view_id: &ViewId,
+    kind: UndoKind,
+  ) -> DocumentResult<()> {
+    self.commit();
+    let steps = self.history.later(kind);
+    self.replay(view_id, steps)
+  }
+
+  /// Insert `text` at every cursor of `view_id`, moving the cursors past
+  /// it.
+  pub fn insert(&mut self, view_id: &ViewId, text: &str) -> DocumentResult<()> {
+    let selection = self
+      .selections
+      .get(view_id)
+      .ok_or(DocumentError::ViewNotPresent)?;
+    let rope = self.rope.slice(..);
+    let mut positions: Vec<_> = selection
+      .iter()
+      .map(|range| movement::char_index(&rope, &range.head))
+      .collect();
+    positions.dedup();
+    let changes = positions
+      .into_iter()
+      .map(|at| (at, at, Some(text.to_string())));
+    let transaction = Transaction::change(&self.rope, changes);
+    self.apply(&transaction, view_id)
+  }
+
+  /// Remove the chars in `range`, pulling the cursors inside it back to its
+  /// start.
+  pub fn delete(
+    &mut self,
+    view_id: &ViewId,
+    range: Range<usize>,
+  ) -> DocumentResult<()> {
+    let transaction = Transaction::delete(&self.rope, range);
+    self.apply(&transaction, view_id)
+  }
+
+  /// Delete at every cursor of `view_id`, chars that more than one cursor
+  /// deletes are deleted once.
+  fn delete_at_cursor(
     &mut self,
     view_id: &ViewId,
     event: &DocEvent,
   ) -> DocumentResult<()> {
-    if !self.cursor.contains_key(view_id) {
-      return Err(DocumentError::ViewNotPresent);
+    let rope = self.rope.slice(..);
+    let selection = self
+      .selections
+      .get(view_id)
+      .ok_or(DocumentError::ViewNotPresent)?;
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for range in selection.iter() {
+      let (line, pos) = range.head;
+      let idx = movement::char_index(&rope, &(line, pos));
+      let at_line_end = pos >= movement::line_len(&rope, line);
+
+      // whole graphemes are deleted, never a part of one
+      let range = match event {
+        DocEvent::DeleteChar | DocEvent::DeleteCharForward if !at_line_end => {
+          idx..next_grapheme_boundary(&rope, idx)
+        }
+        DocEvent::DeleteCharBackward if pos > 0 => {
+          prev_grapheme_boundary(&rope, idx)..idx
+        }
+        DocEvent::DeleteCharBackward if line > 0 => {
+          line_end_char_index(&rope, line - 1)..idx
+        }
+        DocEvent::DeleteCharForward if line + 1 < rope.len_lines() => {
+          idx..rope.line_to_char(line + 1)
+        }
+        _ => continue,
+      };
+      match ranges.last_mut() {
+        Some(last) if range.start <= last.end => last.end = range.end,
+        _ => ranges.push(range),
+      }
+    }
+
+    let changes = ranges
+      .into_iter()
+      .map(|range| (range.start, range.end, None));
+    let transaction = Transaction::change(&self.rope, changes);
+    self.apply(&transaction, view_id)
+  }
+
+  /// A cursor on the line above the topmost selection, or below the
+  /// bottommost one, on the screen column of that selection.
+  fn add_cursor(&self, selection: Selection, below: bool) -> Selection {
+    let rope = self.rope.slice(..);
+    let format = &self.text_format;
+    let range = if below {
+      selection.ranges()[selection.len() - 1]
+    } else {
+      selection.ranges()[0]
+    };
+    let (line, pos) = range.head;
+    let column = range
+      .column
+      .unwrap_or_else(|| visual_column(rope.line(line), pos, format));
+    let head = if below {
+      cursor::down(&rope, &range.head, 1, column, format)
+    } else {
+      cursor::up(&rope, &range.head, 1, column, format)
+    };
+    if head.0 == line {
+      return selection;
+    }
+    selection.push(selection::Range {
+      column: Some(column),
+      ..selection::Range::point(head)
+    })
+  }
+
+  /// `range` with its head moved by `motion` taken `count` times, the
+  /// anchor follows unless `extend`.
+  fn move_range(
+    &self,
+    range: selection::Range,
+    motion: Motion,
+    count: usize,
+    extend: bool,
+  ) -> selection::Range {
+    let rope = self.rope.slice(..);
+    let format = &self.text_format;
+    let cursor = range.head;
+    let column = range
+      .column
+      .unwrap_or_else(|| visual_column(rope.line(cursor.0), cursor.1, format));
+    let (head, column) = motion.apply(&rope, &cursor, column, count, format);
+    selection::Range {
+      column,
+      ..range.put_head(head, extend)
+    }
+  }
+
+  /// Apply `operator` to the text every selection of `view_id` covers as
+  /// `kind`. Returns that text before the operator touched it, one value
+  /// per selection or per line of a block, lines of a linewise selection
+  /// always end with a line ending.
+  ///
+  /// Every selection becomes a cursor at its start, the first non-blank
+  /// for whole lines. A blockwise change leaves a cursor on every line of
+  /// the block to insert the same text on each.
+  pub fn operate(
+    &mut self,
+    view_id: &ViewId,
+    operator: Operator,
+    kind: SelectionKind,
+  ) -> DocumentResult<Vec<String>> {
+    let selection = self
+      .selections
+      .get(view_id)
+      .ok_or(DocumentError::ViewNotPresent)?;
+    let rope = self.rope.slice(..);
+    let format = &self.text_format;
+    let regions = selection.regions(&rope, kind, format);
+
+    // where the cursors go, before the edit
+    let block_change = operator == Operator::Change
+      && kind == SelectionKind::Blockwise
+      && !regions.is_empty();
+    let cursors: Vec<usize> = if block_change {
+      regions.iter().map(|region| region.start).collect()
+    } else {
+      selection
+        .iter()
+        .map(|range| {
+          range.regions(&rope, kind, format).first().map_or_else(
+            || movement::char_index(&rope, &range.from()),
+            |region| region.start,
+          )
+        })
+        .collect()
     };
+    let primary = if block_change {
+      0
+    } else {
+      selection.primary_index()
+    };
+    self.apply_operator(view_id, operator, kind, regions, cursors, primary)
+  }
 
-    // TODO: better error type
+  /// Apply `operator` to the text from every cursor of `view_id` to where
+  /// `motion` taken `count` times moves it, see [`Self::operate`]. Motions
+  /// between lines act on whole lines, exclusive motions leave out the char
+  /// they end on.
+  pub fn operate_motion(
+    &mut self,
+    view_id: &ViewId,
+    operator: Operator,
+    motion: Motion,
+    count: usize,
+  ) -> DocumentResult<Vec<String>> {
+    let selection = self
+      .selections
+      .get(view_id)
+      .ok_or(DocumentError::ViewNotPresent)?;
     let rope = self.rope.slice(..);
+    let format = &self.text_format;
+    let kind = if motion.is_linewise() {
+      SelectionKind::Linewise
+    } else {
+      SelectionKind::Charwise
+    };
 
-    if let DocEvent::MoveWordForward = event {
-      self.cursor.entry(*view_id).and_modify(|c| {
-        *c = movement::jumps::next_word(&rope, c);
+    let mut regions = Vec::new();
+    let mut cursors = Vec::new();
+    for range in selection.iter() {
+      let head = range.head;
+      let idx = movement::char_index(&rope, &head);
+      // `cw` on a word changes up to its end, like `ce`
+      let motion = match motion {
+        Motion::WordForward
+          if operator == Operator::Change
+            && !rope.get_char(idx).is_some_and(char::is_whitespace) =>
+        {
+          Motion::WordEnd
+        }
+        motion => motion,
+      };
+      let column = range
+        .column
+        .unwrap_or_else(|| visual_column(rope.line(head.0), head.1, format));
+      let (target, _) = motion.apply(&rope, &head, column, count, format);
+      let (from, to) = (head.min(target), head.max(target));
+      let (start, end) = (
+        movement::char_index(&rope, &from),
+        movement::char_index(&rope, &to),
+      );
+
+      let region = if motion.is_linewise() {
+        rope.line_to_char(from.0)
+          ..rope.line_to_char((to.0 + 1).min(rope.len_lines()))
+      } else if motion == Motion::Right {
+        // `l` takes the last char of the line, the cursor can not go past it
+        let line_end = line_end_char_index(&rope, head.0);
+        let end = (0..count).fold(idx, |end, _| {
+          next_grapheme_boundary(&rope, end).min(line_end)
+        });
+        idx..end
+      } else if motion.is_inclusive() {
+        start..next_grapheme_boundary(&rope, end).max(start)
+      } else if to.1 == 0 && to.0 > from.0 {
+        // ending at the start of a line leaves the line ending before it
+        start..line_end_char_index(&rope, to.0 - 1).max(start)
+      } else {
+        start..end
+      };
+      cursors.push(if operator == Operator::Yank {
+        start
+      } else {
+        region.start
       });
+      if !region.is_empty() {
+        regions.push(region);
+      }
+    }
+    let regions = selection::merge_regions(regions);
+    let primary = selection.primary_index();
+    self.apply_operator(view_id, operator, kind, regions, cursors, primary)
+  }
+
+  /// Apply `operator` to the text object at every cursor of `view_id`, see
+  /// [`Self::operate`]. Cursors without the object around them stay.
+  pub fn operate_object(
+    &mut self,
+    view_id: &ViewId,
+    operator: Operator,
+    object: TextObject,
+    around: bool,
+    count: usize,
+  ) -> DocumentResult<Vec<String>> {
+    let selection = self
+      .selections
+      .get(view_id)
+      .ok_or(DocumentError::ViewNotPresent)?;
+    let rope = self.rope.slice(..);
+    let kind = if object.is_linewise() {
+      SelectionKind::Linewise
+    } else {
+      SelectionKind::Charwise
+    };
+
+    let mut regions = Vec::new();
+    let mut cursors = Vec::new();
+    for range in selection.iter() {
+      let idx = movement::char_index(&rope, &range.head);
+      match object.range(&rope, idx, around, count) {
+        Some(region) if !region.is_empty() => {
+          cursors.push(region.start);
+          regions.push(region);
+        }
+        _ => cursors.push(idx),
+      }
+    }
+    let regions = selection::merge_regions(regions);
+    let primary = selection.primary_index();
+    self.apply_operator(view_id, operator, kind, regions, cursors, primary)
+  }
+
+  /// Apply `operator` to `regions`, sorted without overlaps, and put
+  /// cursors at `cursors` as they were before the edit.
+  fn apply_operator(
+    &mut self,
+    view_id: &ViewId,
+    operator: Operator,
+    kind: SelectionKind,
+    regions: Vec<Range<usize>>,
+    mut cursors: Vec<usize>,
+    primary: usize,
+  ) -> DocumentResult<Vec<String>> {
+    let rope = self.rope.slice(..);
+    let format = &self.text_format;
+    let line_ending = self.line_ending.as_str();
+    let values = regions
+      .iter()
+      .map(|region| {
+        let mut text = rope.slice(region.clone()).to_string();
+        if kind == SelectionKind::Linewise
+          && get_line_ending_of_str(&text).is_none()
+        {
+          text.push_str(line_ending);
+        }
+        text
+      })
+      .collect();
+
+    let lines = || {
+      let mut lines: Vec<_> = regions
+        .iter()
+        .flat_map(|region| {
+          rope.char_to_line(region.start)
+            ..=rope.char_to_line(region.end.max(region.start + 1) - 1)
+        })
+        .collect();
+      lines.dedup();
+      lines
+    };
+    let changes: Vec<_> = match operator {
+      Operator::Yank => Vec::new(),
+      Operator::Delete | Operator::Change => regions
+        .iter()
+        .map(|region| {
+          let (mut start, mut end) = (region.start, region.end);
+          if kind == SelectionKind::Linewise {
+            let last = rope.char_to_line(end.max(start + 1) - 1);
+            if operator == Operator::Change {
+              // a change keeps a line to insert on
+              end = line_end_char_index(&rope, last);
+            } else if end == rope.len_chars()
+              && start > 0
+              && get_line_ending(&rope.line(last)).is_none()
+            {
+              // without a line ending after the last line, the one before
+              // it goes
+              let first = rope.char_to_line(start);
+              start = line_end_char_index(&rope, first - 1);
+            }
+          }
+          (start, end, None)
+        })
+        .collect(),
+      Operator::Indent => lines()
+        .into_iter()
+        .filter(|line| line_len(&rope, *line) > 0)
+        .map(|line| {
+          let at = rope.line_to_char(line);
+          (at, at, Some("\t".to_string()))
+        })
+        .collect(),
+      Operator::Outdent => lines()
+        .into_iter()
+        .filter_map(|line| {
+          let at = rope.line_to_char(line);
+          let mut chars = rope.line(line).chars();
+          let width = match chars.next() {
+            Some('\t') => 1,
+            Some(' ') => {
+              1 + chars
+                .take(format.tab_width.max(1) - 1)
+                .take_while(|c| *c == ' ')
+                .count()
+            }
+            _ => return None,
+          };
+          Some((at, at + width, None))
+        })
+        .collect(),
+      Operator::ToggleCase => regions
+        .iter()
+        .map(|region| {
+          let text: String = rope
+            .slice(region.clone())
+            .chars()
+            .flat_map(|c| {
+              if c.is_uppercase() {
+                c.to_lowercase().collect::<Vec<_>>()
+              } else {
+                c.to_uppercase().collect()
+              }
+            })
+            .collect();
+          (region.start, region.end, Some(text))
+        })
+        .collect(),
+    };
+
+    let transaction = Transaction::change(&self.rope, changes);
+    for cursor in &mut cursors {
+      *cursor = transaction.changes().map_pos(*cursor, Assoc::Before);
+    }
+    if !transaction.is_empty() {
+      self.apply(&transaction, view_id)?;
+    }
+
+    let rope = self.rope.slice(..);
+    let whole_lines = kind == SelectionKind::Linewise
+      && !matches!(operator, Operator::Change | Operator::Yank);
+    let ranges = cursors
+      .into_iter()
+      .map(|cursor| {
+        let (line, pos) = movement::position(&rope, cursor);
+        let pos = if whole_lines {
+          first_non_blank(&rope, line)
+        } else {
+          pos
+        };
+        selection::Range::point((line, pos))
+      })
+      .collect();
+    self
+      .selections
+      .insert(*view_id, Selection::new(ranges, primary));
+    Ok(values)
+  }
+
+  /// A cursor on every line of the block the primary selection of
+  /// `view_id` covers, at its left edge or right after it when `append`.
+  /// Lines that end left of the block are left out, unless appending,
+  /// then they are padded with spaces up to the block.
+  fn insert_block(
+    &mut self,
+    view_id: &ViewId,
+    append: bool,
+  ) -> DocumentResult<()> {
+    let selection = self
+      .selections
+      .get(view_id)
+      .ok_or(DocumentError::ViewNotPresent)?;
+    let rope = self.rope.slice(..);
+    let format = &self.text_format;
+    let range = selection.primary();
+    let (left, right) = range.block_columns(&rope, format);
+
+    let mut changes = Vec::new();
+    let mut cursors = Vec::new();
+    for line in range.from().0..=range.to().0 {
+      let text = rope.line(line);
+      let len = line_len(&rope, line);
+      let width = visual_column(text, len, format);
+      if !append {
+        if width > left {
+          cursors.push((line, char_offset(text, left, format)));
+        }
+      } else if right == usize::MAX || width <= right {
+        if right != usize::MAX {
+          let at = rope.line_to_char(line) + len;
+          changes.push((at, at, Some(" ".repeat(right + 1 - width))));
+        }
+        cursors.push((line, usize::MAX));
+      } else {
+        cursors.push((line, char_offset(text, right + 1, format)));
+      }
+    }
+    if cursors.is_empty() {
+      cursors.push(range.from());
+    }
+
+    let transaction = Transaction::change(&self.rope, changes);
+    if !transaction.is_empty() {
+      self.apply(&transaction, view_id)?;
+    }
+    let rope = self.rope.slice(..);
+    let ranges = cursors
+      .into_iter()
+      .map(|(line, pos)| {
+        selection::Range::point((line, pos.min(line_len(&rope, line))))
+      })
+      .collect();
+    self.selections.insert(*view_id, Selection::new(ranges, 0));
+    Ok(())
+  }
+
+  /// Put the text of `register` `count` times at every cursor of
+  /// `view_id`, after the cursor or `before` it. Charwise text goes into
+  /// the line, linewise text on lines of its own below or above it and a
+  /// block at the column of the primary cursor, on the lines from there
+  /// down. Every cursor gets a value of its own when there are as many.
+  pub fn put(
+    &mut self,
+    view_id: &ViewId,
+    register: &Register,
+    before: bool,
+    count: usize,
+  ) -> DocumentResult<()> {
+    let count = count.max(1);
+    let selection = self
+      .selections
+      .get(view_id)
+      .ok_or(DocumentError::ViewNotPresent)?;
+    if register.kind == SelectionKind::Blockwise {
+      let head = selection.primary().head;
+      return self.put_block(view_id, head, &register.values, before, count);
+    }
+    let primary = selection.primary_index();
+    let rope = self.rope.slice(..);
+    let line_ending = self.line_ending.as_str();
+    let linewise = register.kind == SelectionKind::Linewise;
+    let joined = if linewise {
+      register.values.concat()
+    } else {
+      register.values.join(line_ending)
+    };
+
+    let mut changes = Vec::new();
+    // where every cursor goes, the start of its text and the chars in
+    // front of it that are not part of the text
+    let mut starts = Vec::new();
+    for (i, range) in selection.iter().enumerate() {
+      let value = match register.values.get(i) {
+        Some(value) if register.values.len() == selection.len() => value,
+        _ => &joined,
+      };
+      let mut text = normalize_line_endings(value, self.line_ending);
+      let (line, pos) = range.head;
+      let idx = movement::char_index(&rope, &range.head);
+      let mut prefix = 0;
+      let at = if !linewise {
+        if before || pos >= line_len(&rope, line) {
+          idx
+        } else {
+          next_grapheme_boundary(&rope, idx)
+        }
+      } else if before {
+        rope.line_to_char(line)
+      } else if line < last_line(&rope)
+        || get_line_ending(&rope.line(line)).is_some()
+      {
+        rope.line_to_char(line + 1)
+      } else {
+        // the last line has no line ending to put the text after
+        if let Some(ending) = get_line_ending_of_str(&text) {
+          text.truncate(text.len() - ending.as_str().len());
+        }
+        text.insert_str(0, line_ending);
+        prefix = line_ending.chars().count();
+        rope.len_chars()
+      };
+      let text = text.repeat(count);
+      if changes.last().is_some_and(|(last, _, _)| *last == at) {
+        continue;
+      }
+      starts.push((at, prefix, text.chars().count()));
+      changes.push((at, at, Some(text)));
     }
 
+    let transaction = Transaction::change(&self.rope, changes);
+    let starts: Vec<_> = starts
+      .into_iter()
+      .map(|(at, prefix, len)| {
+        (
+          transaction.changes().map_pos(at, Assoc::Before) + prefix,
+          len,
+        )
+      })
+      .collect();
+    if !transaction.is_empty() {
+      self.apply(&transaction, view_id)?;
+    }
+
+    // the cursor goes to the first line put, or to the end of text put
+    // within a line
+    let rope = self.rope.slice(..);
+    let ranges: Vec<_> = starts
+      .into_iter()
+      .map(|(start, len)| {
+        let (line, pos) = movement::position(&rope, start);
+        let end = start + len;
+        let position = if linewise {
+          (line, first_non_blank(&rope, line))
+        } else if len > 0 && rope.char_to_line(end - 1) == line {
+          movement::position(&rope, prev_grapheme_boundary(&rope, end))
+        } else {
+          (line, pos)
+        };
+        selection::Range::point(position)
+      })
+      .collect();
+    let primary = primary.min(ranges.len() - 1);
+    self
+      .selections
+      .insert(*view_id, Selection::new(ranges, primary));
+    Ok(())
+  }
+
+  /// Put the lines of a block `count` times side by side at the column of
+  /// `head` or right of it, on the lines from `head` down. Lines that end
+  /// left of the column are padded with spaces, lines past the end of the
+  /// text are added.
+  fn put_block(
+    &mut self,
+    view_id: &ViewId,
+    head: Position,
+    values: &[String],
+    before: bool,
+    count: usize,
+  ) -> DocumentResult<()> {
+    let rope = self.rope.slice(..);
+    let format = &self.text_format;
+    let (line, mut pos) = head;
+    if !before && pos < line_len(&rope, line) {
+      let idx = movement::char_index(&rope, &head);
+      pos += next_grapheme_boundary(&rope, idx) - idx;
+    }
+    let column = visual_column(rope.line(line), pos, format);
+    let width = values.iter().map(|value| value.width()).max().unwrap_or(0);
+
+    let mut changes = Vec::new();
+    let mut added = String::new();
+    for (i, value) in values.iter().enumerate() {
+      let padded = value.clone() + &" ".repeat(width - value.width());
+      let target = line + i;
+      if target > last_line(&rope) {
+        added.push_str(self.line_ending.as_str());
+        added.push_str(&" ".repeat(column));
+        added.push_str(&padded.repeat(count - 1));
+        added.push_str(value);
+        continue;
+      }
+      let text = rope.line(target);
+      let len = line_len(&rope, target);
+      let line_width = visual_column(text, len, format);
+      let start = rope.line_to_char(target);
+      let change = if line_width > column {
+        let at = start + char_offset(text, column, format);
+        (at, at, Some(padded.repeat(count)))
+      } else {
+        let padding = " ".repeat(column - line_width);
+        let text = padding + &padded.repeat(count - 1) + value;
+        (start + len, start + len, Some(text))
+      };
+      changes.push(change);
+    }
+    if !added.is_empty() {
+      let end = self.rope.len_chars();
+      changes.push((end, end, Some(added)));
+    }
+
+    let transaction = Transaction::change(&self.rope, changes);
+    if !transaction.is_empty() {
+      self.apply(&transaction, view_id)?;
+    }
+    let pos = char_offset(self.rope.line(line), column, &self.text_format);
+    self
+      .selections
+      .insert(*view_id, Selection::point((line, pos)));
+    Ok(())
+  }
+
+  pub fn process(
+    &mut self,
+    view_id: &ViewId,
+    event: &DocEvent,
+  ) -> DocumentResult<()> {
+    let selection = self
+      .selections
+      .get(view_id)
+      .ok_or(DocumentError::ViewNotPresent)?
+      .clone();
+
+    let selection = match event {
+      DocEvent::InsertChar(c) => {
+        return self.insert(view_id, c.encode_utf8(&mut [0; 4]));
+      }
+      DocEvent::InsertNewline => {
+        return self.insert(view_id, self.line_ending().as_str());
+      }
+      DocEvent::InsertTab => return self.insert(view_id, "\t"),
+      DocEvent::Paste(text) => {
+        let text = normalize_line_endings(text, self.line_ending);
+        return self.insert(view_id, &text);
+      }
+      DocEvent::Undo => return self.undo(view_id),
+      DocEvent::Redo => return self.redo(view_id),
+      DocEvent::Earlier(kind) => return self.earlier(view_id, *kind),
+      DocEvent::Later(kind) => return self.later(view_id, *kind),
+      DocEvent::DeleteChar
+      | DocEvent::DeleteCharBackward
+      | DocEvent::DeleteCharForward => {
+        return self.delete_at_cursor(view_id, event);
+      }
+      DocEvent::AddCursorAbove => self.add_cursor(selection, false),
+      DocEvent::AddCursorBelow => self.add_cursor(selection, true),
+      DocEvent::SplitSelectionLines => {
+        selection.split_lines(&self.rope.slice(..))
+      }
+      DocEvent::RotatePrimaryForward => selection.rotate_primary(1),
+      DocEvent::RotatePrimaryBackward => selection.rotate_primary(-1),
+      DocEvent::KeepPrimary => selection.keep_primary(),
+      DocEvent::CollapseSelections => {
+        selection.transform(|range| selection::Range {
+          column: range.column,
+          ..selection::Range::point(range.head)
+        })
+      }
+      DocEvent::FlipSelections => selection.transform(selection::Range::flip),
+      DocEvent::SelectObject {
+        object,
+        around,
+        count,
+      } => {
+        let rope = self.rope.slice(..);
+        selection.transform(|range| {
+          let idx = movement::char_index(&rope, &range.head);
+          match object.range(&rope, idx, *around, *count) {
+            Some(region) if !region.is_empty() => selection::Range::new(
+              movement::position(&rope, region.start),
+              movement::position(
+                &rope,
+                prev_grapheme_boundary(&rope, region.end),
+              ),
+            ),
+            _ => range,
+          }
+        })
+      }
+      DocEvent::InsertBlock => return self.insert_block(view_id, false),
+      DocEvent::AppendBlock => return self.insert_block(view_id, true),
+      DocEvent::Move(motion, count) => selection
+        .transform(|range| self.move_range(range, *motion, *count, false)),
+      DocEvent::Extend(motion, count) => selection
+        .transform(|range| self.move_range(range, *motion, *count, true)),
+    };
+    self.selections.insert(*view_id, selection);
+
     Ok(())
   }
 }
@@ -84,8 +1392,10 @@ impl FromStr for Document {
   type Err = Infallible;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let rope = Rope::from_str(s);
     Ok(Self {
-      rope: Rope::from_str(s),
+      line_ending: auto_detect_line_ending(&rope).unwrap_or_default(),
+      rope,
       ..Default::default()
     })
   }
@@ -93,27 +1403,685 @@ impl FromStr for Document {
 
 #[cfg(test)]
 mod tests {
-  use super::*;
+  use {super::*, crate::util::fs::TempDir};
 
   #[test]
   fn test_processing_of_events() {
     // create the document
-    let mut document = Document::default();
-    document.rope.insert(0, "one two three four");
+    let mut document = Document::from_str("one two three four").unwrap();
 
     // create a view
     let view_id = ViewId::default();
     document.new_view(view_id);
 
     document
-      .process(&view_id, &DocEvent::MoveWordForward)
+      .process(&view_id, &DocEvent::Move(Motion::WordForward, 1))
+      .unwrap();
+    assert_eq!(document.cursor(&view_id), Some((0, 4)));
+
+    document
+      .process(&view_id, &DocEvent::Move(Motion::WordForward, 1))
+      .unwrap();
+    assert_eq!(document.cursor(&view_id), Some((0, 8)));
+  }
+
+  #[test]
+  fn test_sticky_column() {
+    let mut document =
+      Document::from_str("a long line\nab\nanother line").unwrap();
+    let view_id = ViewId::default();
+    document.new_view(view_id);
+
+    document
+      .process(&view_id, &DocEvent::Move(Motion::LineEnd, 1))
+      .unwrap();
+    document
+      .process(&view_id, &DocEvent::Move(Motion::Down, 1))
+      .unwrap();
+    assert_eq!(document.cursor(&view_id), Some((1, 1)));
+    document
+      .process(&view_id, &DocEvent::Move(Motion::Down, 1))
+      .unwrap();
+    assert_eq!(document.cursor(&view_id), Some((2, 11)));
+
+    document
+      .process(&view_id, &DocEvent::Move(Motion::LineStart, 1))
+      .unwrap();
+    document
+      .process(&view_id, &DocEvent::Move(Motion::WordForward, 1))
+      .unwrap();
+    document
+      .process(&view_id, &DocEvent::Move(Motion::Up, 1))
+      .unwrap();
+    assert_eq!(document.cursor(&view_id), Some((1, 2)));
+    document
+      .process(&view_id, &DocEvent::Move(Motion::Up, 1))
+      .unwrap();
+    assert_eq!(document.cursor(&view_id), Some((0, 8)));
+  }
+
+  #[test]
+  fn test_insert_and_delete() {
+    let mut document = Document::from_str("one\r\ntwo").unwrap();
+    let view_id = ViewId::default();
+    document.new_view(view_id);
+
+    document
+      .process(&view_id, &DocEvent::InsertChar('a'))
+      .unwrap();
+    document
+      .process(&view_id, &DocEvent::InsertNewline)
+      .unwrap();
+    assert_eq!(document.rope, "a\r\none\r\ntwo");
+    assert_eq!(document.cursor(&view_id), Some((1, 0)));
+
+    document
+      .process(&view_id, &DocEvent::DeleteCharBackward)
+      .unwrap();
+    assert_eq!(document.rope, "aone\r\ntwo");
+    assert_eq!(document.cursor(&view_id), Some((0, 1)));
+
+    document
+      .process(&view_id, &DocEvent::Move(Motion::LineEnd, 1))
+      .unwrap();
+    document
+      .process(&view_id, &DocEvent::Move(Motion::Right, 1))
+      .unwrap();
+    document.process(&view_id, &DocEvent::DeleteChar).unwrap();
+    assert_eq!(document.rope, "aone\r\ntwo");
+    document
+      .process(&view_id, &DocEvent::DeleteCharForward)
+      .unwrap();
+    assert_eq!(document.rope, "aonetwo");
+  }
+
+  #[test]
+  fn test_delete_graphemes() {
+    let mut document = Document::from_str("ae\u{301}🇳🇱b").unwrap();
+    let view_id = ViewId::default();
+    document.new_view(view_id);
+
+    document
+      .process(&view_id, &DocEvent::Move(Motion::Right, 1))
+      .unwrap();
+    assert_eq!(document.cursor(&view_id), Some((0, 1)));
+    document.process(&view_id, &DocEvent::DeleteChar).unwrap();
+    assert_eq!(document.rope, "a🇳🇱b");
+
+    document
+      .process(&view_id, &DocEvent::Move(Motion::LineEnd, 1))
+      .unwrap();
+    document
+      .process(&view_id, &DocEvent::DeleteCharBackward)
+      .unwrap();
+    assert_eq!(document.rope, "ab");
+  }
+
+  #[test]
+  fn test_edit_at_every_cursor() {
+    let mut document = Document::from_str("one\ntwo\nsix\n").unwrap();
+    let view_id = ViewId::default();
+    document.new_view(view_id);
+    document
+      .process(&view_id, &DocEvent::Move(Motion::Right, 1))
+      .unwrap();
+    for _ in 0..3 {
+      document
+        .process(&view_id, &DocEvent::AddCursorBelow)
+        .unwrap();
+    }
+    let selection = &document.selections[&view_id];
+    assert_eq!(selection.len(), 3);
+    assert_eq!(selection.cursor(), (2, 1));
+
+    document
+      .process(&view_id, &DocEvent::InsertChar('-'))
+      .unwrap();
+    assert_eq!(document.text(), "o-ne\nt-wo\ns-ix\n");
+    document
+      .process(&view_id, &DocEvent::DeleteCharBackward)
+      .unwrap();
+    document
+      .process(&view_id, &DocEvent::DeleteCharBackward)
+      .unwrap();
+    assert_eq!(document.text(), "ne\nwo\nix\n");
+
+    // cursors that meet merge into one
+    document
+      .process(&view_id, &DocEvent::RotatePrimaryForward)
+      .unwrap();
+    assert_eq!(document.cursor(&view_id), Some((0, 0)));
+    document
+      .process(&view_id, &DocEvent::Move(Motion::DocumentStart, 1))
+      .unwrap();
+    assert_eq!(document.selections[&view_id], Selection::point((0, 0)));
+  }
+
+  #[test]
+  fn test_visual_operators() {
+    use selection::Range;
+    let view_id = ViewId::default();
+    let text = "one two\n  three\nfour";
+    let document = |range| {
+      let mut document = Document::from_str(text).unwrap();
+      document
+        .selections
+        .insert(view_id, Selection::single(range));
+      document
+    };
+    let operate = |range, operator, kind| {
+      let mut document = document(range);
+      let values = document.operate(&view_id, operator, kind).unwrap();
+      let cursor = document.cursor(&view_id).unwrap();
+      (document.text().to_string(), values, cursor)
+    };
+    let (charwise, linewise, blockwise) = (
+      SelectionKind::Charwise,
+      SelectionKind::Linewise,
+      SelectionKind::Blockwise,
+    );
+
+    let range = Range::new((1, 3), (0, 4));
+    assert_eq!(
+      operate(range, Operator::Delete, charwise),
+      ("one ree\nfour".into(), vec!["two\n  th".into()], (0, 4))
+    );
+    // the last line has no line ending, the one before it goes instead
+    assert_eq!(
+      operate(Range::new((1, 3), (2, 0)), Operator::Delete, linewise),
+      ("one two".into(), vec!["  three\nfour\n".into()], (0, 0))
+    );
+    assert_eq!(
+      operate(range, Operator::Indent, linewise).0,
+      "\tone two\n\t  three\nfour"
+    );
+    assert_eq!(
+      operate(range, Operator::Outdent, linewise).0,
+      "one two\nthree\nfour"
+    );
+    assert_eq!(
+      operate(Range::new((0, 0), (0, 2)), Operator::ToggleCase, charwise).0,
+      "ONE two\n  three\nfour"
+    );
+    assert_eq!(
+      operate(Range::new((0, 1), (1, 3)), Operator::Yank, blockwise),
+      (text.into(), vec!["ne ".into(), " th".into()], (0, 1))
+    );
+
+    // a blockwise change inserts on every line of the block
+    let mut changed = document(Range::new((0, 1), (1, 3)));
+    changed
+      .operate(&view_id, Operator::Change, blockwise)
+      .unwrap();
+    changed
+      .process(&view_id, &DocEvent::InsertChar('X'))
+      .unwrap();
+    assert_eq!(changed.text(), "oXtwo\n Xree\nfour");
+
+    // `I` skips the line too short for the block, `A` pads it
+    let insert = |range, event| {
+      let mut document = document(range);
+      document.process(&view_id, &event).unwrap();
+      document
+        .process(&view_id, &DocEvent::InsertChar('|'))
+        .unwrap();
+      document.text().to_string()
+    };
+    let range = Range::new((0, 4), (2, 4));
+    assert_eq!(
+      insert(range, DocEvent::InsertBlock),
+      "one |two\n  th|ree\nfour"
+    );
+    assert_eq!(
+      insert(Range::new((0, 5), (2, 5)), DocEvent::AppendBlock),
+      "one tw|o\n  thre|e\nfour  |"
+    );
+    let to_line_end = Range {
+      column: Some(usize::MAX),
+      ..Range::new((0, 0), (2, 0))
+    };
+    assert_eq!(
+      insert(to_line_end, DocEvent::AppendBlock),
+      "one two|\n  three|\nfour|"
+    );
+  }
+
+  #[test]
+  fn test_operator_motions() {
+    let view_id = ViewId::default();
+    let text = "one two three\nfour five\nsix";
+    let operate = |cursor, operator, motion, count| {
+      let mut document = Document::from_str(text).unwrap();
+      document
+        .selections
+        .insert(view_id, Selection::point(cursor));
+      let values = document
+        .operate_motion(&view_id, operator, motion, count)
+        .unwrap();
+      let cursor = document.cursor(&view_id).unwrap();
+      (document.text().to_string(), values, cursor)
+    };
+
+    assert_eq!(
+      operate((0, 0), Operator::Delete, Motion::WordForward, 2),
+      (
+        "three\nfour five\nsix".into(),
+        vec!["one two ".into()],
+        (0, 0)
+      )
+    );
+    // `cw` keeps the space after the word, `dw` at the end of a line keeps
+    // the line ending
+    assert_eq!(
+      operate((0, 0), Operator::Change, Motion::WordForward, 1).0,
+      " two three\nfour five\nsix"
+    );
+    assert_eq!(
+      operate((0, 8), Operator::Delete, Motion::WordForward, 1).0,
+      "one two \nfour five\nsix"
+    );
+    let till_e = Motion::FindChar {
+      c: 'e',
+      forward: true,
+      till: true,
+    };
+    assert_eq!(
+      operate((0, 0), Operator::Delete, till_e, 1).0,
+      "e two three\nfour five\nsix"
+    );
+    // `2dd`
+    assert_eq!(
+      operate((0, 3), Operator::Delete, Motion::Down, 1),
+      (
+        "six".into(),
+        vec!["one two three\nfour five\n".into()],
+        (0, 0)
+      )
+    );
+    assert_eq!(
+      operate((1, 5), Operator::Yank, Motion::WordBackward, 1),
+      (text.into(), vec!["four ".into()], (1, 0))
+    );
+    // `3~` stops at the end of the line
+    assert_eq!(
+      operate((2, 1), Operator::ToggleCase, Motion::Right, 3).0,
+      "one two three\nfour five\nsIX"
+    );
+  }
+
+  #[test]
+  fn test_object_operators() {
+    let view_id = ViewId::default();
+    let text = "call(one, two)\n\nnext line\n";
+    let operate = |cursor, operator, object| {
+      let mut document = Document::from_str(text).unwrap();
+      document
+        .selections
+        .insert(view_id, Selection::point(cursor));
+      let values = document
+        .operate_object(
+          &view_id,
+          operator,
+          object,
+          object == TextObject::Paragraph,
+          1,
+        )
+        .unwrap();
+      let cursor = document.cursor(&view_id).unwrap();
+      (document.text().to_string(), values, cursor)
+    };
+
+    let word = TextObject::Word { big: false };
+    assert_eq!(
+      operate((0, 2), Operator::Yank, word),
+      (text.into(), vec!["call".into()], (0, 0))
+    );
+    assert_eq!(
+      operate((0, 7), Operator::Change, TextObject::Bracket('(', ')')),
+      (
+        "call()\n\nnext line\n".into(),
+        vec!["one, two".into()],
+        (0, 5)
+      )
+    );
+    // `dap` takes the blank line after the paragraph
+    assert_eq!(
+      operate((0, 3), Operator::Delete, TextObject::Paragraph),
+      (
+        "next line\n".into(),
+        vec!["call(one, two)\n\n".into()],
+        (0, 0)
+      )
+    );
+  }
+
+  #[test]
+  fn test_put_registers() {
+    let view_id = ViewId::default();
+    let put = |text: &str, cursor, kind, values: &[&str], before, count| {
+      let mut document = Document::from_str(text).unwrap();
+      document
+        .selections
+        .insert(view_id, Selection::point(cursor));
+      let register = Register {
+        kind,
+        values: values.iter().map(|value| value.to_string()).collect(),
+      };
+      document.put(&view_id, &register, before, count).unwrap();
+      let cursor = document.cursor(&view_id).unwrap();
+      (document.text().to_string(), cursor)
+    };
+
+    // charwise text goes after the cursor, which ends on its last char
+    assert_eq!(
+      put("abc", (0, 0), SelectionKind::Charwise, &["xy"], false, 2),
+      ("axyxybc".into(), (0, 4))
+    );
+    assert_eq!(
+      put("abc", (0, 1), SelectionKind::Charwise, &["x\ny"], true, 1),
+      ("ax\nybc".into(), (0, 1))
+    );
+    // linewise text goes below the line, or above it
+    assert_eq!(
+      put(
+        "one\ntwo",
+        (1, 1),
+        SelectionKind::Linewise,
+        &["  x\n"],
+        false,
+        1
+      ),
+      ("one\ntwo\n  x".into(), (2, 2))
+    );
+    assert_eq!(
+      put(
+        "one\ntwo\n",
+        (1, 1),
+        SelectionKind::Linewise,
+        &["x\n"],
+        true,
+        1
+      ),
+      ("one\nx\ntwo\n".into(), (1, 0))
+    );
+    // a block goes on the lines from the cursor down, padded to its width
+    assert_eq!(
+      put(
+        "abc\nd\n",
+        (0, 0),
+        SelectionKind::Blockwise,
+        &["12", "3"],
+        false,
+        1
+      ),
+      ("a12bc\nd3\n".into(), (0, 1))
+    );
+    assert_eq!(
+      put(
+        "abc",
+        (0, 2),
+        SelectionKind::Blockwise,
+        &["1", "2"],
+        true,
+        2
+      ),
+      ("ab11c\n  22".into(), (0, 2))
+    );
+  }
+
+  #[test]
+  fn test_line_endings() {
+    let mut document = Document::from_str("one\r\ntwo\nthree\r\n").unwrap();
+    let view_id = ViewId::default();
+    document.new_view(view_id);
+    assert_eq!(document.line_ending(), LineEnding::Crlf);
+    assert!(document.has_mixed_line_endings());
+
+    // pasted text gets the line ending of the document
+    let paste = DocEvent::Paste("a\nb\n".into());
+    document.process(&view_id, &paste).unwrap();
+    document.commit();
+    assert_eq!(document.rope, "a\r\nb\r\none\r\ntwo\nthree\r\n");
+    assert_eq!(document.cursor(&view_id), Some((2, 0)));
+
+    document
+      .process(&view_id, &DocEvent::Move(Motion::LineEnd, 1))
+      .unwrap();
+    document.convert_line_endings(LineEnding::LF).unwrap();
+    assert_eq!(document.rope, "a\nb\none\ntwo\nthree\n");
+    assert_eq!(document.cursor(&view_id), Some((2, 2)));
+    assert!(!document.has_mixed_line_endings());
+
+    // the conversion is a single undo step
+    document.process(&view_id, &DocEvent::Undo).unwrap();
+    assert_eq!(document.rope, "a\r\nb\r\none\r\ntwo\nthree\r\n");
+    assert_eq!(document.line_ending(), LineEnding::Crlf);
+    document.process(&view_id, &DocEvent::Redo).unwrap();
+    assert_eq!(document.line_ending(), LineEnding::LF);
+  }
+
+  #[test]
+  fn test_edits_shift_other_views() {
+    let mut document = Document::from_str("one\ntwo").unwrap();
+    let mut views = slotmap::SlotMap::<ViewId, ()>::with_key();
+    let (editing, other) = (views.insert(()), views.insert(()));
+    document.new_view(editing);
+    document.new_view(other);
+    document.selections.insert(other, Selection::point((1, 1)));
+
+    document
+      .process(&editing, &DocEvent::InsertNewline)
+      .unwrap();
+    assert_eq!(document.cursor(&other), Some((2, 1)));
+
+    document.process(&editing, &DocEvent::DeleteChar).unwrap();
+    assert_eq!(document.rope, "\nne\ntwo");
+    assert_eq!(document.cursor(&other), Some((2, 1)));
+
+    document
+      .selections
+      .insert(editing, Selection::point((2, 1)));
+    document.process(&editing, &DocEvent::DeleteChar).unwrap();
+    assert_eq!(document.cursor(&other), Some((2, 1)));
+    assert_eq!(document.rope, "\nne\nto");
+  }
+
+  #[test]
+  fn test_apply_transaction() {
+    let mut document = Document::from_str("hello world").unwrap();
+    let mut views = slotmap::SlotMap::<ViewId, ()>::with_key();
+    let (editing, other) = (views.insert(()), views.insert(()));
+    document.new_view(editing);
+    document.new_view(other);
+    document.selections.insert(other, Selection::point((0, 6)));
+
+    let transaction = Transaction::change(document.text(), [
+      (0, 5, Some("goodbye".into())),
+      (6, 6, Some("cruel ".into())),
+    ]);
+    document.apply(&transaction, &editing).unwrap();
+    assert_eq!(document.text(), "goodbye cruel world");
+    assert_eq!(document.cursor(&editing), Some((0, 7)));
+    assert_eq!(document.cursor(&other), Some((0, 8)));
+
+    // a transaction for another text leaves the document untouched
+    let stale = Transaction::insert(&Rope::from_str("hi"), 0, "oops");
+    assert!(document.apply(&stale, &editing).is_err());
+    assert_eq!(document.text(), "goodbye cruel world");
+  }
+
+  #[test]
+  fn test_undo_groups_commits_and_restores_cursors() {
+    let mut document = Document::from_str("one\ntwo").unwrap();
+    let mut views = slotmap::SlotMap::<ViewId, ()>::with_key();
+    let (editing, other) = (views.insert(()), views.insert(()));
+    document.new_view(editing);
+    document.new_view(other);
+    document.selections.insert(other, Selection::point((1, 2)));
+
+    // an insert session is a single undo step
+    for c in "abc".chars() {
+      document
+        .process(&editing, &DocEvent::InsertChar(c))
+        .unwrap();
+    }
+    document.commit();
+    document.process(&editing, &DocEvent::DeleteChar).unwrap();
+    document.commit();
+    assert_eq!(document.text(), "abcne\ntwo");
+
+    document.selections.insert(other, Selection::point((0, 0)));
+    document.process(&editing, &DocEvent::Undo).unwrap();
+    assert_eq!(document.text(), "abcone\ntwo");
+    assert_eq!(document.cursor(&editing), Some((0, 3)));
+    assert_eq!(document.cursor(&other), Some((1, 2)));
+
+    document.process(&editing, &DocEvent::Undo).unwrap();
+    assert_eq!(document.text(), "one\ntwo");
+    assert_eq!(document.cursor(&editing), Some((0, 0)));
+
+    document.process(&editing, &DocEvent::Redo).unwrap();
+    assert_eq!(document.text(), "abcone\ntwo");
+    assert_eq!(document.cursor(&editing), Some((0, 3)));
+
+    document
+      .process(&editing, &DocEvent::Later(UndoKind::Steps(1)))
+      .unwrap();
+    assert_eq!(document.text(), "abcne\ntwo");
+  }
+
+  #[test]
+  fn test_save_tracks_modified() {
+    let dir = TempDir::new("document-save");
+    let path = dir.join("file.txt");
+    std::fs::write(&path, "one").unwrap();
+
+    let mut document = Document::from_reader(&path).unwrap();
+    document.undo_dir = Some(dir.join("undo"));
+    let view_id = ViewId::default();
+    document.new_view(view_id);
+    assert!(!document.is_modified());
+    // a history without edits is not worth a file
+    document.write_undo_history().unwrap();
+    assert!(!dir.join("undo").exists());
+
+    document
+      .process(&view_id, &DocEvent::InsertChar('1'))
+      .unwrap();
+    assert!(document.is_modified());
+    document.save().unwrap();
+    assert!(!document.is_modified());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "1one");
+    // the history goes to the undo directory of the document, once
+    let undo_file = std::fs::read_dir(dir.join("undo"))
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .collect::<Vec<_>>();
+    assert_eq!(undo_file.len(), 1);
+    std::fs::remove_file(&undo_file[0]).unwrap();
+    document.write_undo_history().unwrap();
+    assert!(!undo_file[0].exists());
+
+    // going back to the saved revision is not a modification
+    document.process(&view_id, &DocEvent::Undo).unwrap();
+    assert!(document.is_modified());
+    document.process(&view_id, &DocEvent::Redo).unwrap();
+    assert!(!document.is_modified());
+
+    // a copy leaves the file and the unsaved changes alone
+    document
+      .process(&view_id, &DocEvent::InsertChar('2'))
+      .unwrap();
+    let copy = dir.join("copy.txt");
+    document.write_to(&copy).unwrap();
+    assert_eq!(std::fs::read_to_string(&copy).unwrap(), "12one");
+    assert_eq!(document.path(), Some(path.as_path()));
+    assert!(document.is_modified());
+
+    // a failed save as keeps the old file
+    assert!(document.save_as(dir.join("missing/file.txt")).is_err());
+    assert_eq!(document.path(), Some(path.as_path()));
+    document.save_as(&copy).unwrap();
+    assert_eq!(document.path(), Some(copy.as_path()));
+    assert!(!document.is_modified());
+  }
+
+  #[test]
+  fn test_encoding_round_trip() {
+    use {crate::util::encoding::FileEncoding, encoding_rs::WINDOWS_1252};
+
+    let dir = TempDir::new("document-encoding");
+    let path = dir.join("file.txt");
+    std::fs::write(&path, b"caf\xE9 cr\xE8me br\xFBl\xE9e\n").unwrap();
+
+    let mut document = Document::from_reader(&path).unwrap();
+    document.undo_dir = Some(dir.join("undo"));
+    let view_id = ViewId::default();
+    document.new_view(view_id);
+    assert_eq!(document.encoding(), FileEncoding::new(WINDOWS_1252));
+    assert_eq!(document.text(), "café crème brûlée\n");
+
+    document
+      .process(&view_id, &DocEvent::InsertChar('à'))
       .unwrap();
-    assert_eq!(document.cursor.get(&view_id), Some(&(0, 4)));
+    document.save().unwrap();
+    assert_eq!(
+      std::fs::read(&path).unwrap(),
+      b"\xE0caf\xE9 cr\xE8me br\xFBl\xE9e\n"
+    );
 
+    // reading it as UTF-8 instead shows replacement chars
+    document.reload(Some(FileEncoding::default())).unwrap();
+    assert!(document.text().to_string().starts_with("\u{FFFD}caf"));
+    assert!(!document.is_modified());
+  }
+
+  #[test]
+  fn test_sync_with_disk() {
+    let dir = TempDir::new("document-sync");
+    let path = dir.join("main.rs");
+    std::fs::write(&path, "fn main() {\n  run();\n}\n").unwrap();
+
+    let mut document = Document::from_reader(&path).unwrap();
+    let view_id = ViewId::default();
+    document.new_view(view_id);
     document
-      .process(&view_id, &DocEvent::MoveWordForward)
+      .selections
+      .insert(view_id, Selection::point((1, 2)));
+    assert_eq!(
+      document.sync_with_disk().unwrap(),
+      ExternalChange::Unchanged
+    );
+
+    // a clean document follows the file, the cursor stays on `run`
+    std::fs::write(&path, "// main\nfn main() {\n    run();\n}\n").unwrap();
+    assert_eq!(document.sync_with_disk().unwrap(), ExternalChange::Reloaded);
+    assert_eq!(document.text(), "// main\nfn main() {\n    run();\n}\n");
+    assert_eq!(document.cursor(&view_id), Some((2, 4)));
+    assert!(!document.is_modified());
+
+    // the reload can be undone
+    document.process(&view_id, &DocEvent::Undo).unwrap();
+    assert_eq!(document.text(), "fn main() {\n  run();\n}\n");
+    document.process(&view_id, &DocEvent::Redo).unwrap();
+
+    // a modified document is left alone
+    document
+      .process(&view_id, &DocEvent::InsertChar('x'))
       .unwrap();
-    assert_eq!(document.cursor.get(&view_id), Some(&(0, 8)));
+    std::fs::write(&path, "changed").unwrap();
+    assert_eq!(document.sync_with_disk().unwrap(), ExternalChange::Conflict);
+    assert_eq!(
+      document.sync_with_disk().unwrap(),
+      ExternalChange::Unchanged
+    );
+    assert!(document.text().to_string().contains("xrun"));
+  }
+
+  #[test]
+  fn test_save_without_path() {
+    let mut document = Document::from_str("text").unwrap();
+    assert!(matches!(document.save(), Err(DocumentError::NoPath)));
   }
 
   #[test]
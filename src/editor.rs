@@ -1,23 +1,163 @@
 use {
   crate::{
-    application::{Application, Plugin, PluginError, ProcessEvent},
-    document::{DocEvent, Document, DocumentError, DocumentId},
-    keymap::ViKeymap,
+    application::{Application, Plugin, PluginError, ProcessEvent, Waker},
+    command::{BufferRef, CommandError, ExCommand, TabMove},
+    config::Config,
+    document::{
+      DocEvent,
+      Document,
+      DocumentError,
+      DocumentId,
+      ExternalChange,
+      Operator,
+    },
+    gutter::{Gutter, GutterContext, LineNumbers},
+    history::persistent,
+    keymap::{KeymapEvent, Mode, ViKeymap},
+    layout::{Axis, Direction},
+    movement::{last_line, line_len, textobject::TextObject, Motion},
+    register::{Register, RegisterError, Registers, UNNAMED},
+    selection::{Position, Range, Selection, SelectionKind},
+    tab::Tabs,
+    text_layout::{self, Row, TextFormat, WrapFormat},
+    util::{
+      char::prev_grapheme_boundary,
+      encoding::{Encoding, FileEncoding},
+      line_endings::LineEnding,
+    },
     view::{View, ViewId},
+    watcher::FileWatcher,
+  },
+  crossterm::event::Event as TuiEvent,
+  ropey::RopeSlice,
+  slotmap::{SecondaryMap, SlotMap},
+  std::{
+    ffi::OsStr,
+    io::ErrorKind,
+    path::{Path, PathBuf},
   },
-  anyhow::Error as AnyError,
-  crossterm::event::{Event as TuiEvent, KeyCode},
-  slotmap::SlotMap,
   thiserror::Error,
-  tui::{buffer::Buffer as TuiBuffer, layout::Rect, style::Style},
+  tui::{
+    buffer::Buffer as TuiBuffer,
+    layout::Rect,
+    style::{Modifier, Style},
+  },
+  unicode_width::UnicodeWidthStr,
 };
 
 #[derive(Default)]
 pub struct Editor {
   pub views: SlotMap<ViewId, View>,
+  /// Tab pages with the views they show, views outside of the active one
+  /// are not drawn.
+  pub tabs: Tabs,
+  /// Where the layout of the active tab was last drawn.
+  area: Rect,
   pub documents: SlotMap<DocumentId, Document>,
-  pub active_view: Option<ViewId>,
+  /// Numbers of the documents in `:ls`, in the order they were opened.
+  buffer_numbers: SecondaryMap<DocumentId, usize>,
+  last_buffer_number: usize,
   pub keymap: ViKeymap, // TOOD: make this configurable
+  /// Message shown on the bottom line until the next key press.
+  pub status: Option<String>,
+  pub config: Config,
+  /// Columns left of the text of every view.
+  pub gutter: Gutter,
+  /// Text deleted, yanked, inserted or searched for, to put back later.
+  pub registers: Registers,
+  /// Text typed since insert mode was entered, for the `.` register.
+  inserted: String,
+  /// Wakes the application when an open file changed on disk.
+  waker: Option<Waker>,
+  file_watcher: Option<FileWatcher>,
+}
+
+/// Editor wide actions, not tied to a single document.
+pub enum EditorEvent {
+  /// A line typed on the `:` command line.
+  Command(String),
+  /// Scroll the active view sideways by a number of columns, left when
+  /// negative.
+  ScrollColumns(isize),
+  /// Scroll sideways to put the cursor at the left edge of the view.
+  ScrollCursorStart,
+  /// Scroll sideways to put the cursor at the right edge of the view.
+  ScrollCursorEnd,
+  /// Move the cursor `count` screen rows up, within a wrapped line if it
+  /// is one. `extend` keeps the anchor, in visual mode.
+  MoveRowUp {
+    count: usize,
+    extend: bool,
+  },
+  /// Move the cursor `count` screen rows down, within a wrapped line if it
+  /// is one.
+  MoveRowDown {
+    count: usize,
+    extend: bool,
+  },
+  Window(WindowEvent),
+  /// Make the tab after the active one active, `gt`.
+  NextTab,
+  /// Make the tab before the active one active, `gT`.
+  PreviousTab,
+  /// Apply an operator to the selections of the active view, keeping the
+  /// text it deleted or yanked in the named register or the default ones.
+  Operator(Operator, SelectionKind, Option<char>),
+  /// Apply an operator from the cursors of the active view to where a
+  /// motion taken a number of times moves them, like `d2w`.
+  OperatorMotion(Operator, Motion, usize, Option<char>),
+  /// Apply an operator to the text object at the cursors of the active
+  /// view, like `ci(`.
+  OperatorObject {
+    operator: Operator,
+    object: TextObject,
+    around: bool,
+    count: usize,
+    register: Option<char>,
+  },
+  /// Put the text of a register, the unnamed one without a name, a number
+  /// of times after the cursors of the active view or before them.
+  Put {
+    register: Option<char>,
+    before: bool,
+    count: usize,
+  },
+}
+
+/// `ctrl-w` commands, on the views in the layout.
+pub enum WindowEvent {
+  /// Show the active document in a new view above the active one, or left
+  /// of it for [`Axis::Vertical`].
+  Split(Axis),
+  /// Stop showing the active view.
+  Close,
+  /// Make the view next to the active one in a direction active.
+  Focus(Direction),
+  /// Make the next view active, the first after the last.
+  FocusNext,
+  /// Grow the active view by a number of rows along [`Axis::Horizontal`]
+  /// or columns along [`Axis::Vertical`], shrink it when negative.
+  Resize(Axis, isize),
+  /// Give all views the same size.
+  Equalize,
+}
+
+/// Screen rows of `line` in a view `width` columns wide, a single one
+/// unless lines are soft wrapped.
+fn line_rows(
+  line: RopeSlice,
+  format: &TextFormat,
+  config: &Config,
+  width: usize,
+) -> Vec<Row> {
+  if !config.soft_wrap {
+    return vec![Row::default()];
+  }
+  text_layout::wrap(line, format, &WrapFormat {
+    width,
+    indicator: &config.wrap_indicator,
+    preserve_indent: config.wrap_indent,
+  })
 }
 
 #[derive(Debug, Error)]
@@ -26,15 +166,105 @@ pub enum EditorError {
   ViewNotPresent,
   #[error("Trying to access a non-existent document")]
   DocumentNotPresent,
+  #[error("No write since last change (add ! to override)")]
+  UnsavedChanges,
+  #[error("Cannot close last window")]
+  LastView,
+  #[error("Cannot close last tab page")]
+  LastTab,
+  #[error("Read-only, add ! to write anyway")]
+  ReadOnly,
+  #[error("Buffer {0} does not exist")]
+  NoBuffer(usize),
+  #[error("No matching buffer for {0}")]
+  NoMatchingBuffer(String),
+  #[error("More than one match for {0}")]
+  AmbiguousBuffer(String),
   #[error(transparent)]
   IoError(#[from] std::io::Error),
   #[error(transparent)]
   DocumentError(#[from] DocumentError),
+  #[error(transparent)]
+  CommandError(#[from] CommandError),
+  #[error(transparent)]
+  RegisterError(#[from] RegisterError),
 }
 
-type EditorResult<T> = Result<T, EditorError>;
+pub type EditorResult<T> = Result<T, EditorError>;
+
+/// Name of a line ending in `:fileformat`.
+fn file_format_name(line_ending: LineEnding) -> &'static str {
+  match line_ending {
+    LineEnding::Crlf => "dos",
+    #[cfg(feature = "unicode-lines")]
+    LineEnding::CR => "mac",
+    _ => "unix",
+  }
+}
 
 impl Editor {
+  pub fn with_waker(waker: Waker) -> Self {
+    Self {
+      waker: Some(waker),
+      ..Default::default()
+    }
+  }
+
+  /// Start watching `path` for changes by other programs. Without a waker
+  /// nobody would pick the changes up, so nothing is watched.
+  fn watch(&mut self, path: &std::path::Path) -> notify::Result<()> {
+    let Some(waker) = &self.waker else {
+      return Ok(());
+    };
+    let watcher = match &mut self.file_watcher {
+      Some(watcher) => watcher,
+      None => {
+        let waker = waker.clone();
+        self
+          .file_watcher
+          .insert(FileWatcher::new(move || waker.wake())?)
+      }
+    };
+    watcher.watch(path)
+  }
+
+  /// Reload the documents whose files changed on disk.
+  fn check_file_changes(&mut self) {
+    let Some(watcher) = &self.file_watcher else {
+      return;
+    };
+    let changed = watcher.changed_files();
+    if changed.is_empty() {
+      return;
+    }
+
+    for document in self.documents.values_mut() {
+      let Some(path) = document.path() else {
+        continue;
+      };
+      let is_changed = path
+        .canonicalize()
+        .is_ok_and(|path| changed.contains(&path));
+      if !is_changed {
+        continue;
+      }
+
+      let name = path.display().to_string();
+      match document.sync_with_disk() {
+        Ok(ExternalChange::Unchanged) => {}
+        Ok(ExternalChange::Reloaded) => {
+          self.status = Some(format!("\"{name}\" reloaded"));
+        }
+        Ok(ExternalChange::Conflict) => {
+          self.status = Some(format!(
+            "\"{name}\" changed on disk, unsaved changes are kept"
+          ));
+        }
+        Err(e) => tracing::warn!("Failed to reload {}: {}", name, e),
+      }
+    }
+  }
+
   pub fn create_view(
     &mut self,
     document_id: DocumentId,
@@ -48,15 +278,347 @@ impl Editor {
     document.new_view(view_id);
 
     // set active view if none is set
-    if self.active_view.is_none() {
-      self.active_view = Some(view_id);
+    let tab = self.tabs.active_mut();
+    if tab.active_view.is_none() {
+      tab.active_view = Some(view_id);
+    }
+    // the first view fills the tab, others are shown by splitting
+    if tab.layout.is_empty() {
+      tab.layout.set_root(view_id);
     }
 
     Ok(view_id)
   }
 
+  /// Show `document_id` in `view_id`. The selections of the view in the
+  /// document are kept for when it is shown again.
+  pub fn show_document(
+    &mut self,
+    view_id: ViewId,
+    document_id: DocumentId,
+  ) -> EditorResult<()> {
+    let document = self
+      .documents
+      .get_mut(document_id)
+      .ok_or(EditorError::DocumentNotPresent)?;
+    if !document.selections.contains_key(&view_id) {
+      document.new_view(view_id);
+    }
+    let view = self
+      .views
+      .get_mut(view_id)
+      .ok_or(EditorError::ViewNotPresent)?;
+    *view = View {
+      area: view.area,
+      ..View::new(document_id)
+    };
+    Ok(())
+  }
+
+  /// Split the active view, the new view shows the same document at the
+  /// same place and becomes active.
+  pub fn split_view(&mut self, axis: Axis) -> EditorResult<ViewId> {
+    let (view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    let new_view_id = self.create_view(document_id)?;
+    let view = &self.views[view_id];
+    let (offset, first_row) = (view.offset, view.first_row);
+    let new_view = &mut self.views[new_view_id];
+    new_view.offset = offset;
+    new_view.first_row = first_row;
+
+    let document = &mut self.documents[document_id];
+    let selection = document.selections[&view_id].clone();
+    document.selections.insert(new_view_id, selection);
+
+    let tab = self.tabs.active_mut();
+    tab.layout.split(view_id, new_view_id, axis);
+    tab.active_view = Some(new_view_id);
+    Ok(new_view_id)
+  }
+
+  /// Drop `view_id` and its selections in the documents it showed.
+  /// Documents no view showed are released, unless they have unsaved
+  /// changes.
+  fn remove_view(&mut self, view_id: ViewId) {
+    self.views.remove(view_id);
+    let mut unused = Vec::new();
+    for (document_id, document) in &mut self.documents {
+      if document.selections.remove(&view_id).is_none() {
+        continue;
+      }
+      if document.selections.is_empty() && !document.is_modified() {
+        unused.push(document_id);
+      }
+    }
+    for document_id in unused {
+      self.release_document(document_id);
+    }
+  }
+
+  /// Drop a document no view shows anymore.
+  fn release_document(&mut self, document_id: DocumentId) {
+    let Some(mut document) = self.documents.remove(document_id) else {
+      return;
+    };
+    self.buffer_numbers.remove(document_id);
+    if let Err(e) = document.write_undo_history() {
+      tracing::warn!("Failed to write undo history: {}", e);
+    }
+    if let (Some(watcher), Some(path)) =
+      (&mut self.file_watcher, document.path())
+    {
+      if let Err(e) = watcher.unwatch(path) {
+        tracing::warn!("Failed to unwatch {}: {}", path.display(), e);
+      }
+    }
+  }
+
+  /// Add `document` as the last buffer.
+  pub fn add_document(&mut self, mut document: Document) -> DocumentId {
+    document.undo_dir.clone_from(&self.config.undo_dir);
+    let document_id = self.documents.insert(document);
+    self.last_buffer_number += 1;
+    self
+      .buffer_numbers
+      .insert(document_id, self.last_buffer_number);
+    document_id
+  }
+
+  /// The documents by buffer number.
+  pub fn buffers(&self) -> Vec<DocumentId> {
+    let mut buffers: Vec<_> = self.documents.keys().collect();
+    buffers.sort_by_key(|document_id| self.buffer_number(*document_id));
+    buffers
+  }
+
+  pub fn buffer_number(&self, document_id: DocumentId) -> usize {
+    self
+      .buffer_numbers
+      .get(document_id)
+      .copied()
+      .unwrap_or_default()
+  }
+
+  /// The document `buffer` refers to, the active one for `None`.
+  fn find_buffer(
+    &self,
+    buffer: Option<&BufferRef>,
+  ) -> EditorResult<DocumentId> {
+    let name = match buffer {
+      None => {
+        let (_view_id, document_id) =
+          self.active_view().ok_or(EditorError::ViewNotPresent)?;
+        return Ok(document_id);
+      }
+      Some(BufferRef::Number(number)) => {
+        return self
+          .buffers()
+          .into_iter()
+          .find(|document_id| self.buffer_number(*document_id) == *number)
+          .ok_or(EditorError::NoBuffer(*number));
+      }
+      Some(BufferRef::Name(name)) => name,
+    };
+    // a file named exactly that goes before files that only contain it
+    let matching = |exact: bool| -> Vec<_> {
+      self
+        .buffers()
+        .into_iter()
+        .filter(|document_id| {
+          self.documents[*document_id].path().is_some_and(|path| {
+            if exact {
+              path == Path::new(name)
+                || path.file_name() == Some(OsStr::new(name))
+            } else {
+              path.to_string_lossy().contains(name.as_str())
+            }
+          })
+        })
+        .collect()
+    };
+    let exact = matching(true);
+    let matches = if exact.is_empty() {
+      matching(false)
+    } else {
+      exact
+    };
+    match matches[..] {
+      [document_id] => Ok(document_id),
+      [] => Err(EditorError::NoMatchingBuffer(name.clone())),
+      _ => Err(EditorError::AmbiguousBuffer(name.clone())),
+    }
+  }
+
+  /// Show the buffer `offset` places after the active one in the active
+  /// view, counting around.
+  fn cycle_buffer(&mut self, offset: isize) -> EditorResult<()> {
+    let (view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    let buffers = self.buffers();
+    let index = buffers
+      .iter()
+      .position(|id| *id == document_id)
+      .unwrap_or_default() as isize;
+    let next = (index + offset).rem_euclid(buffers.len() as isize);
+    self.show_document(view_id, buffers[next as usize])
+  }
+
+  /// Close the document `buffer` refers to. Views showing it move on to
+  /// the next buffer, or to a new empty one when it was the only one.
+  fn delete_buffer(
+    &mut self,
+    force: bool,
+    buffer: Option<BufferRef>,
+  ) -> EditorResult<()> {
+    let document_id = self.find_buffer(buffer.as_ref())?;
+    if !force && self.documents[document_id].is_modified() {
+      return Err(EditorError::UnsavedChanges);
+    }
+
+    let buffers = self.buffers();
+    let index = buffers
+      .iter()
+      .position(|id| *id == document_id)
+      .unwrap_or_default();
+    let next = match buffers.len() {
+      1 => self.create_document(),
+      len => buffers[(index + 1) % len],
+    };
+    let showing: Vec<_> = self
+      .views
+      .iter()
+      .filter(|(_, view)| view.document_id == document_id)
+      .map(|(view_id, _)| view_id)
+      .collect();
+    for view_id in showing {
+      self.show_document(view_id, next)?;
+    }
+    self.release_document(document_id);
+    Ok(())
+  }
+
+  /// The lines of `:ls`: the number of every document, `%` for the active
+  /// one, `a` when a view shows it and `h` when hidden, `+` for unsaved
+  /// changes, its name and the line of its cursor.
+  fn list_buffers(&self) -> String {
+    let active = self.active_view();
+    let lines: Vec<_> = self
+      .buffers()
+      .into_iter()
+      .map(|document_id| {
+        let document = &self.documents[document_id];
+        let current = match active {
+          Some((_, id)) if id == document_id => '%',
+          _ => ' ',
+        };
+        let shown = self
+          .views
+          .values()
+          .any(|view| view.document_id == document_id);
+        let modified = if document.is_modified() { '+' } else { ' ' };
+        let name = document.path().map_or_else(
+          || "[No Name]".to_string(),
+          |path| path.display().to_string(),
+        );
+        let line = active
+          .and_then(|(view_id, _)| document.selections.get(&view_id))
+          .or_else(|| document.selections.values().next())
+          .map_or(0, |selection| selection.cursor().0);
+        format!(
+          "{:>3} {current}{} {modified} \"{}\" line {}",
+          self.buffer_number(document_id),
+          if shown { 'a' } else { 'h' },
+          name,
+          line + 1
+        )
+      })
+      .collect();
+    lines.join("\n")
+  }
+
+  /// Close the active view, the view that takes its space becomes active.
+  /// Closing the last view of a tab closes the tab.
+  fn close_view(&mut self) -> EditorResult<()> {
+    let (view_id, _document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    if self.tabs.active().layout.views().len() < 2 {
+      return match self.tabs.count() {
+        1 => Err(EditorError::LastView),
+        _ => self.close_tab(),
+      };
+    }
+
+    let tab = self.tabs.active_mut();
+    tab.active_view = tab.layout.remove(view_id);
+    self.remove_view(view_id);
+    Ok(())
+  }
+
+  /// Open a tab showing the document at `path`, or a new one without a
+  /// path.
+  fn new_tab(&mut self, path: Option<PathBuf>) -> EditorResult<()> {
+    let document_id = match path {
+      Some(path) => self.load(path, None)?,
+      None => self.create_document(),
+    };
+    self.tabs.open();
+    self.create_view(document_id)?;
+    Ok(())
+  }
+
+  /// Close the active tab and its views, the documents stay open.
+  fn close_tab(&mut self) -> EditorResult<()> {
+    let tab = self.tabs.close().ok_or(EditorError::LastTab)?;
+    for view_id in tab.layout.views() {
+      self.remove_view(view_id);
+    }
+    Ok(())
+  }
+
+  /// Move the active tab as `:tabmove` does.
+  fn move_tab(&mut self, to: TabMove) {
+    let index = self.tabs.index();
+    let index = match to {
+      TabMove::To(index) => index,
+      TabMove::By(offset) => index.saturating_add_signed(offset),
+      TabMove::Last => usize::MAX,
+    };
+    self.tabs.move_to(index);
+  }
+
+  fn process_window_event(&mut self, event: WindowEvent) -> EditorResult<()> {
+    let tab = self.tabs.active_mut();
+    let view_id = tab.active_view.ok_or(EditorError::ViewNotPresent)?;
+    match event {
+      WindowEvent::Split(axis) => {
+        self.split_view(axis)?;
+      }
+      WindowEvent::Close => self.close_view()?,
+      WindowEvent::Focus(direction) => {
+        if let Some(next) = tab.layout.neighbour(view_id, direction, self.area)
+        {
+          tab.active_view = Some(next);
+        }
+      }
+      WindowEvent::FocusNext => {
+        let views = tab.layout.views();
+        let next = views
+          .iter()
+          .position(|id| *id == view_id)
+          .map_or(0, |i| (i + 1) % views.len());
+        tab.active_view = views.get(next).copied().or(tab.active_view);
+      }
+      WindowEvent::Resize(axis, delta) => {
+        tab.layout.resize(view_id, axis, delta, self.area);
+      }
+      WindowEvent::Equalize => tab.layout.equalize(),
+    }
+    Ok(())
+  }
+
   pub fn active_view(&self) -> Option<(ViewId, DocumentId)> {
-    self.active_view.map(|view_id| {
+    self.tabs.active().active_view.map(|view_id| {
       let document_id = self
         .views
         .get(view_id)
@@ -66,22 +628,960 @@ impl Editor {
     })
   }
 
+  /// Whether the tab line is drawn at the top, only with several tabs.
+  pub fn tabline_visible(&self) -> bool {
+    self.tabs.count() > 1
+  }
+
   pub fn create_document(&mut self) -> DocumentId {
-    self.documents.insert(Document::default())
+    self.add_document(Document::default())
+  }
+
+  fn process_document_event(&mut self, event: &DocEvent) -> EditorResult<()> {
+    let (view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    let document = self
+      .documents
+      .get_mut(document_id)
+      .ok_or(EditorError::DocumentNotPresent)?;
+    document.process(&view_id, event)?;
+    Ok(())
+  }
+
+  fn process_editor_event(
+    &mut self,
+    app: &mut Application,
+    event: EditorEvent,
+  ) {
+    match event {
+      EditorEvent::Command(command) => {
+        let result = command
+          .parse()
+          .map_err(EditorError::from)
+          .and_then(|command| self.execute(app, command));
+        if let Err(e) = result {
+          self.status = Some(e.to_string());
+        }
+      }
+      EditorEvent::ScrollColumns(columns) => self.scroll_columns(columns),
+      EditorEvent::ScrollCursorStart => self.scroll_to_cursor(false),
+      EditorEvent::ScrollCursorEnd => self.scroll_to_cursor(true),
+      EditorEvent::Window(event) => {
+        if let Err(e) = self.process_window_event(event) {
+          self.status = Some(e.to_string());
+        }
+      }
+      EditorEvent::NextTab => self.tabs.select_next(),
+      EditorEvent::PreviousTab => self.tabs.select_previous(),
+      EditorEvent::MoveRowUp { count, extend } => {
+        self.move_row(true, count, extend)
+      }
+      EditorEvent::MoveRowDown { count, extend } => {
+        self.move_row(false, count, extend)
+      }
+      EditorEvent::Operator(operator, kind, register) => {
+        if let Err(e) = self.operate(operator, kind, register) {
+          self.status = Some(e.to_string());
+        }
+      }
+      EditorEvent::OperatorMotion(operator, motion, count, register) => {
+        if let Err(e) = self.operate_motion(operator, motion, count, register) {
+          self.status = Some(e.to_string());
+        }
+      }
+      EditorEvent::OperatorObject {
+        operator,
+        object,
+        around,
+        count,
+        register,
+      } => {
+        let result =
+          self.operate_object(operator, object, around, count, register);
+        if let Err(e) = result {
+          self.status = Some(e.to_string());
+        }
+      }
+      EditorEvent::Put {
+        register,
+        before,
+        count,
+      } => {
+        if let Err(e) = self.put(register, before, count) {
+          self.status = Some(e.to_string());
+        }
+      }
+    }
+  }
+
+  fn operate(
+    &mut self,
+    operator: Operator,
+    kind: SelectionKind,
+    register: Option<char>,
+  ) -> EditorResult<()> {
+    let (view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    Self::check_register(operator, register)?;
+    let values =
+      self.documents[document_id].operate(&view_id, operator, kind)?;
+    self.keep(operator, kind, values, register)
+  }
+
+  fn operate_motion(
+    &mut self,
+    operator: Operator,
+    motion: Motion,
+    count: usize,
+    register: Option<char>,
+  ) -> EditorResult<()> {
+    let (view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    Self::check_register(operator, register)?;
+    let values = self.documents[document_id]
+      .operate_motion(&view_id, operator, motion, count)?;
+    let kind = if motion.is_linewise() {
+      SelectionKind::Linewise
+    } else {
+      SelectionKind::Charwise
+    };
+    self.keep(operator, kind, values, register)
+  }
+
+  fn operate_object(
+    &mut self,
+    operator: Operator,
+    object: TextObject,
+    around: bool,
+    count: usize,
+    register: Option<char>,
+  ) -> EditorResult<()> {
+    let (view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    Self::check_register(operator, register)?;
+    let values = self.documents[document_id]
+      .operate_object(&view_id, operator, object, around, count)?;
+    let kind = if object.is_linewise() {
+      SelectionKind::Linewise
+    } else {
+      SelectionKind::Charwise
+    };
+    self.keep(operator, kind, values, register)
+  }
+
+  /// Fail before the text changes when an operator would keep it in a
+  /// register that cannot be written.
+  fn check_register(
+    operator: Operator,
+    register: Option<char>,
+  ) -> EditorResult<()> {
+    match register {
+      Some(name) if Self::keeps_text(operator) => {
+        Ok(Registers::check_writable(name)?)
+      }
+      _ => Ok(()),
+    }
+  }
+
+  fn keeps_text(operator: Operator) -> bool {
+    matches!(
+      operator,
+      Operator::Delete | Operator::Change | Operator::Yank
+    )
+  }
+
+  /// Put the text an operator deleted or yanked in the register `name`, or
+  /// in the default ones without a name. Nothing is kept when the operator
+  /// found no text.
+  fn keep(
+    &mut self,
+    operator: Operator,
+    kind: SelectionKind,
+    values: Vec<String>,
+    name: Option<char>,
+  ) -> EditorResult<()> {
+    if !Self::keeps_text(operator) || values.iter().all(String::is_empty) {
+      return Ok(());
+    }
+    let register = Register { kind, values };
+    if operator == Operator::Yank {
+      self.registers.yank(name, register)?;
+    } else {
+      self.registers.delete(name, register)?;
+    }
+    Ok(())
+  }
+
+  /// What register `name` holds, `%` is the file name of the active
+  /// document.
+  pub fn register(&self, name: char) -> Option<Register> {
+    if name == '%' {
+      let (_view_id, document_id) = self.active_view()?;
+      let path = self.documents[document_id].path()?;
+      return Some(Register::charwise(path.display().to_string()));
+    }
+    self.registers.get(name).cloned()
+  }
+
+  /// Put the text of register `name`, the unnamed one without a name,
+  /// `count` times at the cursors of the active view.
+  pub fn put(
+    &mut self,
+    name: Option<char>,
+    before: bool,
+    count: usize,
+  ) -> EditorResult<()> {
+    let name = name.unwrap_or(UNNAMED);
+    let register = self.register(name).ok_or(RegisterError::Empty(name))?;
+    let (view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    self.documents[document_id].put(&view_id, &register, before, count)?;
+    Ok(())
+  }
+
+  /// Follow what is typed in insert mode for the `.` register. `inserting`
+  /// tells whether insert mode was on before the key, edits made on the
+  /// way into insert mode, like the new line of `o`, are not typed text.
+  fn record_insert(&mut self, inserting: bool, event: &DocEvent) {
+    if !inserting || self.keymap.mode() != Mode::Insert {
+      return;
+    }
+    match event {
+      DocEvent::InsertChar(c) => self.inserted.push(*c),
+      DocEvent::InsertNewline => self.inserted.push('\n'),
+      DocEvent::InsertTab => self.inserted.push('\t'),
+      DocEvent::Paste(text) => self.inserted.push_str(text),
+      DocEvent::DeleteCharBackward => {
+        self.inserted.pop();
+      }
+      _ => {}
+    }
+  }
+
+  /// Move the cursors of the active view `count` screen rows up, or down,
+  /// keeping their screen column. Without soft wrap that is a line.
+  /// `extend` keeps the anchors where they are.
+  fn move_row(&mut self, up: bool, count: usize, extend: bool) {
+    let Some((view_id, document_id)) = self.active_view() else {
+      return;
+    };
+    let width = self.views[view_id].area.width as usize;
+    let document = &self.documents[document_id];
+    let text = document.text().slice(..);
+    let format = document.text_format;
+    let rows_of =
+      |line| line_rows(text.line(line), &format, &self.config, width);
+
+    let move_head = |(line, pos): Position| {
+      let rows = rows_of(line);
+      let index = text_layout::row_at(&rows, pos);
+      let column = text_layout::visual_column(text.line(line), pos, &format);
+      let screen_column = rows[index].screen_column(column);
+
+      let (line, rows, index) = if up {
+        if index > 0 {
+          (line, rows, index - 1)
+        } else if line > 0 {
+          let rows = rows_of(line - 1);
+          let index = rows.len() - 1;
+          (line - 1, rows, index)
+        } else {
+          return None;
+        }
+      } else if index + 1 < rows.len() {
+        (line, rows, index + 1)
+      } else if line < last_line(&text) {
+        (line + 1, rows_of(line + 1), 0)
+      } else {
+        return None;
+      };
+
+      let row = rows[index];
+      let column = row.column + screen_column.saturating_sub(row.indent);
+      let mut pos = text_layout::char_offset(text.line(line), column, &format);
+      // stay on the row when it is shorter
+      if let Some(next) = rows.get(index + 1) {
+        if pos >= next.start {
+          pos = prev_grapheme_boundary(&text.line(line), next.start);
+        }
+      }
+      Some((line, pos))
+    };
+    let selection = document.selections[&view_id].clone().transform(|range| {
+      // as many rows as there are, up to `count`
+      let head =
+        std::iter::successors(Some(range.head), |head| move_head(*head))
+          .take(count + 1)
+          .last()
+          .unwrap_or(range.head);
+      if head == range.head {
+        range
+      } else {
+        range.put_head(head, extend)
+      }
+    });
+    self.documents[document_id]
+      .selections
+      .insert(view_id, selection);
+  }
+
+  /// Put the cursor of the active view on `(line, pos)`, clamped to the
+  /// text.
+  pub fn set_cursor(
+    &mut self,
+    (line, pos): (usize, usize),
+  ) -> EditorResult<()> {
+    let (view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    let document = &mut self.documents[document_id];
+    let text = document.text().slice(..);
+    let line = line.min(last_line(&text));
+    let pos = pos.min(line_len(&text, line));
+    document
+      .selections
+      .insert(view_id, Selection::point((line, pos)));
+    Ok(())
+  }
+
+  /// Side scroll margin of the active view, never over half its width.
+  fn side_margin(&self, width: usize) -> usize {
+    self.config.sidescrolloff.min(width.saturating_sub(1) / 2)
+  }
+
+  /// Scroll the active view sideways. The primary cursor is moved along
+  /// when it would end up outside the columns shown.
+  fn scroll_columns(&mut self, columns: isize) {
+    let Some((view_id, document_id)) = self.active_view() else {
+      return;
+    };
+    if self.config.soft_wrap {
+      return;
+    }
+    let width = self.views[view_id].area.width as usize;
+    let margin = self.side_margin(width);
+    let view = &mut self.views[view_id];
+    view.offset.1 = view.offset.1.saturating_add_signed(columns);
+    let first = view.offset.1 + margin;
+    let last = (view.offset.1 + width).saturating_sub(margin + 1);
+
+    let document = &mut self.documents[document_id];
+    let selection = &document.selections[&view_id];
+    let (line, pos) = selection.cursor();
+    let text = document.text().line(line);
+    let format = document.text_format;
+    let column = text_layout::visual_column(text, pos, &format);
+    if (first..=last).contains(&column) {
+      view.scrolled_at = Some((line, pos));
+      return;
+    }
+
+    // the nearest grapheme that is fully in view, if the line reaches it
+    let mut graphemes = text_layout::graphemes(text, &format);
+    let target = if column < first {
+      graphemes.find(|grapheme| grapheme.column >= first)
+    } else {
+      graphemes
+        .take_while(|grapheme| grapheme.column + grapheme.width - 1 <= last)
+        .last()
+    };
+    if let Some(grapheme) = target {
+      let selection = selection
+        .clone()
+        .with_primary(Range::point((line, grapheme.offset)));
+      document.selections.insert(view_id, selection);
+    }
+    // a line too short to reach the view leaves the cursor out of sight
+    view.scrolled_at = Some(document.selections[&view_id].cursor());
+  }
+
+  /// Scroll the active view sideways to put the cursor at its left edge, or
+  /// its right edge with `at_end`, minding the side scroll margin.
+  fn scroll_to_cursor(&mut self, at_end: bool) {
+    let Some((view_id, document_id)) = self.active_view() else {
+      return;
+    };
+    if self.config.soft_wrap {
+      return;
+    }
+    let document = &self.documents[document_id];
+    let (line, pos) = document.selections[&view_id].cursor();
+    let column = text_layout::visual_column(
+      document.text().line(line),
+      pos,
+      &document.text_format,
+    );
+
+    let width = self.views[view_id].area.width as usize;
+    let margin = self.side_margin(width);
+    self.views[view_id].offset.1 = if at_end {
+      (column + margin + 1).saturating_sub(width)
+    } else {
+      column.saturating_sub(margin)
+    };
+  }
+
+  fn quit(&mut self, app: &mut Application, force: bool) -> EditorResult<()> {
+    // like vim only the active view closes while there are others
+    if self.tabs.count() > 1 || self.tabs.active().layout.views().len() > 1 {
+      return self.close_view();
+    }
+    if !force && self.documents.values().any(Document::is_modified) {
+      return Err(EditorError::UnsavedChanges);
+    }
+
+    for document in self.documents.values_mut() {
+      if let Err(e) = document.write_undo_history() {
+        tracing::warn!("Failed to write undo history: {}", e);
+      }
+    }
+    if let Some(undo_dir) = &self.config.undo_dir {
+      let pruned =
+        persistent::prune_undo_files(undo_dir, persistent::MAX_UNDO_FILE_AGE);
+      if let Err(e) = pruned {
+        tracing::warn!("Failed to prune undo files: {}", e);
+      }
+    }
+    if let Err(e) = app.quit() {
+      tracing::error!("Failed to quit: {}", e);
+    }
+    Ok(())
+  }
+
+  /// Save the active document, in another encoding when `encoding` is
+  /// given. Given a `path`, a copy is written there instead, unless the
+  /// document has no file yet. A read-only document is only written to its
+  /// own file when forced.
+  fn write(
+    &mut self,
+    force: bool,
+    path: Option<PathBuf>,
+    encoding: Option<&'static Encoding>,
+  ) -> EditorResult<()> {
+    let (_view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    let document = self
+      .documents
+      .get_mut(document_id)
+      .ok_or(EditorError::DocumentNotPresent)?;
+    match path {
+      Some(path) if document.path().is_some() => {
+        let previous_encoding = document.encoding();
+        if let Some(encoding) = encoding {
+          document.set_encoding(FileEncoding::new(encoding));
+        }
+        let written = document.write_to(&path);
+        document.set_encoding(previous_encoding);
+        written?;
+        self.status = Some(format!("\"{}\" written", path.display()));
+        Ok(())
+      }
+      Some(path) => self.save(Some(path), encoding),
+      None if document.read_only && !force => Err(EditorError::ReadOnly),
+      None => self.save(None, encoding),
+    }
+  }
+
+  /// Save the active document to its own file, or to `path` which becomes
+  /// its file, in another encoding when `encoding` is given.
+  fn save(
+    &mut self,
+    path: Option<PathBuf>,
+    encoding: Option<&'static Encoding>,
+  ) -> EditorResult<()> {
+    let (_view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    let document = self
+      .documents
+      .get_mut(document_id)
+      .ok_or(EditorError::DocumentNotPresent)?;
+    let previous_encoding = document.encoding();
+    if let Some(encoding) = encoding {
+      document.set_encoding(FileEncoding::new(encoding));
+    }
+    let old_path = document.path().map(PathBuf::from);
+    let saved = match path {
+      Some(path) => document.save_as(path),
+      None => document.save(),
+    };
+    if let Err(e) = saved {
+      document.set_encoding(previous_encoding);
+      return Err(e.into());
+    }
+    let Some(path) = document.path().map(PathBuf::from) else {
+      return Ok(());
+    };
+    self.status = Some(format!("\"{}\" written", path.display()));
+    if old_path.as_ref() == Some(&path) {
+      return Ok(());
+    }
+    // the old file is no longer the document's to reload
+    if let (Some(watcher), Some(old_path)) = (&mut self.file_watcher, old_path)
+    {
+      if let Err(e) = watcher.unwatch(&old_path) {
+        tracing::warn!("Failed to unwatch {}: {}", old_path.display(), e);
+      }
+    }
+    if let Err(e) = self.watch(&path) {
+      tracing::warn!("Failed to watch {}: {}", path.display(), e);
+    }
+    Ok(())
+  }
+
+  /// Open `path`, or read the active document again when there is none.
+  fn edit(
+    &mut self,
+    force: bool,
+    path: Option<PathBuf>,
+    encoding: Option<&'static Encoding>,
+  ) -> EditorResult<()> {
+    let encoding = encoding.map(FileEncoding::new);
+    if let Some(path) = path {
+      self.open_with_encoding(path, encoding)?;
+      return Ok(());
+    }
+
+    let (_view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    let document = self
+      .documents
+      .get_mut(document_id)
+      .ok_or(EditorError::DocumentNotPresent)?;
+    if !force && document.is_modified() {
+      return Err(EditorError::UnsavedChanges);
+    }
+    document.reload(encoding)?;
+    if let Some(path) = document.path() {
+      self.status = Some(format!(
+        "\"{}\" read as {}",
+        path.display(),
+        document.encoding().name()
+      ));
+    }
+    Ok(())
+  }
+
+  /// Convert the line endings of the active document and show the ones it
+  /// uses.
+  fn file_format(
+    &mut self,
+    line_ending: Option<LineEnding>,
+  ) -> EditorResult<()> {
+    let (_view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    let document = self
+      .documents
+      .get_mut(document_id)
+      .ok_or(EditorError::DocumentNotPresent)?;
+
+    if let Some(line_ending) = line_ending {
+      document.convert_line_endings(line_ending)?;
+    }
+    self.status = Some(format!(
+      "fileformat={}",
+      file_format_name(document.line_ending())
+    ));
+    Ok(())
+  }
+
+  /// Set the tab width of the active document and show it.
+  fn tab_stop(&mut self, width: Option<usize>) -> EditorResult<()> {
+    let (_view_id, document_id) =
+      self.active_view().ok_or(EditorError::ViewNotPresent)?;
+    let document = self
+      .documents
+      .get_mut(document_id)
+      .ok_or(EditorError::DocumentNotPresent)?;
+
+    if let Some(width) = width {
+      document.text_format.tab_width = width;
+    }
+    self.status = Some(format!("tabstop={}", document.text_format.tab_width));
+    Ok(())
+  }
+
+  /// Set the scroll margin around the cursor and show it.
+  fn scroll_off(&mut self, lines: Option<usize>) {
+    if let Some(lines) = lines {
+      self.config.scrolloff = lines;
+    }
+    self.status = Some(format!("scrolloff={}", self.config.scrolloff));
+  }
+
+  /// Turn soft wrap on or off. Views start over at their left edge, the
+  /// rows they were scrolled to are different now.
+  fn set_wrap(&mut self, wrap: bool) {
+    self.config.soft_wrap = wrap;
+    for view in self.views.values_mut() {
+      view.offset.1 = 0;
+      view.first_row = 0;
+    }
+  }
+
+  /// Set the scroll margin beside the cursor and show it.
+  fn side_scroll_off(&mut self, columns: Option<usize>) {
+    if let Some(columns) = columns {
+      self.config.sidescrolloff = columns;
+    }
+    self.status = Some(format!("sidescrolloff={}", self.config.sidescrolloff));
+  }
+
+  /// Run a command from the `:` command line.
+  pub fn execute(
+    &mut self,
+    app: &mut Application,
+    command: ExCommand,
+  ) -> EditorResult<()> {
+    match command {
+      ExCommand::Quit { force } => self.quit(app, force),
+      ExCommand::Write {
+        force,
+        path,
+        encoding,
+      } => self.write(force, path, encoding),
+      ExCommand::SaveAs { path, encoding } => self.save(Some(path), encoding),
+      ExCommand::Edit {
+        force,
+        path,
+        encoding,
+      } => self.edit(force, path, encoding),
+      ExCommand::WriteQuit => {
+        self.write(false, None, None)?;
+        self.quit(app, false)
+      }
+      ExCommand::Undo => self.process_document_event(&DocEvent::Undo),
+      ExCommand::Redo => self.process_document_event(&DocEvent::Redo),
+      ExCommand::Earlier(kind) => {
+        self.process_document_event(&DocEvent::Earlier(kind))
+      }
+      ExCommand::Later(kind) => {
+        self.process_document_event(&DocEvent::Later(kind))
+      }
+      ExCommand::FileFormat(line_ending) => self.file_format(line_ending),
+      ExCommand::TabStop(width) => self.tab_stop(width),
+      ExCommand::ScrollOff(lines) => {
+        self.scroll_off(lines);
+        Ok(())
+      }
+      ExCommand::SideScrollOff(columns) => {
+        self.side_scroll_off(columns);
+        Ok(())
+      }
+      ExCommand::Wrap(wrap) => {
+        self.set_wrap(wrap);
+        Ok(())
+      }
+      ExCommand::ShowBreak(indicator) => {
+        self.config.wrap_indicator = indicator;
+        Ok(())
+      }
+      ExCommand::BreakIndent(indent) => {
+        self.config.wrap_indent = indent;
+        Ok(())
+      }
+      ExCommand::TabNew(path) => self.new_tab(path),
+      ExCommand::TabClose => self.close_tab(),
+      ExCommand::TabNext => {
+        self.tabs.select_next();
+        Ok(())
+      }
+      ExCommand::TabPrevious => {
+        self.tabs.select_previous();
+        Ok(())
+      }
+      ExCommand::TabMove(to) => {
+        self.move_tab(to);
+        Ok(())
+      }
+      ExCommand::Buffers => {
+        self.status = Some(self.list_buffers());
+        Ok(())
+      }
+      ExCommand::BufferNext => self.cycle_buffer(1),
+      ExCommand::BufferPrevious => self.cycle_buffer(-1),
+      ExCommand::Buffer(buffer) => {
+        let document_id = self.find_buffer(Some(&buffer))?;
+        let (view_id, _) =
+          self.active_view().ok_or(EditorError::ViewNotPresent)?;
+        self.show_document(view_id, document_id)
+      }
+      ExCommand::BufferDelete { force, buffer } => {
+        self.delete_buffer(force, buffer)
+      }
+      ExCommand::Number(number) => {
+        let relative = self.config.line_numbers.relative();
+        self.config.line_numbers = LineNumbers::from_flags(number, relative);
+        Ok(())
+      }
+      ExCommand::RelativeNumber(relative) => {
+        let number = self.config.line_numbers.number();
+        self.config.line_numbers = LineNumbers::from_flags(number, relative);
+        Ok(())
+      }
+    }
+  }
+
+  /// The open document of the file at `path`.
+  pub fn find_document(&self, path: &std::path::Path) -> Option<DocumentId> {
+    let canonical =
+      |path: &std::path::Path| path.canonicalize().unwrap_or(path.into());
+    let path = canonical(path);
+    self
+      .documents
+      .iter()
+      .find(|(_, document)| {
+        document
+          .path()
+          .is_some_and(|other| canonical(other) == path)
+      })
+      .map(|(document_id, _)| document_id)
+  }
+
+  /// Read `path` into a new document, in `encoding` or a guessed one. A file
+  /// that is open already keeps its document, read again when given an
+  /// encoding. A file that does not exist yet gets an empty document and a
+  /// directory a listing of its entries.
+  pub fn load(
+    &mut self,
+    path: impl AsRef<std::path::Path>,
+    encoding: Option<FileEncoding>,
+  ) -> EditorResult<DocumentId> {
+    let path = path.as_ref();
+    if let Some(document_id) = self.find_document(path) {
+      let document = &mut self.documents[document_id];
+      if encoding.is_some() {
+        if document.is_modified() {
+          return Err(EditorError::UnsavedChanges);
+        }
+        document.reload(encoding)?;
+      }
+      return Ok(document_id);
+    }
+
+    if path.is_dir() {
+      return Ok(self.add_document(Document::from_directory(path)?));
+    }
+    // load path into a rope
+    let document = match Document::from_file(path, encoding) {
+      Err(DocumentError::IoError(e)) if e.kind() == ErrorKind::NotFound => {
+        self.status = Some(format!("\"{}\" [New]", path.display()));
+        return Ok(self.add_document(Document::new_file(path)));
+      }
+      document => document?,
+    };
+    if let Err(e) = self.watch(path) {
+      tracing::warn!("Failed to watch {}: {}", path.display(), e);
+    }
+    if document.has_mixed_line_endings() {
+      self.status = Some(format!(
+        "\"{}\" has mixed line endings, new lines are {}",
+        path.display(),
+        file_format_name(document.line_ending())
+      ));
+    }
+
+    let document_id = self.add_document(document);
+    if let Err(e) = self.documents[document_id].load_undo_history() {
+      tracing::warn!("Failed to load undo history: {}", e);
+    }
+    Ok(document_id)
   }
 
   pub fn open(
     &mut self,
     path: impl AsRef<std::path::Path>,
   ) -> EditorResult<(DocumentId, ViewId)> {
-    // load path into a rope
-    let document = Document::from_reader(path)?;
+    self.open_with_encoding(path, None)
+  }
 
-    // add to editor
-    let document_id = self.documents.insert(document);
-    let view_id = self.create_view(document_id)?;
+  /// Open `path` in `encoding`, guessed when not given, and show it in the
+  /// active view.
+  pub fn open_with_encoding(
+    &mut self,
+    path: impl AsRef<std::path::Path>,
+    encoding: Option<FileEncoding>,
+  ) -> EditorResult<(DocumentId, ViewId)> {
+    let document_id = self.load(path, encoding)?;
+    let view_id = match self.tabs.active().active_view {
+      Some(view_id) => {
+        self.show_document(view_id, document_id)?;
+        view_id
+      }
+      None => self.create_view(document_id)?,
+    };
     Ok((document_id, view_id))
   }
+
+  /// Draw `view_id` with its gutter in `area`.
+  fn render_view(
+    &mut self,
+    view_id: ViewId,
+    area: Rect,
+    frame: &mut TuiBuffer,
+  ) {
+    let kind = match self.keymap.mode() {
+      Mode::Visual(kind) => Some(kind),
+      _ => None,
+    };
+    let view = self.views.get_mut(view_id).expect("view not present");
+    let document = self
+      .documents
+      .get(view.document_id)
+      .expect("document not present");
+
+    let gutter = GutterContext {
+      document,
+      view_id,
+      config: &self.config,
+    };
+    // the text goes right of the gutter
+    let gutter_width = self.gutter.width(&gutter).min(area.width as usize);
+    view.area = Rect {
+      x: area.x + gutter_width as u16,
+      width: area.width - gutter_width as u16,
+      ..area
+    };
+    let area = view.area;
+    let height = view.area.height as usize;
+    let width = view.area.width as usize;
+    let text = document.text();
+    let format = &document.text_format;
+    let config = &self.config;
+    let rows_of = |line| line_rows(text.line(line), format, config, width);
+
+    let selection = &document.selections[&view_id];
+    let (cursor_line, cursor_pos) = selection.cursor();
+    let cursor_row = text_layout::row_at(&rows_of(cursor_line), cursor_pos);
+    view.scroll_to_row(
+      (cursor_line, cursor_row),
+      height,
+      config.scrolloff,
+      |line| rows_of(line).len(),
+    );
+    if config.soft_wrap {
+      view.offset.1 = 0;
+    } else if view.scrolled_at != Some((cursor_line, cursor_pos)) {
+      view.scrolled_at = None;
+      let column =
+        text_layout::visual_column(text.line(cursor_line), cursor_pos, format);
+      view.scroll_to_column(column, width, config.sidescrolloff);
+    }
+
+    // the terminal cursor shows the primary cursor, everything else
+    // selected is reversed, in visual mode the way it selects
+    let r = text.slice(..);
+    let blocks: Vec<_> = match kind {
+      Some(SelectionKind::Blockwise) => selection
+        .iter()
+        .map(|range| range.block_columns(&r, format))
+        .collect(),
+      _ => Vec::new(),
+    };
+    let selected = |pos: Position, column: usize, width: usize| {
+      let lines = |range: &Range| range.from().0..=range.to().0;
+      let covered = match kind {
+        Some(SelectionKind::Linewise) => {
+          selection.iter().any(|range| lines(range).contains(&pos.0))
+        }
+        Some(SelectionKind::Blockwise) => {
+          selection.iter().zip(&blocks).any(|(range, (left, right))| {
+            lines(range).contains(&pos.0)
+              && column <= *right
+              && column + width.max(1) > *left
+          })
+        }
+        _ => selection.contains(pos),
+      };
+      if pos != (cursor_line, cursor_pos) && covered {
+        Style::default().add_modifier(Modifier::REVERSED)
+      } else {
+        Style::default()
+      }
+    };
+
+    let (first_line, first_column) = view.offset;
+    let first_line = first_line.min(text.len_lines());
+    let indicator = config.wrap_indicator.width();
+    // screen row the current line starts at, the first one can start above
+    // the view
+    let mut top = -(view.first_row as isize);
+    for (number, line) in (first_line..).zip(text.lines_at(first_line)) {
+      if top >= height as isize {
+        break;
+      }
+      let rows = rows_of(number);
+      let on_screen = |index: usize| {
+        let y = top + index as isize;
+        (0..height as isize)
+          .contains(&y)
+          .then_some(area.y + y as u16)
+      };
+
+      if let Some(y) = on_screen(0) {
+        for (x, text, style) in self.gutter.render_line(&gutter, number) {
+          frame.set_stringn(
+            area.x - gutter_width as u16 + x as u16,
+            y,
+            text,
+            gutter_width.saturating_sub(x),
+            style,
+          );
+        }
+      }
+
+      for (index, row) in rows.iter().enumerate().skip(1) {
+        if let Some(y) = on_screen(index).filter(|_| row.indent >= indicator) {
+          frame.set_string(
+            area.x + (row.indent - indicator) as u16,
+            y,
+            &config.wrap_indicator,
+            Style::default(),
+          );
+        }
+      }
+
+      let mut index = 0;
+      for grapheme in text_layout::graphemes(line, format) {
+        while rows
+          .get(index + 1)
+          .is_some_and(|row| row.start <= grapheme.offset)
+        {
+          index += 1;
+        }
+        let column = rows[index].screen_column(grapheme.column);
+        // graphemes cut off by the left edge are left out
+        if column < first_column || column - first_column >= width {
+          continue;
+        }
+        let Some(y) = on_screen(index) else {
+          continue;
+        };
+        frame.set_stringn(
+          area.x + (column - first_column) as u16,
+          y,
+          &grapheme.symbol,
+          width - (column - first_column),
+          selected((number, grapheme.offset), grapheme.column, grapheme.width),
+        );
+      }
+
+      // a cursor after the last grapheme, or a selected line ending
+      let len = line_len(&text.slice(..), number);
+      let index = text_layout::row_at(&rows, len);
+      let line_width = text_layout::visual_column(line, len, format);
+      let column = rows[index].screen_column(line_width);
+      if let Some(y) = on_screen(index)
+        .filter(|_| (first_column..first_column + width).contains(&column))
+      {
+        frame.set_string(
+          area.x + (column - first_column) as u16,
+          y,
+          " ",
+          selected((number, len), line_width, 1),
+        );
+      }
+      top += rows.len() as isize;
+    }
+  }
 }
 
 impl Plugin for Editor {
@@ -99,75 +1599,139 @@ impl Plugin for Editor {
     app: &mut Application,
     event: &TuiEvent,
   ) -> Result<ProcessEvent, PluginError> {
-    if let Some((view_id, document_id)) = self.active_view() {
-      let document = self
-        .documents
-        .get_mut(document_id)
-        .expect("document not present");
+    self.status = None;
 
-      if let Some(events) = self.keymap.process_event(event) {
-        for event in events {
-          document.process(&view_id, &event).map_err(AnyError::from)?;
+    let inserting = self.keymap.mode() == Mode::Insert;
+    for event in self.keymap.process_event(event).unwrap_or_default() {
+      match event {
+        KeymapEvent::Document(event) => {
+          self.record_insert(inserting, &event);
+          // a failed edit is reported, leaving is up to the user
+          if let Err(e) = self.process_document_event(&event) {
+            self.status = Some(e.to_string());
+          }
         }
+        KeymapEvent::Editor(event) => self.process_editor_event(app, event),
       }
     }
 
-    // temporary quit
-    if let TuiEvent::Key(key) = event {
-      match key.code {
-        KeyCode::Char('q') => {
-          println!("Quitting");
-          if let Err(e) = app.quit() {
-            tracing::error!("Failed to quit: {}", e);
-          }
-        }
-        _ => (),
+    match (inserting, self.keymap.mode() == Mode::Insert) {
+      (false, true) => self.inserted.clear(),
+      (true, false) => {
+        let inserted = std::mem::take(&mut self.inserted);
+        self.registers.set_inserted(inserted);
+      }
+      _ => {}
+    }
+
+    // everything typed in one insert session is undone at once
+    if self.keymap.mode() != Mode::Insert {
+      if let Some((_view_id, document_id)) = self.active_view() {
+        self.documents[document_id].commit();
       }
     }
 
     Ok(ProcessEvent::Consumed)
   }
 
+  fn poll(&mut self, _app: &mut Application) -> Result<(), PluginError> {
+    self.check_file_changes();
+    Ok(())
+  }
+
   fn render(
     &mut self,
     _app: &mut Application,
     area: &Rect,
     frame: &mut TuiBuffer,
   ) {
-    // render the active view and the command bar
-    // or is the command bar a separate plugin?
-    // frame.set_string(0, 0, "Hello World", Style::default());
-    if let Some((_view_id, document_id)) = self.active_view() {
-      // TODO: get offset of view
-      let document = self
-        .documents
-        .get_mut(document_id)
-        .expect("document not present");
+    // the views and the borders between them, the bottom line is kept for
+    // the command line and the top one for the tab line when it is shown
+    let tabline = u16::from(self.tabline_visible());
+    self.area = Rect {
+      y: area.y + tabline,
+      height: area.height.saturating_sub(1 + tabline),
+      ..*area
+    };
+    let placement = self.tabs.active().layout.place(self.area);
+    for (view_id, area) in placement.views {
+      self.render_view(view_id, area, frame);
+    }
+    for (axis, area) in placement.borders {
+      let symbol = match axis {
+        Axis::Horizontal => "─",
+        Axis::Vertical => "│",
+      };
+      for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+          frame.set_string(x, y, symbol, Style::default());
+        }
+      }
+    }
 
-      for (line, text) in
-        document.rope.lines().enumerate().take(area.height as usize)
-      {
-        frame.set_string(0, line as u16, text.to_string(), Style::default());
+    // the bottom line shows the command being typed or the last message
+    let bottom = area.bottom().saturating_sub(1);
+    if let Some(command_line) = self.keymap.command_line() {
+      frame.set_string(0, bottom, format!(":{command_line}"), Style::default());
+    } else if let Some(status) = &self.status {
+      // messages of several lines, like `:ls`, grow up over the views
+      let width = area.width as usize;
+      for (y, line) in (area.y..=bottom).rev().zip(status.lines().rev()) {
+        frame.set_stringn(
+          0,
+          y,
+          format!("{line:width$}"),
+          width,
+          Style::default(),
+        );
       }
     }
+    // a command being typed in normal or visual mode, right of the status
+    let keys = self.keymap.pending_keys();
+    if !keys.is_empty() {
+      let x = area.right().saturating_sub(keys.width() as u16 + 1);
+      frame.set_string(x, bottom, keys, Style::default());
+    }
   }
 
-  fn cursor(&self, _area: Rect) -> Option<(u16, u16)> {
+  fn cursor(&self, area: Rect) -> Option<(u16, u16)> {
+    if let Some(command_line) = self.keymap.command_line() {
+      let pos = command_line.width() + 1;
+      return Some((area.bottom().saturating_sub(1), pos as u16));
+    }
+
     self.active_view().map(|(view_id, document_id)| {
       let document = self
         .documents
         .get(document_id)
         .expect("document not present");
-      let (pos, line) =
-        document.cursor.get(&view_id).expect("cursor not present");
-      (*pos as u16, *line as u16)
+      let view = self.views.get(view_id).expect("view not present");
+      let (line, pos) = &document.cursor(&view_id).expect("cursor not present");
+      let text = document.text();
+      let format = &document.text_format;
+      let width = view.area.width as usize;
+      let rows_of =
+        |line| line_rows(text.line(line), format, &self.config, width);
+
+      let rows = rows_of(*line);
+      let index = text_layout::row_at(&rows, *pos);
+      let column = text_layout::visual_column(text.line(*line), *pos, format);
+      // relative to the part of the document in view
+      let row = view
+        .screen_row((*line, index), |line| rows_of(line).len())
+        .unwrap_or(0);
+      let column = rows[index]
+        .screen_column(column)
+        .saturating_sub(view.offset.1)
+        .min(width.saturating_sub(1));
+      (view.area.y + row as u16, view.area.x + column as u16)
     })
   }
 }
 
 #[cfg(test)]
 mod test {
-  use super::*;
+  use {super::*, crate::util::fs::TempDir};
 
   #[test]
   fn test_editor_document_and_view() {
@@ -177,6 +1741,209 @@ mod test {
 
     // check if we created the cursor in the document
     let document = editor.documents.get(document_id).unwrap();
-    assert_eq!(document.cursor[&view_id], (0, 0));
+    assert_eq!(document.cursor(&view_id), Some((0, 0)));
+  }
+
+  #[test]
+  fn test_split_views_keep_their_cursor() {
+    let mut editor = Editor::default();
+    let document_id = editor.documents.insert("a\nb\nc\n".parse().unwrap());
+    let view_id = editor.create_view(document_id).unwrap();
+    editor.documents[document_id]
+      .selections
+      .insert(view_id, Selection::point((1, 0)));
+
+    let split_id = editor.split_view(Axis::Vertical).unwrap();
+    assert_eq!(editor.tabs.active().active_view, Some(split_id));
+    assert_eq!(editor.tabs.active().layout.views(), [split_id, view_id]);
+    editor
+      .process_document_event(&DocEvent::Move(Motion::Down, 1))
+      .unwrap();
+    let document = &editor.documents[document_id];
+    assert_eq!(document.cursor(&split_id), Some((2, 0)));
+    assert_eq!(document.cursor(&view_id), Some((1, 0)));
+
+    editor.close_view().unwrap();
+    assert_eq!(editor.tabs.active().active_view, Some(view_id));
+    assert!(!editor.documents[document_id]
+      .selections
+      .contains_key(&split_id));
+    assert!(matches!(editor.close_view(), Err(EditorError::LastView)));
+  }
+
+  #[test]
+  fn test_tabs_share_documents() {
+    let dir = TempDir::new("editor-tabs");
+    let path = dir.join("file.txt");
+    std::fs::write(&path, "one\ntwo\n").unwrap();
+
+    let mut editor = Editor::default();
+    editor.config.undo_dir = Some(dir.join("undo"));
+    let (document_id, view_id) = editor.open(&path).unwrap();
+    editor.documents[document_id]
+      .selections
+      .insert(view_id, Selection::point((1, 0)));
+    editor.new_tab(Some(path.clone())).unwrap();
+    assert_eq!(editor.tabs.count(), 2);
+    assert_eq!(editor.documents.len(), 1);
+
+    // every tab has a view of its own in the document
+    let (tab_view_id, tab_document_id) = editor.active_view().unwrap();
+    assert_eq!(tab_document_id, document_id);
+    assert_ne!(tab_view_id, view_id);
+    assert_eq!(
+      editor.documents[document_id].cursor(&tab_view_id),
+      Some((0, 0))
+    );
+
+    editor.close_view().unwrap();
+    assert_eq!(editor.tabs.count(), 1);
+    assert_eq!(editor.active_view(), Some((view_id, document_id)));
+    assert!(!editor.views.contains_key(tab_view_id));
+  }
+
+  #[test]
+  fn test_buffers() {
+    let mut editor = Editor::default();
+    let a = editor.create_document();
+    let view_id = editor.create_view(a).unwrap();
+    let b = editor.create_document();
+    let c = editor.create_document();
+    assert_eq!(editor.buffers(), [a, b, c]);
+
+    editor.cycle_buffer(-1).unwrap();
+    assert_eq!(editor.active_view(), Some((view_id, c)));
+    editor.cycle_buffer(1).unwrap();
+    assert_eq!(editor.active_view(), Some((view_id, a)));
+
+    // unsaved changes keep a buffer open unless forced
+    editor
+      .documents
+      .get_mut(a)
+      .unwrap()
+      .process(&view_id, &DocEvent::InsertChar('x'))
+      .unwrap();
+    assert!(matches!(
+      editor.delete_buffer(false, None),
+      Err(EditorError::UnsavedChanges)
+    ));
+    editor.delete_buffer(true, None).unwrap();
+    assert_eq!(editor.active_view(), Some((view_id, b)));
+    assert_eq!(editor.buffers(), [b, c]);
+    assert!(matches!(
+      editor.find_buffer(Some(&BufferRef::Number(1))),
+      Err(EditorError::NoBuffer(1))
+    ));
+
+    // closing the only view showing a document releases it
+    let split_id = editor.split_view(Axis::Horizontal).unwrap();
+    let d = editor.create_document();
+    editor.show_document(split_id, d).unwrap();
+    assert_eq!(editor.buffers(), [b, c, d]);
+    editor.close_view().unwrap();
+    assert_eq!(editor.buffers(), [b, c]);
+    assert!(editor.list_buffers().starts_with("  2 %a"));
+  }
+
+  #[test]
+  fn test_find_buffer_by_name() {
+    let mut editor = Editor::default();
+    let main = editor.documents.insert(Document::new_file("src/main.rs"));
+    let domain = editor.documents.insert(Document::new_file("src/domain.rs"));
+    let lib = editor.documents.insert(Document::new_file("src/lib.rs"));
+    let find = |editor: &Editor, name: &str| {
+      editor.find_buffer(Some(&BufferRef::Name(name.into())))
+    };
+
+    assert_eq!(find(&editor, "main.rs").unwrap(), main);
+    assert_eq!(find(&editor, "src/lib.rs").unwrap(), lib);
+    assert_eq!(find(&editor, "dom").unwrap(), domain);
+    assert!(matches!(
+      find(&editor, "main"),
+      Err(EditorError::AmbiguousBuffer(_))
+    ));
+    assert!(matches!(
+      find(&editor, "none"),
+      Err(EditorError::NoMatchingBuffer(_))
+    ));
+  }
+
+  #[test]
+  fn test_scroll_columns_moves_cursor() {
+    let mut editor = Editor::default();
+    let document_id = editor
+      .documents
+      .insert("0123456789abcdefghij\n".parse().unwrap());
+    let view_id = editor.create_view(document_id).unwrap();
+    editor.config.sidescrolloff = 2;
+    editor.views[view_id].area = Rect::new(0, 0, 10, 5);
+
+    editor.scroll_columns(4);
+    assert_eq!(editor.documents[document_id].cursor(&view_id), Some((0, 6)));
+    editor.scroll_to_cursor(true);
+    assert_eq!(editor.views[view_id].offset.1, 0);
+    editor.scroll_to_cursor(false);
+    assert_eq!(editor.views[view_id].offset.1, 4);
+  }
+
+  #[test]
+  fn test_scroll_columns_past_short_line() {
+    let mut editor = Editor::default();
+    let document_id = editor
+      .documents
+      .insert("abc\n0123456789abcdefghij\n".parse().unwrap());
+    let view_id = editor.create_view(document_id).unwrap();
+    let area = Rect::new(0, 0, 10, 5);
+    let mut frame = TuiBuffer::empty(area);
+
+    // the cursor cannot follow, drawing keeps the columns scrolled to
+    editor.scroll_columns(5);
+    editor.render_view(view_id, area, &mut frame);
+    assert_eq!(editor.views[view_id].offset.1, 5);
+
+    // moving the cursor brings it back into view
+    editor.documents[document_id]
+      .selections
+      .insert(view_id, Selection::point((0, 1)));
+    editor.render_view(view_id, area, &mut frame);
+    assert_eq!(editor.views[view_id].offset.1, 0);
+  }
+
+  #[test]
+  fn test_move_by_wrapped_rows() {
+    let mut editor = Editor::default();
+    let document_id = editor
+      .documents
+      .insert("aaaa bbbb cccc dddd\nxyz\n".parse().unwrap());
+    let view_id = editor.create_view(document_id).unwrap();
+    editor.config.soft_wrap = true;
+    editor.views[view_id].area = Rect::new(0, 0, 10, 5);
+    let cursor =
+      |editor: &Editor| editor.documents[document_id].cursor(&view_id).unwrap();
+
+    // rows start at 0, 10 and 15, behind a two column wrap indicator
+    editor.documents[document_id]
+      .selections
+      .insert(view_id, Selection::point((0, 1)));
+    editor.move_row(false, 1, false);
+    assert_eq!(cursor(&editor), (0, 10));
+    editor.move_row(false, 1, false);
+    assert_eq!(cursor(&editor), (0, 15));
+    editor.move_row(false, 1, false);
+    assert_eq!(cursor(&editor), (1, 2));
+    editor.move_row(true, 1, false);
+    assert_eq!(cursor(&editor), (0, 15));
+
+    // extending keeps the anchor
+    editor.move_row(true, 1, true);
+    let selection = &editor.documents[document_id].selections[&view_id];
+    assert_eq!(selection.primary().anchor, (0, 15));
+    assert_eq!(selection.primary().head, (0, 10));
+
+    // a count goes that many rows, as far as there are any
+    editor.move_row(false, 5, false);
+    assert_eq!(cursor(&editor), (1, 2));
+    editor.move_row(true, 2, false);
+    assert_eq!(cursor(&editor), (0, 10));
   }
 }
@@ -0,0 +1,256 @@
+//! Columns drawn left of the text of a view, line numbers and whatever
+//! else is added, like signs or markers.
+
+use {
+  crate::{
+    config::Config,
+    document::Document,
+    movement::last_line,
+    view::ViewId,
+  },
+  tui::style::{Color, Style},
+};
+
+/// Which line numbers the gutter shows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+  Off,
+  #[default]
+  Absolute,
+  /// Distance to the cursor line.
+  Relative,
+  /// Distance to the cursor line, the cursor line itself absolute.
+  Hybrid,
+}
+
+impl LineNumbers {
+  /// The mode for vim's `number` and `relativenumber` flags.
+  pub fn from_flags(number: bool, relative: bool) -> Self {
+    match (number, relative) {
+      (false, false) => Self::Off,
+      (true, false) => Self::Absolute,
+      (false, true) => Self::Relative,
+      (true, true) => Self::Hybrid,
+    }
+  }
+
+  pub fn number(&self) -> bool {
+    matches!(self, Self::Absolute | Self::Hybrid)
+  }
+
+  pub fn relative(&self) -> bool {
+    matches!(self, Self::Relative | Self::Hybrid)
+  }
+}
+
+/// What the gutter of a view is drawn for.
+pub struct GutterContext<'a> {
+  pub document: &'a Document,
+  pub view_id: ViewId,
+  pub config: &'a Config,
+}
+
+impl GutterContext<'_> {
+  pub fn cursor_line(&self) -> usize {
+    self
+      .document
+      .cursor(&self.view_id)
+      .map_or(0, |(line, _)| line)
+  }
+}
+
+/// A column of the gutter.
+pub trait GutterColumn {
+  /// Screen columns it takes up, `0` hides it.
+  fn width(&self, cx: &GutterContext) -> usize;
+
+  /// What to draw next to the first row of `line`, at most `width` wide.
+  fn render(
+    &self,
+    cx: &GutterContext,
+    line: usize,
+    width: usize,
+  ) -> Option<(String, Style)>;
+}
+
+/// Line numbers as set by [`Config::line_numbers`], followed by a space.
+pub struct LineNumberColumn;
+
+impl GutterColumn for LineNumberColumn {
+  fn width(&self, cx: &GutterContext) -> usize {
+    if cx.config.line_numbers == LineNumbers::Off {
+      return 0;
+    }
+    // room for the last line number, and never less than three digits
+    let lines = last_line(&cx.document.text().slice(..)) + 1;
+    lines.to_string().len().max(3) + 1
+  }
+
+  fn render(
+    &self,
+    cx: &GutterContext,
+    line: usize,
+    width: usize,
+  ) -> Option<(String, Style)> {
+    // the empty line after a trailing line ending is not numbered
+    if line > last_line(&cx.document.text().slice(..)) {
+      return None;
+    }
+    let cursor_line = cx.cursor_line();
+    let number = match cx.config.line_numbers {
+      LineNumbers::Off => return None,
+      LineNumbers::Hybrid if line == cursor_line => line + 1,
+      LineNumbers::Absolute => line + 1,
+      LineNumbers::Relative | LineNumbers::Hybrid => line.abs_diff(cursor_line),
+    };
+    let style = if line == cursor_line {
+      Style::default()
+    } else {
+      Style::default().fg(Color::DarkGray)
+    };
+    Some((format!("{number:>0$} ", width.saturating_sub(1)), style))
+  }
+}
+
+/// The columns left of the text of every view, in order.
+pub struct Gutter {
+  columns: Vec<Box<dyn GutterColumn>>,
+}
+
+impl Default for Gutter {
+  fn default() -> Self {
+    Self {
+      columns: vec![Box::new(LineNumberColumn)],
+    }
+  }
+}
+
+impl Gutter {
+  /// Add a column right of the existing ones.
+  pub fn add(&mut self, column: impl GutterColumn + 'static) {
+    self.columns.push(Box::new(column));
+  }
+
+  /// Screen columns taken up by all columns together.
+  pub fn width(&self, cx: &GutterContext) -> usize {
+    self.columns.iter().map(|column| column.width(cx)).sum()
+  }
+
+  /// What each column draws next to `line`, with the screen column it
+  /// starts at.
+  pub fn render_line(
+    &self,
+    cx: &GutterContext,
+    line: usize,
+  ) -> Vec<(usize, String, Style)> {
+    let mut x = 0;
+    let mut cells = Vec::new();
+    for column in &self.columns {
+      let width = column.width(cx);
+      if width == 0 {
+        continue;
+      }
+      if let Some((text, style)) = column.render(cx, line, width) {
+        cells.push((x, text, style));
+      }
+      x += width;
+    }
+    cells
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::selection::Selection};
+
+  fn numbers(mode: LineNumbers) -> Vec<Option<String>> {
+    let mut document: Document = "a\nb\nc\nd\n".parse().unwrap();
+    let view_id = ViewId::default();
+    document.new_view(view_id);
+    document
+      .selections
+      .insert(view_id, Selection::point((1, 0)));
+    let config = Config {
+      line_numbers: mode,
+      ..Default::default()
+    };
+    let cx = GutterContext {
+      document: &document,
+      view_id,
+      config: &config,
+    };
+
+    let gutter = Gutter::default();
+    (0..5)
+      .map(|line| {
+        let cells = gutter.render_line(&cx, line);
+        cells.first().map(|(x, text, _)| {
+          assert_eq!(*x, 0);
+          assert_eq!(text.len(), gutter.width(&cx));
+          text.clone()
+        })
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_line_numbers() {
+    let some = |numbers: &[&str]| -> Vec<_> {
+      numbers
+        .iter()
+        .map(|number| Some(number.to_string()))
+        .chain([None])
+        .collect()
+    };
+    assert_eq!(
+      numbers(LineNumbers::Absolute),
+      some(&["  1 ", "  2 ", "  3 ", "  4 "])
+    );
+    assert_eq!(
+      numbers(LineNumbers::Relative),
+      some(&["  1 ", "  0 ", "  1 ", "  2 "])
+    );
+    assert_eq!(
+      numbers(LineNumbers::Hybrid),
+      some(&["  1 ", "  2 ", "  1 ", "  2 "])
+    );
+    assert_eq!(numbers(LineNumbers::Off), [None, None, None, None, None]);
+  }
+
+  #[test]
+  fn test_added_columns() {
+    struct Marker;
+    impl GutterColumn for Marker {
+      fn width(&self, _cx: &GutterContext) -> usize {
+        1
+      }
+
+      fn render(
+        &self,
+        cx: &GutterContext,
+        line: usize,
+        _width: usize,
+      ) -> Option<(String, Style)> {
+        (line == cx.cursor_line()).then(|| (">".to_string(), Style::default()))
+      }
+    }
+
+    let document: Document = "a\n".parse().unwrap();
+    let config = Config::default();
+    let cx = GutterContext {
+      document: &document,
+      view_id: ViewId::default(),
+      config: &config,
+    };
+    let mut gutter = Gutter::default();
+    gutter.add(Marker);
+
+    assert_eq!(gutter.width(&cx), 5);
+    let cells: Vec<_> = gutter
+      .render_line(&cx, 0)
+      .into_iter()
+      .map(|(x, text, _)| (x, text))
+      .collect();
+    assert_eq!(cells, [(0, "  1 ".to_string()), (4, ">".to_string())]);
+  }
+}
@@ -0,0 +1,325 @@
+pub mod persistent;
+
+use {
+  crate::{selection::Selection, transaction::Transaction, view::ViewId},
+  ropey::Rope,
+  std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    time::{Duration, SystemTime},
+  },
+};
+
+/// Selection of every view on a document.
+pub type Cursors = HashMap<ViewId, Selection>;
+
+/// How far to travel through the history with `:earlier` and `:later`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoKind {
+  Steps(usize),
+  TimePeriod(Duration),
+}
+
+/// One edit of the document. Revisions form a tree, undoing and making a new
+/// edit starts a new branch instead of throwing the old one away.
+#[derive(Debug, Clone)]
+pub struct Revision {
+  parent: usize,
+  last_child: Option<NonZeroUsize>,
+  transaction: Transaction,
+  /// Undoes `transaction`.
+  inversion: Transaction,
+  timestamp: SystemTime,
+  /// Cursors before the edit, restored when it is undone.
+  cursors_before: Cursors,
+  /// Cursors after the edit, restored when it is redone.
+  cursors_after: Cursors,
+}
+
+/// A transaction to apply while moving through the history, with the
+/// cursors to restore afterwards.
+pub type Step = (Transaction, Cursors);
+
+/// Branching undo history. Revision 0 is the document as it was loaded,
+/// revisions are stored in the order they were made.
+#[derive(Debug, Clone)]
+pub struct History {
+  revisions: Vec<Revision>,
+  current: usize,
+}
+
+impl Default for History {
+  fn default() -> Self {
+    Self {
+      revisions: vec![Revision {
+        parent: 0,
+        last_child: None,
+        transaction: Transaction::default(),
+        inversion: Transaction::default(),
+        timestamp: SystemTime::now(),
+        cursors_before: Cursors::default(),
+        cursors_after: Cursors::default(),
+      }],
+      current: 0,
+    }
+  }
+}
+
+impl History {
+  /// Record `transaction` as a child of the current revision, `original` is
+  /// the text it was applied to.
+  pub fn commit_revision(
+    &mut self,
+    transaction: &Transaction,
+    original: &Rope,
+    cursors_before: Cursors,
+    cursors_after: Cursors,
+  ) {
+    self.commit_revision_at_timestamp(
+      transaction,
+      original,
+      cursors_before,
+      cursors_after,
+      SystemTime::now(),
+    );
+  }
+
+  pub fn commit_revision_at_timestamp(
+    &mut self,
+    transaction: &Transaction,
+    original: &Rope,
+    cursors_before: Cursors,
+    cursors_after: Cursors,
+    timestamp: SystemTime,
+  ) {
+    let new_current = self.revisions.len();
+    self.revisions[self.current].last_child = NonZeroUsize::new(new_current);
+    self.revisions.push(Revision {
+      parent: self.current,
+      last_child: None,
+      transaction: transaction.clone(),
+      inversion: transaction.invert(original),
+      timestamp,
+      cursors_before,
+      cursors_after,
+    });
+    self.current = new_current;
+  }
+
+  pub fn current_revision(&self) -> usize {
+    self.current
+  }
+
+  /// Number of revisions, the root included.
+  pub fn revision_count(&self) -> usize {
+    self.revisions.len()
+  }
+
+  pub fn at_root(&self) -> bool {
+    self.current == 0
+  }
+
+  fn undo_step(&self, revision: usize) -> Step {
+    let revision = &self.revisions[revision];
+    (revision.inversion.clone(), revision.cursors_before.clone())
+  }
+
+  fn redo_step(&self, revision: usize) -> Step {
+    let revision = &self.revisions[revision];
+    (revision.transaction.clone(), revision.cursors_after.clone())
+  }
+
+  /// Go back to the parent of the current revision.
+  pub fn undo(&mut self) -> Option<Step> {
+    if self.at_root() {
+      return None;
+    }
+    let step = self.undo_step(self.current);
+    self.current = self.revisions[self.current].parent;
+    Some(step)
+  }
+
+  /// Go forward to the most recent child of the current revision.
+  pub fn redo(&mut self) -> Option<Step> {
+    let child = self.revisions[self.current].last_child?.get();
+    self.current = child;
+    Some(self.redo_step(child))
+  }
+
+  fn lowest_common_ancestor(&self, mut a: usize, mut b: usize) -> usize {
+    // a parent is always older than its children
+    while a != b {
+      if a > b {
+        a = self.revisions[a].parent;
+      } else {
+        b = self.revisions[b].parent;
+      }
+    }
+    a
+  }
+
+  /// Move to any revision, undoing up to the common ancestor and redoing
+  /// down to `target`.
+  fn jump_to(&mut self, target: usize) -> Vec<Step> {
+    let lca = self.lowest_common_ancestor(self.current, target);
+
+    let mut steps = Vec::new();
+    let mut revision = self.current;
+    while revision != lca {
+      steps.push(self.undo_step(revision));
+      revision = self.revisions[revision].parent;
+    }
+
+    let mut down = Vec::new();
+    let mut revision = target;
+    while revision != lca {
+      down.push(revision);
+      revision = self.revisions[revision].parent;
+    }
+    steps.extend(down.into_iter().rev().map(|rev| self.redo_step(rev)));
+
+    // following redos should take the branch we jumped to
+    let mut revision = target;
+    while revision != lca {
+      let parent = self.revisions[revision].parent;
+      self.revisions[parent].last_child = NonZeroUsize::new(revision);
+      revision = parent;
+    }
+
+    self.current = target;
+    steps
+  }
+
+  /// The last revision made at or before `timestamp`.
+  fn revision_at(&self, timestamp: SystemTime) -> usize {
+    match self
+      .revisions
+      .binary_search_by(|revision| revision.timestamp.cmp(&timestamp))
+    {
+      Ok(revision) => revision,
+      Err(revision) => revision.saturating_sub(1),
+    }
+  }
+
+  pub fn earlier(&mut self, kind: UndoKind) -> Vec<Step> {
+    let target = match kind {
+      UndoKind::Steps(n) => self.current.saturating_sub(n),
+      UndoKind::TimePeriod(duration) => {
+        let timestamp = self.revisions[self.current].timestamp;
+        timestamp
+          .checked_sub(duration)
+          .map_or(0, |timestamp| self.revision_at(timestamp))
+          .min(self.current)
+      }
+    };
+    self.jump_to(target)
+  }
+
+  pub fn later(&mut self, kind: UndoKind) -> Vec<Step> {
+    let last = self.revisions.len() - 1;
+    let target = match kind {
+      UndoKind::Steps(n) => self.current.saturating_add(n).min(last),
+      UndoKind::TimePeriod(duration) => {
+        let timestamp = self.revisions[self.current].timestamp;
+        timestamp
+          .checked_add(duration)
+          .map_or(last, |timestamp| self.revision_at(timestamp))
+          .max(self.current)
+      }
+    };
+    self.jump_to(target)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn commit(
+    history: &mut History,
+    text: &mut Rope,
+    transaction: Transaction,
+    timestamp: SystemTime,
+  ) {
+    let original = text.clone();
+    assert!(transaction.apply(text));
+    history.commit_revision_at_timestamp(
+      &transaction,
+      &original,
+      Cursors::default(),
+      Cursors::default(),
+      timestamp,
+    );
+  }
+
+  fn replay(text: &mut Rope, steps: Vec<Step>) {
+    for (transaction, _) in steps {
+      assert!(transaction.apply(text));
+    }
+  }
+
+  #[test]
+  fn test_undo_redo_branches() {
+    let mut history = History::default();
+    let mut text = Rope::from_str("hello");
+    let now = SystemTime::now();
+
+    let t = Transaction::insert(&text, 5, " world");
+    commit(&mut history, &mut text, t, now);
+    let t = Transaction::insert(&text, 11, "!");
+    commit(&mut history, &mut text, t, now);
+    assert_eq!(text, "hello world!");
+
+    let (undo, _) = history.undo().unwrap();
+    undo.apply(&mut text);
+    assert_eq!(text, "hello world");
+
+    // a new edit starts a branch, redo follows it
+    let t = Transaction::insert(&text, 11, "?");
+    commit(&mut history, &mut text, t, now);
+    history.undo().unwrap().0.apply(&mut text);
+    history.redo().unwrap().0.apply(&mut text);
+    assert_eq!(text, "hello world?");
+    assert!(history.redo().is_none());
+
+    // the old branch is still reachable going back in time
+    replay(&mut text, history.earlier(UndoKind::Steps(1)));
+    assert_eq!(text, "hello world!");
+    replay(&mut text, history.earlier(UndoKind::Steps(5)));
+    assert_eq!(text, "hello");
+    assert!(history.at_root());
+    assert!(history.undo().is_none());
+    replay(&mut text, history.later(UndoKind::Steps(3)));
+    assert_eq!(text, "hello world?");
+  }
+
+  #[test]
+  fn test_earlier_later_by_time() {
+    let mut history = History::default();
+    let mut text = Rope::from_str("");
+    let start = SystemTime::now();
+    let at = |secs| start + Duration::from_secs(secs);
+
+    for (i, secs) in [10, 20, 30].into_iter().enumerate() {
+      let t = Transaction::insert(&text, i, &i.to_string());
+      commit(&mut history, &mut text, t, at(secs));
+    }
+    assert_eq!(text, "012");
+
+    replay(
+      &mut text,
+      history.earlier(UndoKind::TimePeriod(Duration::from_secs(15))),
+    );
+    assert_eq!(text, "0");
+    replay(
+      &mut text,
+      history.later(UndoKind::TimePeriod(Duration::from_secs(10))),
+    );
+    assert_eq!(text, "01");
+    replay(
+      &mut text,
+      history.earlier(UndoKind::TimePeriod(Duration::from_secs(3600))),
+    );
+    assert_eq!(text, "");
+  }
+}
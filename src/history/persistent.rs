@@ -0,0 +1,404 @@
+//! Undo history kept on disk between sessions.
+//!
+//! Every file gets its own undo file in the undo directory, the user's state
+//! directory by default, named after a hash of the file's canonical path. The
+//! undo file holds a hash of the text it belongs to, a history is only loaded
+//! back when the text on disk still matches it.
+
+use {
+  super::{Cursors, History, Revision},
+  crate::{
+    transaction::{ChangeSet, Operation, Transaction},
+    util::fs,
+  },
+  ropey::Rope,
+  sha2::{Digest, Sha256},
+  std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+  },
+};
+
+const MAGIC: &[u8; 8] = b"BLUNDO\0\x01";
+
+/// Undo files not written for this long are removed, their files are
+/// likely gone or forgotten.
+pub const MAX_UNDO_FILE_AGE: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+pub type ContentHash = [u8; 32];
+
+pub fn content_hash(text: &Rope) -> ContentHash {
+  let mut hasher = Sha256::new();
+  for chunk in text.chunks() {
+    hasher.update(chunk.as_bytes());
+  }
+  let mut hash = ContentHash::default();
+  hash.copy_from_slice(hasher.finalize().as_slice());
+  hash
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The undo directory in the user's state directory, `None` when there is
+/// no state directory for this user.
+pub fn default_undo_dir() -> Option<PathBuf> {
+  let state_dir = dirs::state_dir().or_else(dirs::data_local_dir)?;
+  Some(state_dir.join("blasted").join("undo"))
+}
+
+/// Where the undo history of `path` is stored in `undo_dir`.
+pub fn undo_file_path(undo_dir: &Path, path: &Path) -> io::Result<PathBuf> {
+  let path = path.canonicalize()?;
+  let name = hex(&Sha256::digest(path.as_os_str().as_encoded_bytes()));
+  Ok(undo_dir.join(name))
+}
+
+/// Remove the undo files in `undo_dir` last written longer than `max_age`
+/// ago.
+pub fn prune_undo_files(undo_dir: &Path, max_age: Duration) -> io::Result<()> {
+  let entries = match std::fs::read_dir(undo_dir) {
+    Ok(entries) => entries,
+    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+    Err(e) => return Err(e),
+  };
+  for entry in entries {
+    let entry = entry?;
+    let age = entry.metadata()?.modified()?.elapsed().unwrap_or_default();
+    if age > max_age {
+      std::fs::remove_file(entry.path())?;
+    }
+  }
+  Ok(())
+}
+
+/// Store `history`, belonging to `text`, in `undo_file`.
+pub fn write_undo_file(
+  undo_file: &Path,
+  history: &History,
+  text: &Rope,
+) -> io::Result<()> {
+  if let Some(dir) = undo_file.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+  let mut bytes = MAGIC.to_vec();
+  bytes.extend_from_slice(&content_hash(text));
+  history.serialize(&mut bytes)?;
+
+  // the history holds the text of the file, only its owner may read it
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let permissions = std::fs::Permissions::from_mode(0o600);
+    fs::write_atomic_with_permissions(undo_file, &bytes, permissions)
+  }
+  #[cfg(not(unix))]
+  fs::write_atomic(undo_file, &bytes)
+}
+
+/// Load the history in `undo_file`, `None` when there is none or when it
+/// was written for another text than `text`.
+pub fn read_undo_file(
+  undo_file: &Path,
+  text: &Rope,
+) -> io::Result<Option<History>> {
+  let file = match File::open(undo_file) {
+    Ok(file) => file,
+    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+    Err(e) => return Err(e),
+  };
+  let size = file.metadata()?.len();
+  let mut reader = BufReader::new(file);
+
+  let mut magic = [0; MAGIC.len()];
+  reader.read_exact(&mut magic)?;
+  if &magic != MAGIC {
+    return Err(invalid_data("not an undo file"));
+  }
+
+  let mut hash = ContentHash::default();
+  reader.read_exact(&mut hash)?;
+  if hash != content_hash(text) {
+    return Ok(None);
+  }
+
+  let history = History::deserialize(&mut reader, size)?;
+  history.check_lengths(text.len_chars())?;
+  Ok(Some(history))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u64(writer: &mut impl Write, n: u64) -> io::Result<()> {
+  writer.write_all(&n.to_le_bytes())
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+  let mut bytes = [0; 8];
+  reader.read_exact(&mut bytes)?;
+  Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_usize(reader: &mut impl Read) -> io::Result<usize> {
+  usize::try_from(read_u64(reader)?)
+    .map_err(|_| invalid_data("number out of range"))
+}
+
+/// A length or count, which can never be more than the `size` of the file
+/// it is read from. Corrupt files fail here before anything is allocated.
+fn read_len(reader: &mut impl Read, size: u64) -> io::Result<usize> {
+  let len = read_u64(reader)?;
+  if len > size {
+    return Err(invalid_data("length past the end of the file"));
+  }
+  usize::try_from(len).map_err(|_| invalid_data("number out of range"))
+}
+
+fn write_transaction(
+  writer: &mut impl Write,
+  transaction: &Transaction,
+) -> io::Result<()> {
+  // 0 is no cursor, anything else the cursor plus one
+  write_u64(writer, transaction.cursor().map_or(0, |c| c as u64 + 1))?;
+  let operations = transaction.changes().operations();
+  write_u64(writer, operations.len() as u64)?;
+  for op in operations {
+    match op {
+      Operation::Retain(n) => {
+        writer.write_all(&[0])?;
+        write_u64(writer, *n as u64)?;
+      }
+      Operation::Delete(n) => {
+        writer.write_all(&[1])?;
+        write_u64(writer, *n as u64)?;
+      }
+      Operation::Insert(text) => {
+        writer.write_all(&[2])?;
+        write_u64(writer, text.len() as u64)?;
+        writer.write_all(text.as_bytes())?;
+      }
+    }
+  }
+  Ok(())
+}
+
+fn read_transaction(
+  reader: &mut impl Read,
+  size: u64,
+) -> io::Result<Transaction> {
+  let cursor = read_usize(reader)?.checked_sub(1);
+  let len = read_len(reader, size)?;
+  let mut operations = Vec::new();
+  for _ in 0..len {
+    let mut tag = [0];
+    reader.read_exact(&mut tag)?;
+    operations.push(match tag[0] {
+      0 => Operation::Retain(read_usize(reader)?),
+      1 => Operation::Delete(read_usize(reader)?),
+      2 => {
+        let mut bytes = vec![0; read_len(reader, size)?];
+        reader.read_exact(&mut bytes)?;
+        Operation::Insert(
+          String::from_utf8(bytes).map_err(|_| invalid_data("invalid text"))?,
+        )
+      }
+      _ => return Err(invalid_data("unknown operation")),
+    });
+  }
+
+  let transaction = Transaction::from(ChangeSet::from_operations(operations));
+  Ok(match cursor {
+    Some(cursor) => transaction.with_cursor(cursor),
+    None => transaction,
+  })
+}
+
+impl History {
+  /// Write the revision tree. Cursors belong to views of this session and
+  /// are left out.
+  pub fn serialize(&self, writer: &mut impl Write) -> io::Result<()> {
+    write_u64(writer, self.current as u64)?;
+    write_u64(writer, self.revisions.len() as u64)?;
+    for revision in &self.revisions {
+      let timestamp = revision
+        .timestamp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+      write_u64(writer, revision.parent as u64)?;
+      write_u64(writer, revision.last_child.map_or(0, |c| c.get() as u64))?;
+      write_u64(writer, timestamp.as_secs())?;
+      write_u64(writer, timestamp.subsec_nanos() as u64)?;
+      write_transaction(writer, &revision.transaction)?;
+      write_transaction(writer, &revision.inversion)?;
+    }
+    Ok(())
+  }
+
+  /// Read a revision tree from a file of `size` bytes.
+  pub fn deserialize(reader: &mut impl Read, size: u64) -> io::Result<Self> {
+    let current = read_usize(reader)?;
+    let len = read_len(reader, size)?;
+    let mut revisions = Vec::new();
+    for index in 0..len {
+      let parent = read_usize(reader)?;
+      let last_child = NonZeroUsize::new(read_usize(reader)?);
+      let secs = read_u64(reader)?;
+      let nanos = read_u64(reader)?;
+      // parents are always older than their children
+      if (index > 0 && parent >= index)
+        || last_child.is_some_and(|c| c.get() >= len)
+      {
+        return Err(invalid_data("corrupt revision tree"));
+      }
+      let timestamp = u32::try_from(nanos)
+        .ok()
+        .filter(|nanos| *nanos < 1_000_000_000)
+        .and_then(|nanos| {
+          SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+        })
+        .ok_or_else(|| invalid_data("invalid timestamp"))?;
+      revisions.push(Revision {
+        parent,
+        last_child,
+        timestamp,
+        transaction: read_transaction(reader, size)?,
+        inversion: read_transaction(reader, size)?,
+        cursors_before: Cursors::default(),
+        cursors_after: Cursors::default(),
+      });
+    }
+
+    if current >= revisions.len() {
+      return Err(invalid_data("corrupt revision tree"));
+    }
+    Ok(Self { revisions, current })
+  }
+
+  /// Check that every transaction applies to the text its parent leaves and
+  /// its inversion takes that text back, for a text of `len` chars at the
+  /// current revision. A history that fails would break halfway through an
+  /// undo.
+  fn check_lengths(&self, len: usize) -> io::Result<()> {
+    // the text at the root is the one the first step from it applies to
+    let mut revision = self.current;
+    while revision != 0 && self.revisions[revision].parent != 0 {
+      revision = self.revisions[revision].parent;
+    }
+    let root_len = match revision {
+      0 => len,
+      _ => self.revisions[revision].transaction.changes().len(),
+    };
+
+    let mut lens = vec![root_len; self.revisions.len()];
+    for (index, revision) in self.revisions.iter().enumerate().skip(1) {
+      let transaction = revision.transaction.changes();
+      let inversion = revision.inversion.changes();
+      if transaction.len() != lens[revision.parent]
+        || inversion.len() != transaction.len_after()
+        || inversion.len_after() != transaction.len()
+      {
+        return Err(invalid_data("transactions do not fit the text"));
+      }
+      lens[index] = transaction.len_after();
+    }
+    if lens[self.current] != len {
+      return Err(invalid_data("transactions do not fit the text"));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::util::fs::TempDir};
+
+  #[test]
+  fn test_round_trip_and_hash_check() {
+    let mut history = History::default();
+    let mut text = Rope::from_str("hello");
+    for insertion in [" world", "!"] {
+      let original = text.clone();
+      let transaction =
+        Transaction::insert(&text, text.len_chars(), insertion).with_cursor(3);
+      transaction.apply(&mut text);
+      history.commit_revision(
+        &transaction,
+        &original,
+        Cursors::default(),
+        Cursors::default(),
+      );
+    }
+    history.undo().unwrap().0.apply(&mut text);
+
+    let dir = TempDir::new("undo");
+    let undo_file = dir.join("undo");
+    write_undo_file(&undo_file, &history, &text).unwrap();
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      let mode = std::fs::metadata(&undo_file).unwrap().permissions().mode();
+      assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // another text does not get the history
+    let other = Rope::from_str("hello there");
+    assert!(read_undo_file(&undo_file, &other).unwrap().is_none());
+
+    let mut loaded = read_undo_file(&undo_file, &text).unwrap().unwrap();
+    assert_eq!(loaded.current_revision(), 1);
+
+    let (redo, _) = loaded.redo().unwrap();
+    assert_eq!(redo.cursor(), Some(3));
+    redo.apply(&mut text);
+    assert_eq!(text, "hello world!");
+    loaded.undo().unwrap().0.apply(&mut text);
+    loaded.undo().unwrap().0.apply(&mut text);
+    assert_eq!(text, "hello");
+    assert!(loaded.at_root());
+
+    // lengths have to chain from the root to the text
+    assert!(loaded.check_lengths(text.len_chars()).is_ok());
+    assert!(loaded.check_lengths(text.len_chars() + 1).is_err());
+    loaded.revisions[2].inversion = Transaction::default();
+    assert!(loaded.check_lengths(text.len_chars()).is_err());
+  }
+
+  #[test]
+  fn test_prune_old_undo_files() {
+    let dir = TempDir::new("undo-prune");
+    let (old, new) = (dir.join("old"), dir.join("new"));
+    std::fs::write(&new, "").unwrap();
+    let file = File::create(&old).unwrap();
+    let week = Duration::from_secs(7 * 24 * 60 * 60);
+    file.set_modified(SystemTime::now() - 2 * week).unwrap();
+
+    prune_undo_files(dir.path(), week).unwrap();
+    assert!(!old.exists());
+    assert!(new.exists());
+    // no undo directory yet is nothing to prune
+    prune_undo_files(&dir.join("missing"), week).unwrap();
+  }
+
+  #[test]
+  fn test_corrupt_files_fail() {
+    let deserialize = |numbers: &[u64], rest: &[u8]| {
+      let mut bytes: Vec<u8> =
+        numbers.iter().flat_map(|n| n.to_le_bytes()).collect();
+      bytes.extend_from_slice(rest);
+      History::deserialize(&mut &bytes[..], bytes.len() as u64)
+    };
+    // a revision with a second too many of nanoseconds, or too many seconds
+    assert!(deserialize(&[0, 1, 0, 0, 0, 1_000_000_000], &[]).is_err());
+    assert!(deserialize(&[0, 1, 0, 0, u64::MAX, 0], &[]).is_err());
+    // an insert longer than the file fails before its text is allocated
+    let mut insert = vec![2];
+    insert.extend_from_slice(&u64::MAX.to_le_bytes());
+    assert!(deserialize(&[0, 1, 0, 0, 0, 0, 0, 1], &insert).is_err());
+  }
+}
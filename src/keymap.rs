@@ -1,59 +1,529 @@
 use {
-  crate::document::DocEvent,
-  crossterm::event::{Event as TuiEvent, KeyCode},
+  crate::{
+    document::{DocEvent, Operator},
+    editor::{EditorEvent, WindowEvent},
+    layout::{Axis, Direction},
+    movement::{textobject::TextObject, Motion},
+    selection::SelectionKind,
+  },
+  crossterm::event::{Event as TuiEvent, KeyCode, KeyEvent, KeyModifiers},
 };
 
-#[derive(Default)]
-enum Mode {
+/// Prefix of the `ctrl-w` window commands.
+const CTRL_W: char = '\u{17}';
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
   #[default]
   Normal,
   Insert,
-  Visual,
+  Visual(SelectionKind),
+  Command,
+}
+
+/// What a key press asks for, either from the active document or from the
+/// editor as a whole.
+pub enum KeymapEvent {
+  Document(DocEvent),
+  Editor(EditorEvent),
+}
+
+impl From<DocEvent> for KeymapEvent {
+  fn from(event: DocEvent) -> Self {
+    Self::Document(event)
+  }
+}
+
+impl From<EditorEvent> for KeymapEvent {
+  fn from(event: EditorEvent) -> Self {
+    Self::Editor(event)
+  }
+}
+
+/// A normal or visual mode command being typed, vim's `[count] [operator
+/// [count]] motion` or a command of its own with an optional count.
+#[derive(Default)]
+struct PendingCommand {
+  /// Keys typed so far, shown until the command is complete.
+  keys: String,
+  count: Option<usize>,
+  operator: Option<Operator>,
+  /// Count typed after the operator, it multiplies the first one.
+  motion_count: Option<usize>,
+  /// First key of a two key command like `gg`, `fx` or `zh`, [`CTRL_W`]
+  /// for `ctrl-w`.
+  prefix: Option<char>,
+  /// Register named with `"x` in front of the command.
+  register: Option<char>,
+}
+
+impl PendingCommand {
+  /// Add a digit to the count being typed, the one after the operator when
+  /// there is one. A `0` only continues a count, on its own it is a motion.
+  fn push_digit(&mut self, digit: usize) -> bool {
+    let count = if self.operator.is_some() {
+      &mut self.motion_count
+    } else {
+      &mut self.count
+    };
+    if digit == 0 && count.is_none() {
+      return false;
+    }
+    *count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+    true
+  }
+
+  /// Both counts multiplied, `2d3w` deletes six words.
+  fn count(&self) -> Option<usize> {
+    match (self.count, self.motion_count) {
+      (None, None) => None,
+      (count, motion_count) => {
+        Some(count.unwrap_or(1).saturating_mul(motion_count.unwrap_or(1)))
+      }
+    }
+  }
 }
 
 #[derive(Default)]
 pub struct ViKeymap {
   mode: Mode,
+  command_line: String,
+  pending: PendingCommand,
 }
 
 impl ViKeymap {
-  fn process_normal_mode_event(
+  /// The second key of a command started with `prefix`.
+  fn process_prefixed_key(
     &mut self,
-    event: &TuiEvent,
-  ) -> Option<Vec<DocEvent>> {
-    match event {
-      TuiEvent::Key(key_event) => {
-        match key_event.code {
-          KeyCode::Char('i') => {
-            self.mode = Mode::Insert;
-            None
-          }
-          KeyCode::Char('v') => {
-            self.mode = Mode::Visual;
-            None
-          }
-          KeyCode::Esc => {
-            self.mode = Mode::Normal;
-            None
-          }
-          KeyCode::Char('h') => Some(vec![DocEvent::MoveCursorLeft]),
-          KeyCode::Char('j') => Some(vec![DocEvent::MoveCursorDown]),
-          KeyCode::Char('k') => Some(vec![DocEvent::MoveCursorUp]),
-          KeyCode::Char('l') => Some(vec![DocEvent::MoveCursorRight]),
-          KeyCode::Char('w') => Some(vec![DocEvent::MoveWordForward]),
-          KeyCode::Char('b') => Some(vec![DocEvent::MoveWordBackward]),
-          KeyCode::Char('e') => Some(vec![DocEvent::MoveWordEnd]),
-          KeyCode::Char('0') => Some(vec![DocEvent::MoveLineStart]),
-          KeyCode::Char('$') => Some(vec![DocEvent::MoveLineEnd]),
-          KeyCode::Char('G') => Some(vec![DocEvent::MoveDocumentEnd]),
-          KeyCode::Char('g') => {
-            // TODO: gg
-            Some(vec![DocEvent::MoveDocumentStart])
+    mut pending: PendingCommand,
+    prefix: char,
+    code: KeyCode,
+  ) -> Option<Vec<KeymapEvent>> {
+    if prefix == CTRL_W {
+      return Self::window_event(code)
+        .filter(|_| pending.operator.is_none())
+        .map(|event| vec![EditorEvent::Window(event).into()]);
+    }
+    if let ('"', KeyCode::Char(name)) = (prefix, code) {
+      pending.register = Some(name);
+      self.pending = pending;
+      return None;
+    }
+    let find = |forward, till| match code {
+      KeyCode::Char(c) => Some(Motion::FindChar { c, forward, till }),
+      _ => None,
+    };
+    if let ('i' | 'a', KeyCode::Char(c)) = (prefix, code) {
+      let object = Self::text_object(c)?;
+      return Some(self.object_events(&pending, object, prefix == 'a'));
+    }
+    let motion = match (prefix, code) {
+      ('f', _) => find(true, false),
+      ('F', _) => find(false, false),
+      ('t', _) => find(true, true),
+      ('T', _) => find(false, true),
+      ('g', KeyCode::Char('g')) => Some(
+        pending
+          .count()
+          .map_or(Motion::DocumentStart, Motion::GotoLine),
+      ),
+      _ => None,
+    };
+    if let Some(motion) = motion {
+      return Some(self.motion_events(&pending, motion));
+    }
+    if (prefix, code) == ('g', KeyCode::Char('~')) {
+      return self.process_operator(pending, Operator::ToggleCase);
+    }
+    if pending.operator.is_some() {
+      return None;
+    }
+    let extend = matches!(self.mode, Mode::Visual(_));
+    let count = pending.count().unwrap_or(1);
+    let columns = isize::try_from(count).unwrap_or(isize::MAX);
+    let event = match (prefix, code) {
+      ('g', KeyCode::Char('t')) => EditorEvent::NextTab,
+      ('g', KeyCode::Char('T')) => EditorEvent::PreviousTab,
+      ('g', KeyCode::Char('j') | KeyCode::Down) => {
+        EditorEvent::MoveRowDown { count, extend }
+      }
+      ('g', KeyCode::Char('k') | KeyCode::Up) => {
+        EditorEvent::MoveRowUp { count, extend }
+      }
+      ('z', KeyCode::Char('h') | KeyCode::Left) => {
+        EditorEvent::ScrollColumns(-columns)
+      }
+      ('z', KeyCode::Char('l') | KeyCode::Right) => {
+        EditorEvent::ScrollColumns(columns)
+      }
+      ('z', KeyCode::Char('s')) => EditorEvent::ScrollCursorStart,
+      ('z', KeyCode::Char('e')) => EditorEvent::ScrollCursorEnd,
+      _ => return None,
+    };
+    Some(vec![event.into()])
+  }
+
+  /// The motion a key moves the cursor by, in normal and visual mode. A
+  /// count makes `G` go to that line.
+  fn motion(code: KeyCode, count: Option<usize>) -> Option<Motion> {
+    let motion = match code {
+      KeyCode::Char('h') => Motion::Left,
+      KeyCode::Char('j') => Motion::Down,
+      KeyCode::Char('k') => Motion::Up,
+      KeyCode::Char('l') => Motion::Right,
+      KeyCode::Char('w') => Motion::WordForward,
+      KeyCode::Char('b') => Motion::WordBackward,
+      KeyCode::Char('e') => Motion::WordEnd,
+      KeyCode::Char('0') => Motion::LineStart,
+      KeyCode::Char('$') => Motion::LineEnd,
+      KeyCode::Char('G') => count.map_or(Motion::DocumentEnd, Motion::GotoLine),
+      _ => return None,
+    };
+    Some(motion)
+  }
+
+  /// The text object a key after `i` or `a` stands for.
+  fn text_object(c: char) -> Option<TextObject> {
+    let object = match c {
+      'w' => TextObject::Word { big: false },
+      'W' => TextObject::Word { big: true },
+      '"' | '\'' | '`' => TextObject::Quote(c),
+      '(' | ')' | 'b' => TextObject::Bracket('(', ')'),
+      '[' | ']' => TextObject::Bracket('[', ']'),
+      '{' | '}' | 'B' => TextObject::Bracket('{', '}'),
+      '<' | '>' => TextObject::Bracket('<', '>'),
+      's' => TextObject::Sentence,
+      'p' => TextObject::Paragraph,
+      't' => TextObject::Tag,
+      _ => return None,
+    };
+    Some(object)
+  }
+
+  fn operator(c: char) -> Option<Operator> {
+    let operator = match c {
+      'd' => Operator::Delete,
+      'c' => Operator::Change,
+      'y' => Operator::Yank,
+      '>' => Operator::Indent,
+      '<' => Operator::Outdent,
+      _ => return None,
+    };
+    Some(operator)
+  }
+
+  /// The events of a complete motion: the pending operator applied up to
+  /// where it goes, or the cursors moved there. Visual mode extends the
+  /// selections instead.
+  fn motion_events(
+    &mut self,
+    pending: &PendingCommand,
+    motion: Motion,
+  ) -> Vec<KeymapEvent> {
+    let count = pending.count().unwrap_or(1);
+    match (pending.operator, self.mode) {
+      (Some(operator), _) => {
+        self.operate_motion(operator, motion, count, pending.register)
+      }
+      (None, Mode::Visual(_)) => vec![DocEvent::Extend(motion, count).into()],
+      (None, _) => vec![DocEvent::Move(motion, count).into()],
+    }
+  }
+
+  /// The events of a complete text object: the pending operator applied to
+  /// it, or in visual mode the selections set to it. A paragraph makes
+  /// charwise visual mode linewise.
+  fn object_events(
+    &mut self,
+    pending: &PendingCommand,
+    object: TextObject,
+    around: bool,
+  ) -> Vec<KeymapEvent> {
+    let count = pending.count().unwrap_or(1);
+    if let Some(operator) = pending.operator {
+      if operator == Operator::Change {
+        self.mode = Mode::Insert;
+      }
+      return vec![EditorEvent::OperatorObject {
+        operator,
+        object,
+        around,
+        count,
+        register: pending.register,
+      }
+      .into()];
+    }
+    if object.is_linewise()
+      && self.mode == Mode::Visual(SelectionKind::Charwise)
+    {
+      self.mode = Mode::Visual(SelectionKind::Linewise);
+    }
+    vec![DocEvent::SelectObject {
+      object,
+      around,
+      count,
+    }
+    .into()]
+  }
+
+  /// An operator key, it waits for a motion in normal mode and acts on the
+  /// selections right away in visual mode.
+  fn process_operator(
+    &mut self,
+    mut pending: PendingCommand,
+    operator: Operator,
+  ) -> Option<Vec<KeymapEvent>> {
+    if let Mode::Visual(kind) = self.mode {
+      return self.operate_selections(operator, kind, pending.register);
+    }
+    match pending.operator {
+      None => {
+        pending.operator = Some(operator);
+        self.pending = pending;
+        None
+      }
+      // a doubled operator acts on lines, `dd` on this one and `3dd` on
+      // three
+      Some(pending_operator) if pending_operator == operator => {
+        let count = pending.count().unwrap_or(1);
+        let register = pending.register;
+        Some(self.operate_motion(operator, Motion::Down, count - 1, register))
+      }
+      Some(_) => None,
+    }
+  }
+
+  /// A change goes on in insert mode.
+  fn operate_motion(
+    &mut self,
+    operator: Operator,
+    motion: Motion,
+    count: usize,
+    register: Option<char>,
+  ) -> Vec<KeymapEvent> {
+    if operator == Operator::Change {
+      self.mode = Mode::Insert;
+    }
+    vec![EditorEvent::OperatorMotion(operator, motion, count, register).into()]
+  }
+
+  /// Operators leave visual mode, a change to insert text in place.
+  fn operate_selections(
+    &mut self,
+    operator: Operator,
+    kind: SelectionKind,
+    register: Option<char>,
+  ) -> Option<Vec<KeymapEvent>> {
+    self.mode = if operator == Operator::Change {
+      Mode::Insert
+    } else {
+      Mode::Normal
+    };
+    Some(vec![EditorEvent::Operator(operator, kind, register).into()])
+  }
+
+  /// Enter visual mode selecting as `kind`, or leave it when already
+  /// there.
+  fn toggle_visual(&mut self, kind: SelectionKind) -> Option<Vec<KeymapEvent>> {
+    if self.mode == Mode::Visual(kind) {
+      self.mode = Mode::Normal;
+      return Some(vec![DocEvent::CollapseSelections.into()]);
+    }
+    self.mode = Mode::Visual(kind);
+    None
+  }
+
+  /// The window command for the key after `ctrl-w`, with or without ctrl.
+  fn window_event(code: KeyCode) -> Option<WindowEvent> {
+    let event = match code {
+      KeyCode::Char('s' | 'S') => WindowEvent::Split(Axis::Horizontal),
+      KeyCode::Char('v') => WindowEvent::Split(Axis::Vertical),
+      KeyCode::Char('c' | 'q') => WindowEvent::Close,
+      KeyCode::Char('h') | KeyCode::Left => WindowEvent::Focus(Direction::Left),
+      KeyCode::Char('j') | KeyCode::Down => WindowEvent::Focus(Direction::Down),
+      KeyCode::Char('k') | KeyCode::Up => WindowEvent::Focus(Direction::Up),
+      KeyCode::Char('l') | KeyCode::Right => {
+        WindowEvent::Focus(Direction::Right)
+      }
+      KeyCode::Char('w') => WindowEvent::FocusNext,
+      KeyCode::Char('+') => WindowEvent::Resize(Axis::Horizontal, 1),
+      KeyCode::Char('-') => WindowEvent::Resize(Axis::Horizontal, -1),
+      KeyCode::Char('>') => WindowEvent::Resize(Axis::Vertical, 1),
+      KeyCode::Char('<') => WindowEvent::Resize(Axis::Vertical, -1),
+      KeyCode::Char('=') => WindowEvent::Equalize,
+      _ => return None,
+    };
+    Some(event)
+  }
+
+  /// A key in normal or visual mode, where it can be part of a command
+  /// with counts, an operator and a motion.
+  fn process_key(&mut self, key_event: &KeyEvent) -> Option<Vec<KeymapEvent>> {
+    let mut pending = std::mem::take(&mut self.pending);
+    let control = key_event.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key_event.modifiers.contains(KeyModifiers::ALT);
+    if let KeyCode::Char(c) = key_event.code {
+      if control {
+        pending.keys.push('^');
+        pending.keys.push(c.to_ascii_uppercase());
+      } else {
+        pending.keys.push(c);
+      }
+    }
+    if let Some(prefix) = pending.prefix.take() {
+      return self.process_prefixed_key(pending, prefix, key_event.code);
+    }
+
+    match key_event.code {
+      KeyCode::Char(c) if !control && !alt => {
+        if let Some(digit) = c.to_digit(10) {
+          if pending.push_digit(digit as usize) {
+            self.pending = pending;
+            return None;
           }
-          KeyCode::Char('x') => Some(vec![DocEvent::DeleteChar]),
-          _ => None,
+        }
+        if let Some(motion) = Self::motion(key_event.code, pending.count()) {
+          return Some(self.motion_events(&pending, motion));
+        }
+        // `i` and `a` start a text object after an operator or in visual
+        // mode, otherwise they insert
+        let object = matches!(c, 'i' | 'a')
+          && (pending.operator.is_some()
+            || matches!(self.mode, Mode::Visual(_)));
+        // `"x` names the register of the command, in front of it
+        let register = c == '"' && pending.operator.is_none();
+        if object || register || matches!(c, 'g' | 'z' | 'f' | 'F' | 't' | 'T')
+        {
+          pending.prefix = Some(c);
+          self.pending = pending;
+          return None;
+        }
+        if let Some(operator) = Self::operator(c) {
+          return self.process_operator(pending, operator);
+        }
+        // `g~~` toggles the case of the line
+        if c == '~' && pending.operator == Some(Operator::ToggleCase) {
+          return self.process_operator(pending, Operator::ToggleCase);
         }
       }
+      KeyCode::Char('w') if control => {
+        pending.prefix = Some(CTRL_W);
+        self.pending = pending;
+        return None;
+      }
+      _ => {}
+    }
+
+    // any other key cancels what is pending, Esc does nothing else then
+    let count = pending.count().unwrap_or(1);
+    if pending.operator.is_some()
+      || (key_event.code == KeyCode::Esc && !pending.keys.is_empty())
+    {
+      return None;
+    }
+    match self.mode {
+      Mode::Visual(kind) => {
+        self.process_visual_key(kind, key_event, pending.register)
+      }
+      _ => self.process_normal_key(key_event, count, pending.register),
+    }
+  }
+
+  /// A normal mode command that is not a motion or operator.
+  fn process_normal_key(
+    &mut self,
+    key_event: &KeyEvent,
+    count: usize,
+    register: Option<char>,
+  ) -> Option<Vec<KeymapEvent>> {
+    let repeat = |event: fn() -> DocEvent| {
+      Some((0..count).map(|_| event().into()).collect())
+    };
+    if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+      return match key_event.code {
+        KeyCode::Char('r') => repeat(|| DocEvent::Redo),
+        KeyCode::Char('v') => self.toggle_visual(SelectionKind::Blockwise),
+        KeyCode::Up => Some(vec![DocEvent::AddCursorAbove.into()]),
+        KeyCode::Down => Some(vec![DocEvent::AddCursorBelow.into()]),
+        _ => None,
+      };
+    }
+    if key_event.modifiers.contains(KeyModifiers::ALT) {
+      return match key_event.code {
+        KeyCode::Char('s') => Some(vec![DocEvent::SplitSelectionLines.into()]),
+        KeyCode::Char(')') => Some(vec![DocEvent::RotatePrimaryForward.into()]),
+        KeyCode::Char('(') => {
+          Some(vec![DocEvent::RotatePrimaryBackward.into()])
+        }
+        _ => None,
+      };
+    }
+    match key_event.code {
+      KeyCode::Char('i') => {
+        self.mode = Mode::Insert;
+        None
+      }
+      KeyCode::Char('a') => {
+        self.mode = Mode::Insert;
+        Some(vec![DocEvent::Move(Motion::Right, 1).into()])
+      }
+      KeyCode::Char('I') => {
+        self.mode = Mode::Insert;
+        Some(vec![DocEvent::Move(Motion::LineStart, 1).into()])
+      }
+      KeyCode::Char('A') => {
+        self.mode = Mode::Insert;
+        Some(vec![
+          DocEvent::Move(Motion::LineEnd, 1).into(),
+          DocEvent::Move(Motion::Right, 1).into(),
+        ])
+      }
+      KeyCode::Char('o') => {
+        self.mode = Mode::Insert;
+        Some(vec![
+          DocEvent::Move(Motion::LineEnd, 1).into(),
+          DocEvent::Move(Motion::Right, 1).into(),
+          DocEvent::InsertNewline.into(),
+        ])
+      }
+      KeyCode::Char('O') => {
+        self.mode = Mode::Insert;
+        Some(vec![
+          DocEvent::Move(Motion::LineStart, 1).into(),
+          DocEvent::InsertNewline.into(),
+          DocEvent::Move(Motion::Up, 1).into(),
+        ])
+      }
+      KeyCode::Char('v') => self.toggle_visual(SelectionKind::Charwise),
+      KeyCode::Char('V') => self.toggle_visual(SelectionKind::Linewise),
+      KeyCode::Char(':') => {
+        self.mode = Mode::Command;
+        self.command_line.clear();
+        None
+      }
+      // back to a single cursor
+      KeyCode::Esc => Some(vec![DocEvent::KeepPrimary.into()]),
+      // `x` deletes like `dl`, into a register
+      KeyCode::Char('x') => Some(self.operate_motion(
+        Operator::Delete,
+        Motion::Right,
+        count,
+        register,
+      )),
+      KeyCode::Char(c @ ('p' | 'P')) => Some(vec![EditorEvent::Put {
+        register,
+        before: c == 'P',
+        count,
+      }
+      .into()]),
+      KeyCode::Char('u') => repeat(|| DocEvent::Undo),
+      // toggle the case of `count` chars and move past them
+      KeyCode::Char('~') => {
+        let mut events =
+          self.operate_motion(Operator::ToggleCase, Motion::Right, count, None);
+        events.push(DocEvent::Move(Motion::Right, count).into());
+        Some(events)
+      }
       _ => None,
     }
   }
@@ -61,44 +531,355 @@ impl ViKeymap {
   fn process_insert_mode_event(
     &mut self,
     event: &TuiEvent,
-  ) -> Option<Vec<DocEvent>> {
+  ) -> Option<Vec<KeymapEvent>> {
+    if let TuiEvent::Paste(text) = event {
+      return Some(vec![DocEvent::Paste(text.clone()).into()]);
+    }
     if let TuiEvent::Key(key_event) = event {
-        match key_event.code {
+      match key_event.code {
         KeyCode::Esc => {
           self.mode = Mode::Normal;
-          None
+          Some(vec![DocEvent::Move(Motion::Left, 1).into()])
         }
-
+        // chords with ctrl or alt are no text to insert
+        KeyCode::Char(c)
+          if (key_event.modifiers - KeyModifiers::SHIFT).is_empty() =>
+        {
+          Some(vec![DocEvent::InsertChar(c).into()])
+        }
+        KeyCode::Enter => Some(vec![DocEvent::InsertNewline.into()]),
+        KeyCode::Tab => Some(vec![DocEvent::InsertTab.into()]),
+        KeyCode::Backspace => Some(vec![DocEvent::DeleteCharBackward.into()]),
+        KeyCode::Delete => Some(vec![DocEvent::DeleteCharForward.into()]),
+        KeyCode::Left => Some(vec![DocEvent::Move(Motion::Left, 1).into()]),
+        KeyCode::Right => Some(vec![DocEvent::Move(Motion::Right, 1).into()]),
+        KeyCode::Up => Some(vec![DocEvent::Move(Motion::Up, 1).into()]),
+        KeyCode::Down => Some(vec![DocEvent::Move(Motion::Down, 1).into()]),
+        KeyCode::Home => {
+          Some(vec![DocEvent::Move(Motion::LineStart, 1).into()])
+        }
+        KeyCode::End => Some(vec![
+          DocEvent::Move(Motion::LineEnd, 1).into(),
+          DocEvent::Move(Motion::Right, 1).into(),
+        ]),
         _ => None,
       }
     } else {
-        None
+      None
     }
   }
 
-  fn process_visual_mode_event(
+  /// A visual mode command that is not a motion or operator.
+  fn process_visual_key(
+    &mut self,
+    kind: SelectionKind,
+    key_event: &KeyEvent,
+    register: Option<char>,
+  ) -> Option<Vec<KeymapEvent>> {
+    if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+      return match key_event.code {
+        KeyCode::Char('v') => self.toggle_visual(SelectionKind::Blockwise),
+        _ => None,
+      };
+    }
+    match key_event.code {
+      KeyCode::Esc => {
+        self.mode = Mode::Normal;
+        Some(vec![DocEvent::CollapseSelections.into()])
+      }
+      KeyCode::Char('x') => {
+        self.operate_selections(Operator::Delete, kind, register)
+      }
+      KeyCode::Char('s') => {
+        self.operate_selections(Operator::Change, kind, register)
+      }
+      KeyCode::Char('~') => {
+        self.operate_selections(Operator::ToggleCase, kind, register)
+      }
+      KeyCode::Char('I') if kind == SelectionKind::Blockwise => {
+        self.mode = Mode::Insert;
+        Some(vec![DocEvent::InsertBlock.into()])
+      }
+      KeyCode::Char('A') if kind == SelectionKind::Blockwise => {
+        self.mode = Mode::Insert;
+        Some(vec![DocEvent::AppendBlock.into()])
+      }
+      KeyCode::Char('v') => self.toggle_visual(SelectionKind::Charwise),
+      KeyCode::Char('V') => self.toggle_visual(SelectionKind::Linewise),
+      KeyCode::Char('o') => Some(vec![DocEvent::FlipSelections.into()]),
+      _ => None,
+    }
+  }
+
+  fn process_command_mode_event(
     &mut self,
     event: &TuiEvent,
-  ) -> Option<Vec<DocEvent>> {
+  ) -> Option<Vec<KeymapEvent>> {
+    // the command line is a single line
+    if let TuiEvent::Paste(text) = event {
+      self.command_line.extend(text.chars().map(|c| {
+        if c.is_control() {
+          ' '
+        } else {
+          c
+        }
+      }));
+      return None;
+    }
     if let TuiEvent::Key(key_event) = event {
-        match key_event.code {
+      match key_event.code {
         KeyCode::Esc => {
           self.mode = Mode::Normal;
           None
         }
-
+        KeyCode::Enter => {
+          self.mode = Mode::Normal;
+          let command = std::mem::take(&mut self.command_line);
+          Some(vec![EditorEvent::Command(command).into()])
+        }
+        KeyCode::Backspace => {
+          // backspace on an empty command line leaves it, like vim
+          if self.command_line.pop().is_none() {
+            self.mode = Mode::Normal;
+          }
+          None
+        }
+        KeyCode::Char(c) => {
+          self.command_line.push(c);
+          None
+        }
         _ => None,
       }
     } else {
-        None
+      None
     }
   }
 
-  pub fn process_event(&mut self, event: &TuiEvent) -> Option<Vec<DocEvent>> {
+  pub fn mode(&self) -> Mode {
+    self.mode
+  }
+
+  /// Keys of a normal or visual mode command typed so far, empty when
+  /// none is pending.
+  pub fn pending_keys(&self) -> &str {
+    &self.pending.keys
+  }
+
+  /// The command being typed after `:`, if any.
+  pub fn command_line(&self) -> Option<&str> {
+    (self.mode == Mode::Command).then_some(self.command_line.as_str())
+  }
+
+  pub fn process_event(
+    &mut self,
+    event: &TuiEvent,
+  ) -> Option<Vec<KeymapEvent>> {
     match self.mode {
-      Mode::Normal => self.process_normal_mode_event(event),
+      Mode::Normal | Mode::Visual(_) => match event {
+        TuiEvent::Key(key_event) => self.process_key(key_event),
+        TuiEvent::Paste(text) if self.mode == Mode::Normal => {
+          self.pending = PendingCommand::default();
+          Some(vec![DocEvent::Paste(text.clone()).into()])
+        }
+        _ => None,
+      },
       Mode::Insert => self.process_insert_mode_event(event),
-      Mode::Visual => self.process_visual_mode_event(event),
+      Mode::Command => self.process_command_mode_event(event),
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn type_keys(keymap: &mut ViKeymap, keys: &str) -> Vec<KeymapEvent> {
+    keys
+      .chars()
+      .filter_map(|c| {
+        keymap.process_event(&TuiEvent::Key(KeyCode::Char(c).into()))
+      })
+      .flatten()
+      .collect()
+  }
+
+  #[test]
+  fn test_insert_mode_chords() {
+    let mut keymap = ViKeymap::default();
+    type_keys(&mut keymap, "i");
+    let key = |code, modifiers| TuiEvent::Key(KeyEvent::new(code, modifiers));
+    assert!(matches!(
+      keymap
+        .process_event(&key(KeyCode::Char('W'), KeyModifiers::SHIFT))
+        .as_deref(),
+      Some([KeymapEvent::Document(DocEvent::InsertChar('W'))])
+    ));
+    for modifiers in [KeyModifiers::CONTROL, KeyModifiers::ALT] {
+      let event = key(KeyCode::Char('w'), modifiers);
+      assert!(keymap.process_event(&event).is_none());
+    }
+  }
+
+  #[test]
+  fn test_rotate_primary_with_alt() {
+    let mut keymap = ViKeymap::default();
+    let alt =
+      |c| TuiEvent::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT));
+    assert!(matches!(
+      keymap.process_event(&alt(')')).as_deref(),
+      Some([KeymapEvent::Document(DocEvent::RotatePrimaryForward)])
+    ));
+    assert!(matches!(
+      keymap.process_event(&alt('(')).as_deref(),
+      Some([KeymapEvent::Document(DocEvent::RotatePrimaryBackward)])
+    ));
+    assert!(!type_keys(&mut keymap, ")").iter().any(|event| matches!(
+      event,
+      KeymapEvent::Document(DocEvent::RotatePrimaryForward)
+    )));
+  }
+
+  #[test]
+  fn test_counts_operators_and_motions() {
+    let mut keymap = ViKeymap::default();
+    assert!(type_keys(&mut keymap, "2d3").is_empty());
+    assert_eq!(keymap.pending_keys(), "2d3");
+    assert!(matches!(type_keys(&mut keymap, "w")[..], [
+      KeymapEvent::Editor(EditorEvent::OperatorMotion(
+        Operator::Delete,
+        Motion::WordForward,
+        6,
+        None
+      ))
+    ]));
+    assert_eq!(keymap.pending_keys(), "");
+
+    assert!(matches!(type_keys(&mut keymap, "3j")[..], [
+      KeymapEvent::Document(DocEvent::Move(Motion::Down, 3))
+    ]));
+    assert!(matches!(type_keys(&mut keymap, "10G")[..], [
+      KeymapEvent::Document(DocEvent::Move(Motion::GotoLine(10), _))
+    ]));
+    // a doubled operator acts on the line
+    assert!(matches!(type_keys(&mut keymap, "yy")[..], [
+      KeymapEvent::Editor(EditorEvent::OperatorMotion(
+        Operator::Yank,
+        Motion::Down,
+        0,
+        None
+      ))
+    ]));
+    assert!(matches!(type_keys(&mut keymap, "g~~")[..], [
+      KeymapEvent::Editor(EditorEvent::OperatorMotion(
+        Operator::ToggleCase,
+        Motion::Down,
+        0,
+        None
+      ))
+    ]));
+    // a key that is no motion cancels the operator
+    assert!(type_keys(&mut keymap, "dq").is_empty());
+    assert_eq!(keymap.pending_keys(), "");
+
+    assert!(matches!(type_keys(&mut keymap, "ct)")[..], [
+      KeymapEvent::Editor(EditorEvent::OperatorMotion(
+        Operator::Change,
+        Motion::FindChar {
+          c: ')',
+          forward: true,
+          till: true
+        },
+        1,
+        None
+      ))
+    ]));
+    assert_eq!(keymap.mode(), Mode::Insert);
+  }
+
+  #[test]
+  fn test_registers() {
+    let mut keymap = ViKeymap::default();
+    assert!(matches!(type_keys(&mut keymap, "\"Ayy")[..], [
+      KeymapEvent::Editor(EditorEvent::OperatorMotion(
+        Operator::Yank,
+        Motion::Down,
+        0,
+        Some('A')
+      ))
+    ]));
+    assert!(matches!(type_keys(&mut keymap, "3\"aP")[..], [
+      KeymapEvent::Editor(EditorEvent::Put {
+        register: Some('a'),
+        before: true,
+        count: 3,
+      })
+    ]));
+    assert!(matches!(type_keys(&mut keymap, "p")[..], [
+      KeymapEvent::Editor(EditorEvent::Put {
+        register: None,
+        before: false,
+        count: 1,
+      })
+    ]));
+  }
+
+  #[test]
+  fn test_text_objects() {
+    let mut keymap = ViKeymap::default();
+    assert!(matches!(type_keys(&mut keymap, "d2aw")[..], [
+      KeymapEvent::Editor(EditorEvent::OperatorObject {
+        operator: Operator::Delete,
+        object: TextObject::Word { big: false },
+        around: true,
+        count: 2,
+        register: None,
+      })
+    ]));
+    // `i` inserts without an operator
+    assert!(type_keys(&mut keymap, "i").is_empty());
+    assert_eq!(keymap.mode(), Mode::Insert);
+
+    let mut keymap = ViKeymap::default();
+    assert!(matches!(type_keys(&mut keymap, "vib")[..], [
+      KeymapEvent::Document(DocEvent::SelectObject {
+        object: TextObject::Bracket('(', ')'),
+        around: false,
+        count: 1,
+      })
+    ]));
+    type_keys(&mut keymap, "ap");
+    assert_eq!(keymap.mode(), Mode::Visual(SelectionKind::Linewise));
+  }
+
+  #[test]
+  fn test_scroll_columns_count() {
+    let mut keymap = ViKeymap::default();
+    assert!(matches!(type_keys(&mut keymap, "5zl")[..], [
+      KeymapEvent::Editor(EditorEvent::ScrollColumns(5))
+    ]));
+    assert!(matches!(type_keys(&mut keymap, "zh")[..], [
+      KeymapEvent::Editor(EditorEvent::ScrollColumns(-1))
+    ]));
+  }
+
+  #[test]
+  fn test_rows_extend_in_visual_mode() {
+    let mut keymap = ViKeymap::default();
+    assert!(matches!(type_keys(&mut keymap, "gj")[..], [
+      KeymapEvent::Editor(EditorEvent::MoveRowDown {
+        count: 1,
+        extend: false
+      })
+    ]));
+    assert!(matches!(type_keys(&mut keymap, "3gk")[..], [
+      KeymapEvent::Editor(EditorEvent::MoveRowUp {
+        count: 3,
+        extend: false
+      })
+    ]));
+    assert!(matches!(type_keys(&mut keymap, "vgjgk")[..], [
+      KeymapEvent::Editor(EditorEvent::MoveRowDown { extend: true, .. }),
+      KeymapEvent::Editor(EditorEvent::MoveRowUp { extend: true, .. }),
+    ]));
+  }
+}
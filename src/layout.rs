@@ -0,0 +1,453 @@
+//! Where the views of the editor go: a tree of splits with views as
+//! leaves, dividing the screen between them.
+
+use {crate::view::ViewId, tui::layout::Rect};
+
+/// How the children of a split are placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+  /// Above each other, as made by vim's `:split`.
+  Horizontal,
+  /// Next to each other, as made by vim's `:vsplit`.
+  Vertical,
+}
+
+/// A neighbour of a view on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  Left,
+  Down,
+  Up,
+  Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+  View(ViewId),
+  /// Children with their share of the space, two or more.
+  Split {
+    axis: Axis,
+    children: Vec<(Node, f32)>,
+  },
+}
+
+impl Node {
+  fn contains(&self, view_id: ViewId) -> bool {
+    match self {
+      Node::View(id) => *id == view_id,
+      Node::Split { children, .. } => {
+        children.iter().any(|(child, _)| child.contains(view_id))
+      }
+    }
+  }
+
+  fn first_view(&self) -> ViewId {
+    match self {
+      Node::View(id) => *id,
+      Node::Split { children, .. } => children[0].0.first_view(),
+    }
+  }
+
+  fn views(&self, views: &mut Vec<ViewId>) {
+    match self {
+      Node::View(id) => views.push(*id),
+      Node::Split { children, .. } => {
+        children.iter().for_each(|(child, _)| child.views(views))
+      }
+    }
+  }
+
+  fn place(&self, area: Rect, placement: &mut Placement) {
+    match self {
+      Node::View(id) => placement.views.push((*id, area)),
+      Node::Split { axis, children } => {
+        let areas = split_area(*axis, area, children);
+        for (i, ((child, _), area)) in children.iter().zip(&areas).enumerate() {
+          child.place(*area, placement);
+          // a border after every child but the last
+          if i + 1 < areas.len() {
+            placement.borders.push((*axis, border_after(*axis, *area)));
+          }
+        }
+      }
+    }
+  }
+}
+
+/// The cells between a child at `area` and the next one.
+fn border_after(axis: Axis, area: Rect) -> Rect {
+  match axis {
+    Axis::Horizontal => Rect::new(area.x, area.bottom(), area.width, 1),
+    Axis::Vertical => Rect::new(area.right(), area.y, 1, area.height),
+  }
+}
+
+/// Length of `area` along the axis children of a split are placed on.
+fn length(axis: Axis, area: Rect) -> u16 {
+  match axis {
+    Axis::Horizontal => area.height,
+    Axis::Vertical => area.width,
+  }
+}
+
+/// Divide `area` between `children` by their share, leaving a border of
+/// one cell between each of them.
+fn split_area(axis: Axis, area: Rect, children: &[(Node, f32)]) -> Vec<Rect> {
+  let borders = children.len().saturating_sub(1) as u16;
+  let available = length(axis, area).saturating_sub(borders) as f32;
+  let total: f32 = children.iter().map(|(_, share)| share).sum();
+
+  let mut taken = 0.0;
+  let mut offset = 0;
+  children
+    .iter()
+    .map(|(_, share)| {
+      let start = (available * taken / total).round() as u16;
+      taken += share;
+      let end = (available * taken / total).round() as u16;
+      let rect = match axis {
+        Axis::Horizontal => {
+          Rect::new(area.x, area.y + offset, area.width, end - start)
+        }
+        Axis::Vertical => {
+          Rect::new(area.x + offset, area.y, end - start, area.height)
+        }
+      };
+      offset += end - start + 1;
+      rect
+    })
+    .collect()
+}
+
+/// Where the views and the borders between them go on the screen.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Placement {
+  pub views: Vec<(ViewId, Rect)>,
+  /// Borders between the children of a split with that axis.
+  pub borders: Vec<(Axis, Rect)>,
+}
+
+impl Placement {
+  pub fn area(&self, view_id: ViewId) -> Option<Rect> {
+    self
+      .views
+      .iter()
+      .find(|(id, _)| *id == view_id)
+      .map(|(_, area)| *area)
+  }
+}
+
+/// The split layout of the views that are shown.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Layout {
+  root: Option<Node>,
+}
+
+impl Layout {
+  pub fn is_empty(&self) -> bool {
+    self.root.is_none()
+  }
+
+  pub fn contains(&self, view_id: ViewId) -> bool {
+    self
+      .root
+      .as_ref()
+      .is_some_and(|root| root.contains(view_id))
+  }
+
+  /// The views shown, left to right and top to bottom.
+  pub fn views(&self) -> Vec<ViewId> {
+    let mut views = Vec::new();
+    if let Some(root) = &self.root {
+      root.views(&mut views);
+    }
+    views
+  }
+
+  /// Show only `view_id`.
+  pub fn set_root(&mut self, view_id: ViewId) {
+    self.root = Some(Node::View(view_id));
+  }
+
+  /// Split the space of `at` to show `view_id` above it, or left of it for
+  /// [`Axis::Vertical`]. Returns whether `at` was found.
+  pub fn split(&mut self, at: ViewId, view_id: ViewId, axis: Axis) -> bool {
+    fn split_node(node: &mut Node, at: ViewId, view_id: ViewId, axis: Axis) {
+      match node {
+        Node::View(id) if *id == at => {
+          *node = Node::Split {
+            axis,
+            children: vec![(Node::View(view_id), 1.0), (Node::View(at), 1.0)],
+          };
+        }
+        Node::View(_) => {}
+        Node::Split {
+          axis: split_axis,
+          children,
+        } => {
+          let Some(i) =
+            children.iter().position(|(child, _)| child.contains(at))
+          else {
+            return;
+          };
+          // a split the same way gets another child instead of nesting
+          if *split_axis == axis && children[i].0 == Node::View(at) {
+            children[i].1 /= 2.0;
+            let share = children[i].1;
+            children.insert(i, (Node::View(view_id), share));
+          } else {
+            split_node(&mut children[i].0, at, view_id, axis);
+          }
+        }
+      }
+    }
+
+    if !self.contains(at) {
+      return false;
+    }
+    if let Some(root) = &mut self.root {
+      split_node(root, at, view_id, axis);
+    }
+    true
+  }
+
+  /// Stop showing `view_id`, its space goes to its neighbours. Returns the
+  /// view that took its place, if any are left.
+  pub fn remove(&mut self, view_id: ViewId) -> Option<ViewId> {
+    fn remove_from(node: &mut Node, view_id: ViewId) -> Option<ViewId> {
+      let Node::Split { children, .. } = node else {
+        return None;
+      };
+      let i = children
+        .iter()
+        .position(|(child, _)| child.contains(view_id))?;
+      if children[i].0 != Node::View(view_id) {
+        return remove_from(&mut children[i].0, view_id);
+      }
+
+      let (_, share) = children.remove(i);
+      // the view before it grows, or the one after for the first view
+      let next = i.saturating_sub(1);
+      children[next].1 += share;
+      let focus = children[next].0.first_view();
+      if children.len() == 1 {
+        *node = children.remove(0).0;
+      }
+      Some(focus)
+    }
+
+    match &mut self.root {
+      Some(Node::View(id)) if *id == view_id => {
+        self.root = None;
+        None
+      }
+      Some(root) => remove_from(root, view_id),
+      None => None,
+    }
+  }
+
+  /// Give every child of every split the same share.
+  pub fn equalize(&mut self) {
+    fn equalize_node(node: &mut Node) {
+      if let Node::Split { children, .. } = node {
+        for (child, share) in children {
+          *share = 1.0;
+          equalize_node(child);
+        }
+      }
+    }
+
+    if let Some(root) = &mut self.root {
+      equalize_node(root);
+    }
+  }
+
+  /// Grow `view_id` by `delta` cells along `axis`, shrinking the view next
+  /// to it. Growing the height takes a [`Axis::Horizontal`] split.
+  pub fn resize(
+    &mut self,
+    view_id: ViewId,
+    axis: Axis,
+    delta: isize,
+    area: Rect,
+  ) {
+    /// Resize the closest split along `axis` around `view_id`, `false`
+    /// when there is none.
+    fn resize_node(
+      node: &mut Node,
+      area: Rect,
+      view_id: ViewId,
+      axis: Axis,
+      delta: isize,
+    ) -> bool {
+      let Node::Split {
+        axis: split_axis,
+        children,
+      } = node
+      else {
+        return false;
+      };
+      let areas = split_area(*split_axis, area, children);
+      let Some(i) = children
+        .iter()
+        .position(|(child, _)| child.contains(view_id))
+      else {
+        return false;
+      };
+      if resize_node(&mut children[i].0, areas[i], view_id, axis, delta) {
+        return true;
+      }
+      if *split_axis != axis {
+        return false;
+      }
+
+      // move cells between the child and the one after it, or before it
+      // for the last child
+      let other = if i + 1 < children.len() { i + 1 } else { i - 1 };
+      let total: f32 = children.iter().map(|(_, share)| share).sum();
+      let cells: u16 = areas.iter().map(|area| length(axis, *area)).sum();
+      let cell = total / cells.max(1) as f32;
+      let lengths = |i: usize| length(axis, areas[i]) as isize;
+      // every view keeps at least one cell, when there are cells to keep
+      if lengths(i) + lengths(other) < 2 {
+        return true;
+      }
+      let delta = delta.clamp(1 - lengths(i), lengths(other) - 1);
+      children[i].1 += delta as f32 * cell;
+      children[other].1 -= delta as f32 * cell;
+      true
+    }
+
+    if let Some(root) = &mut self.root {
+      resize_node(root, area, view_id, axis, delta);
+    }
+  }
+
+  /// Where the views go in `area`.
+  pub fn place(&self, area: Rect) -> Placement {
+    let mut placement = Placement::default();
+    if let Some(root) = &self.root {
+      root.place(area, &mut placement);
+    }
+    placement
+  }
+
+  /// The view next to `view_id` in `direction` when placed in `area`. Of
+  /// several, the one closest to its top left corner.
+  pub fn neighbour(
+    &self,
+    view_id: ViewId,
+    direction: Direction,
+    area: Rect,
+  ) -> Option<ViewId> {
+    let placement = self.place(area);
+    let from = placement.area(view_id)?;
+    let overlaps = |a: (u16, u16), b: (u16, u16)| a.0 < b.1 && b.0 < a.1;
+
+    placement
+      .views
+      .iter()
+      .filter_map(|(id, to)| {
+        let columns = overlaps((from.x, from.right()), (to.x, to.right()));
+        let rows = overlaps((from.y, from.bottom()), (to.y, to.bottom()));
+        // distance to it, and how far it is off to the side
+        let distance = match direction {
+          Direction::Left if rows && to.right() <= from.x => {
+            (from.x - to.right(), to.y.abs_diff(from.y))
+          }
+          Direction::Right if rows && to.x >= from.right() => {
+            (to.x - from.right(), to.y.abs_diff(from.y))
+          }
+          Direction::Up if columns && to.bottom() <= from.y => {
+            (from.y - to.bottom(), to.x.abs_diff(from.x))
+          }
+          Direction::Down if columns && to.y >= from.bottom() => {
+            (to.y - from.bottom(), to.x.abs_diff(from.x))
+          }
+          _ => return None,
+        };
+        Some((distance, *id))
+      })
+      .min_by_key(|(distance, _)| *distance)
+      .map(|(_, id)| id)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, slotmap::SlotMap};
+
+  fn view_ids(count: usize) -> Vec<ViewId> {
+    let mut views = SlotMap::<ViewId, ()>::with_key();
+    (0..count).map(|_| views.insert(())).collect()
+  }
+
+  #[test]
+  fn test_split_and_place() {
+    let [a, b, c] = view_ids(3)[..] else {
+      unreachable!()
+    };
+    let mut layout = Layout::default();
+    layout.set_root(a);
+    assert!(layout.split(a, b, Axis::Vertical));
+    assert!(layout.split(a, c, Axis::Horizontal));
+    assert_eq!(layout.views(), [b, c, a]);
+
+    // b | c over a, with a border between each
+    let placement = layout.place(Rect::new(0, 0, 21, 11));
+    assert_eq!(placement.views, [
+      (b, Rect::new(0, 0, 10, 11)),
+      (c, Rect::new(11, 0, 10, 5)),
+      (a, Rect::new(11, 6, 10, 5)),
+    ]);
+    assert_eq!(placement.borders, [
+      (Axis::Vertical, Rect::new(10, 0, 1, 11)),
+      (Axis::Horizontal, Rect::new(11, 5, 10, 1)),
+    ]);
+
+    let area = Rect::new(0, 0, 21, 11);
+    assert_eq!(layout.neighbour(a, Direction::Left, area), Some(b));
+    assert_eq!(layout.neighbour(a, Direction::Up, area), Some(c));
+    assert_eq!(layout.neighbour(b, Direction::Right, area), Some(c));
+    assert_eq!(layout.neighbour(c, Direction::Right, area), None);
+  }
+
+  #[test]
+  fn test_resize_and_remove() {
+    let [a, b, c] = view_ids(3)[..] else {
+      unreachable!()
+    };
+    let area = Rect::new(0, 0, 21, 11);
+    let mut layout = Layout::default();
+    layout.set_root(a);
+    layout.split(a, b, Axis::Vertical);
+    layout.split(a, c, Axis::Vertical);
+    assert_eq!(layout.views(), [b, c, a]);
+
+    layout.resize(c, Axis::Vertical, 3, area);
+    let widths = |layout: &Layout| -> Vec<_> {
+      layout
+        .place(area)
+        .views
+        .iter()
+        .map(|(_, area)| area.width)
+        .collect()
+    };
+    assert_eq!(widths(&layout), [10, 7, 2]);
+    // nothing to take the height from
+    layout.resize(c, Axis::Horizontal, 3, area);
+    assert_eq!(widths(&layout), [10, 7, 2]);
+    layout.equalize();
+    assert_eq!(widths(&layout), [6, 7, 6]);
+    // no room to move cells in
+    layout.resize(c, Axis::Vertical, 3, Rect::new(0, 0, 0, 0));
+    assert_eq!(widths(&layout), [6, 7, 6]);
+
+    assert_eq!(layout.remove(c), Some(b));
+    assert_eq!(layout.views(), [b, a]);
+    assert_eq!(layout.remove(b), Some(a));
+    assert_eq!(layout.place(area).views, [(a, area)]);
+    assert_eq!(layout.remove(a), None);
+    assert!(layout.is_empty());
+  }
+}
@@ -1,11 +1,25 @@
 pub mod application;
+pub mod args;
+pub mod command;
+pub mod config;
+pub mod diff;
 pub mod document;
 pub mod editor;
+pub mod gutter;
+pub mod history;
+pub mod keymap;
+pub mod layout;
 pub mod movement;
+pub mod register;
+pub mod selection;
+pub mod tab;
+pub mod tabline;
 pub mod term;
+pub mod text_layout;
+pub mod transaction;
 pub mod tui;
-pub mod view;
 pub mod util;
-pub mod keymap;
+pub mod view;
+pub mod watcher;
 
 pub use document::Document;
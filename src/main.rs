@@ -1,17 +1,44 @@
 use {
   anyhow::Result,
-  blasted::{application::Application, term},
+  blasted::{
+    application::Application,
+    args::{Args, USAGE},
+    term,
+  },
+  std::io::Read,
 };
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()> {
+  let args = match Args::parse(std::env::args_os().skip(1)) {
+    Ok(args) => args,
+    Err(e) => {
+      eprintln!("{e}\n\n{USAGE}");
+      std::process::exit(2);
+    }
+  };
+  if args.help {
+    print!("{USAGE}");
+    return Ok(());
+  }
+  if args.version {
+    println!("blasted {}", env!("CARGO_PKG_VERSION"));
+    return Ok(());
+  }
+
+  // stdin is read before the terminal is claimed, the text may be typed in
+  let mut stdin = Vec::new();
+  if args.reads_stdin() {
+    std::io::stdin().read_to_end(&mut stdin)?;
+  }
+
   let (terminal, mut event_stream) = term::claim_terminal()?;
 
   // run the main application loop for the terminal
   let mut app = Application::new(terminal);
 
-  // find the editor plugin call open on it
-  app.editor().open("src/main.rs")?;
+  // find the editor plugin and open the files on it
+  args.open(app.editor(), &stdin)?;
 
   app.run(&mut event_stream).await?;
 
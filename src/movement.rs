@@ -1 +1,167 @@
+pub mod cursor;
 pub mod jumps;
+pub mod textobject;
+
+use {
+  crate::{text_layout::TextFormat, util::line_endings::line_end_char_index},
+  ropey::RopeSlice,
+};
+
+/// A cursor motion, like vim's `w` or `$`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+  Left,
+  Right,
+  Up,
+  Down,
+  WordForward,
+  WordBackward,
+  WordEnd,
+  LineStart,
+  LineEnd,
+  DocumentStart,
+  DocumentEnd,
+  /// First non-blank of a line, `1` for the first line, like `12G`.
+  GotoLine(usize),
+  /// To a char on the line, like `fx` forward or `Fx` backward. With
+  /// `till` it stops next to the char, like `tx` and `Tx`.
+  FindChar {
+    c: char,
+    forward: bool,
+    till: bool,
+  },
+}
+
+impl Motion {
+  /// Where the motion taken `count` times takes `pos`, and the screen
+  /// column to keep when moving on vertically. `column` is the one kept so
+  /// far.
+  pub fn apply(
+    self,
+    r: &RopeSlice,
+    pos: &(usize, usize),
+    column: usize,
+    count: usize,
+    format: &TextFormat,
+  ) -> ((usize, usize), Option<usize>) {
+    let step: fn(&RopeSlice, &(usize, usize)) -> (usize, usize) = match self {
+      Self::Left => cursor::left,
+      Self::Right => cursor::right,
+      Self::WordForward => jumps::next_word,
+      Self::WordBackward => jumps::prev_word,
+      Self::WordEnd => jumps::word_end,
+      Self::Up => {
+        return (cursor::up(r, pos, count, column, format), Some(column));
+      }
+      Self::Down => {
+        return (cursor::down(r, pos, count, column, format), Some(column));
+      }
+      Self::LineStart => return (cursor::line_start(r, pos), None),
+      // `3$` goes to the end of the second line below
+      Self::LineEnd => {
+        let line = (pos.0 + count.saturating_sub(1)).min(last_line(r));
+        return (cursor::line_end(r, &(line, 0)), Some(usize::MAX));
+      }
+      Self::DocumentStart => return (cursor::document_start(r, pos), None),
+      Self::DocumentEnd => return (cursor::document_end(r, pos), None),
+      Self::GotoLine(line) => {
+        let line = line.saturating_sub(1).min(last_line(r));
+        return ((line, first_non_blank(r, line)), None);
+      }
+      Self::FindChar { c, forward, till } => {
+        let Some((line, found)) = jumps::find_char(r, pos, c, count, forward)
+        else {
+          return (*pos, None);
+        };
+        let found = match (till, forward) {
+          (false, _) => found,
+          (true, true) => found - 1,
+          (true, false) => found + 1,
+        };
+        return ((line, found), None);
+      }
+    };
+    // stop early at the ends of the text, counts can be large
+    let mut pos = *pos;
+    for _ in 0..count {
+      let next = step(r, &pos);
+      if next == pos {
+        break;
+      }
+      pos = next;
+    }
+    (pos, None)
+  }
+
+  /// Motions that move between lines act on whole lines, `dj` deletes two
+  /// lines.
+  pub fn is_linewise(self) -> bool {
+    matches!(
+      self,
+      Self::Up
+        | Self::Down
+        | Self::DocumentStart
+        | Self::DocumentEnd
+        | Self::GotoLine(_)
+    )
+  }
+
+  /// Motions that act on the char they end on as well, like `e` and `$`.
+  /// The others stop in front of it, `dw` keeps the start of the next word.
+  pub fn is_inclusive(self) -> bool {
+    matches!(
+      self,
+      Self::WordEnd | Self::LineEnd | Self::FindChar { forward: true, .. }
+    )
+  }
+}
+
+/// Number of chars on `line`, not counting its line ending.
+pub fn line_len(r: &RopeSlice, line: usize) -> usize {
+  line_end_char_index(r, line) - r.line_to_char(line)
+}
+
+/// The last line a cursor can be placed on. A trailing line ending does
+/// not open a new line, the same way vim treats it.
+pub fn last_line(r: &RopeSlice) -> usize {
+  let last = r.len_lines() - 1;
+  if last > 0 && r.line(last).len_chars() == 0 {
+    last - 1
+  } else {
+    last
+  }
+}
+
+/// Position of the first char on `line` that is not a space or tab.
+pub fn first_non_blank(r: &RopeSlice, line: usize) -> usize {
+  r.line(line)
+    .chars()
+    .take(line_len(r, line))
+    .take_while(|c| *c == ' ' || *c == '\t')
+    .count()
+}
+
+/// Cursor at the first occurrence of `pattern`, taken literally. Matches
+/// do not span lines.
+pub fn find(r: &RopeSlice, pattern: &str) -> Option<(usize, usize)> {
+  r.lines().enumerate().find_map(|(line, text)| {
+    let text = text.to_string();
+    let byte_idx = text.find(pattern)?;
+    Some((line, text[..byte_idx].chars().count()))
+  })
+}
+
+/// Convert a `(line, pos)` cursor into a char index of the slice.
+pub fn char_index(r: &RopeSlice, (line, pos): &(usize, usize)) -> usize {
+  let line = (*line).min(r.len_lines() - 1);
+  r.line_to_char(line) + (*pos).min(line_len(r, line))
+}
+
+/// Convert a char index into a `(line, pos)` cursor, clamping it to the
+/// end of the slice.
+pub fn position(r: &RopeSlice, char_idx: usize) -> (usize, usize) {
+  let char_idx = char_idx.min(r.len_chars());
+  let line = r.char_to_line(char_idx);
+  let pos = char_idx - r.line_to_char(line);
+  (line, pos.min(line_len(r, line)))
+}
@@ -0,0 +1,174 @@
+use {
+  super::{last_line, line_len},
+  crate::{
+    text_layout::{char_offset, TextFormat},
+    util::char::{next_grapheme_boundary, prev_grapheme_boundary},
+  },
+  ropey::RopeSlice,
+};
+
+/// Start of the last grapheme of `line`, `0` on an empty line.
+fn last_grapheme(r: &RopeSlice, line: usize) -> usize {
+  let start = r.line_to_char(line);
+  let end = start + line_len(r, line);
+  prev_grapheme_boundary(r, end).max(start) - start
+}
+
+/// The grapheme of `line` drawn at the sticky screen `column`, `usize::MAX`
+/// is the last grapheme.
+fn column_on_line(
+  r: &RopeSlice,
+  line: usize,
+  column: usize,
+  format: &TextFormat,
+) -> usize {
+  if column == usize::MAX {
+    return last_grapheme(r, line);
+  }
+  char_offset(r.line(line), column, format)
+}
+
+pub fn left(r: &RopeSlice, (line, pos): &(usize, usize)) -> (usize, usize) {
+  let start = r.line_to_char(*line);
+  (
+    *line,
+    prev_grapheme_boundary(r, start + pos).max(start) - start,
+  )
+}
+
+pub fn right(r: &RopeSlice, (line, pos): &(usize, usize)) -> (usize, usize) {
+  let start = r.line_to_char(*line);
+  let len = line_len(r, *line);
+  (
+    *line,
+    (next_grapheme_boundary(r, start + pos) - start).min(len),
+  )
+}
+
+/// Move `count` lines up, landing on screen `column` or the end of the line
+/// when it is shorter.
+pub fn up(
+  r: &RopeSlice,
+  (line, _pos): &(usize, usize),
+  count: usize,
+  column: usize,
+  format: &TextFormat,
+) -> (usize, usize) {
+  let line = line.saturating_sub(count);
+  (line, column_on_line(r, line, column, format))
+}
+
+/// Move `count` lines down, landing on screen `column` or the end of the
+/// line when it is shorter.
+pub fn down(
+  r: &RopeSlice,
+  (line, _pos): &(usize, usize),
+  count: usize,
+  column: usize,
+  format: &TextFormat,
+) -> (usize, usize) {
+  let line = line.saturating_add(count).min(last_line(r));
+  (line, column_on_line(r, line, column, format))
+}
+
+pub fn line_start(
+  _r: &RopeSlice,
+  (line, _pos): &(usize, usize),
+) -> (usize, usize) {
+  (*line, 0)
+}
+
+/// Last grapheme of the line, like vim's `$`.
+pub fn line_end(
+  r: &RopeSlice,
+  (line, _pos): &(usize, usize),
+) -> (usize, usize) {
+  (*line, last_grapheme(r, *line))
+}
+
+pub fn document_start(_r: &RopeSlice, _pos: &(usize, usize)) -> (usize, usize) {
+  (0, 0)
+}
+
+pub fn document_end(r: &RopeSlice, _pos: &(usize, usize)) -> (usize, usize) {
+  (last_line(r), 0)
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, ropey::Rope};
+
+  #[test]
+  fn test_vertical_clamps_to_short_lines() {
+    let buffer = Rope::from_str("a long line\nab\r\n\nanother long line\n");
+    let r = buffer.slice(..);
+    let f = TextFormat::default();
+
+    assert_eq!(down(&r, &(0, 8), 1, 8, &f), (1, 2));
+    assert_eq!(down(&r, &(1, 2), 1, 8, &f), (2, 0));
+    assert_eq!(down(&r, &(2, 0), 1, 8, &f), (3, 8));
+    // the trailing line ending does not count as a line
+    assert_eq!(down(&r, &(3, 8), 1, 8, &f), (3, 8));
+    assert_eq!(up(&r, &(3, 8), 2, 8, &f), (1, 2));
+    assert_eq!(up(&r, &(1, 2), 5, 8, &f), (0, 8));
+    assert_eq!(up(&r, &(3, 8), 3, usize::MAX, &f), (0, 10));
+  }
+
+  #[test]
+  fn test_horizontal_respects_line_endings() {
+    let buffer = Rope::from_str("ab\r\ncd");
+    let r = buffer.slice(..);
+
+    assert_eq!(right(&r, &(0, 1)), (0, 2));
+    assert_eq!(right(&r, &(0, 2)), (0, 2));
+    assert_eq!(left(&r, &(1, 0)), (1, 0));
+    assert_eq!(line_end(&r, &(0, 0)), (0, 1));
+    assert_eq!(line_end(&r, &(1, 0)), (1, 1));
+    assert_eq!(document_end(&r, &(0, 0)), (1, 0));
+  }
+
+  #[test]
+  fn test_moves_over_graphemes() {
+    // decomposed é, a ZWJ family and a flag
+    let buffer =
+      Rope::from_str("ae\u{301}b\n👨\u{200d}👩\u{200d}👧🇳🇱\nabcdefgh\n");
+    let r = buffer.slice(..);
+    let f = TextFormat::default();
+
+    assert_eq!(right(&r, &(0, 1)), (0, 3));
+    assert_eq!(left(&r, &(0, 3)), (0, 1));
+    assert_eq!(right(&r, &(1, 0)), (1, 5));
+    assert_eq!(right(&r, &(1, 5)), (1, 7));
+    assert_eq!(line_end(&r, &(1, 0)), (1, 5));
+    // vertical moves never land inside a grapheme
+    assert_eq!(up(&r, &(2, 2), 1, 2, &f), (1, 0));
+    assert_eq!(up(&r, &(2, 2), 2, 2, &f), (0, 3));
+  }
+
+  #[test]
+  fn test_vertical_keeps_screen_column() {
+    let buffer = Rope::from_str("\tx\nabcdefgh\n漢字漢字\n");
+    let r = buffer.slice(..);
+    let f = TextFormat { tab_width: 4 };
+
+    // the tab covers columns 0 to 3
+    assert_eq!(up(&r, &(1, 4), 1, 4, &f), (0, 1));
+    assert_eq!(up(&r, &(1, 2), 1, 2, &f), (0, 0));
+    // column 5 is the right half of the third wide char
+    assert_eq!(down(&r, &(1, 5), 1, 5, &f), (2, 2));
+  }
+
+  #[cfg(feature = "unicode-lines")]
+  #[test]
+  fn test_unicode_line_endings() {
+    let buffer = Rope::from_str("ab\rcd\u{2028}ef\u{0085}");
+    let r = buffer.slice(..);
+    let f = TextFormat::default();
+
+    assert_eq!(line_end(&r, &(0, 0)), (0, 1));
+    assert_eq!(down(&r, &(0, 1), 1, 1, &f), (1, 1));
+    assert_eq!(right(&r, &(1, 1)), (1, 2));
+    assert_eq!(down(&r, &(1, 1), 2, 1, &f), (2, 1));
+    assert_eq!(document_end(&r, &(0, 0)), (2, 0));
+  }
+}
@@ -1,35 +1,154 @@
-use ropey::RopeSlice;
-use crate::util::char::CharExt;
+use {
+  super::{char_index, line_len, position},
+  crate::util::char::{
+    next_grapheme_boundary as next,
+    prev_grapheme_boundary as prev,
+    CharExt,
+  },
+  ropey::RopeSlice,
+};
 
-pub fn next_word(
-  r: &RopeSlice,
-  (line, pos): &(usize, usize),
-) -> (usize, usize) {
-  // TODO: no unwraps
-  let mut chars = r.get_chars_at(r.line_to_char(*line) + pos).unwrap();
-
-  // "wo|rd  second"
-  let mut whitespace = false;
-  let mut pos_offset = *pos;
-  let mut line_offset = *line;
-  #[allow(clippy::while_let_on_iterator)]
-  while let Some(c) = chars.next() {
-    if c.is_whitespace() {
-      whitespace = true;
-      if c.is_line_ending() {
-        line_offset += 1;
-        pos_offset = 0;
-        continue;
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub(super) enum CharClass {
+  LineEnding,
+  Whitespace,
+  Word,
+  Punctuation,
+}
+
+fn char_class(c: char) -> CharClass {
+  if c.is_line_ending() {
+    CharClass::LineEnding
+  } else if c.is_whitespace() {
+    CharClass::Whitespace
+  } else if c.is_alphanumeric() || c == '_' {
+    CharClass::Word
+  } else {
+    CharClass::Punctuation
+  }
+}
+
+/// Class of the grapheme starting at `char_idx`, decided by its first char
+/// so combining marks belong to the char before them. A CRLF is a line
+/// ending as a whole.
+pub(super) fn class_at(r: &RopeSlice, char_idx: usize) -> CharClass {
+  if r.char(next(r, char_idx) - 1).is_line_ending() {
+    CharClass::LineEnding
+  } else {
+    char_class(r.char(char_idx))
+  }
+}
+
+/// An empty line is a word of its own, word motions stop on it.
+fn is_empty_line(r: &RopeSlice, char_idx: usize) -> bool {
+  let line = r.char_to_line(char_idx);
+  r.line_to_char(line) == char_idx && line_len(r, line) == 0
+}
+
+pub fn next_word(r: &RopeSlice, pos: &(usize, usize)) -> (usize, usize) {
+  let len = r.len_chars();
+  let mut idx = char_index(r, pos);
+  if idx >= len {
+    return position(r, idx);
+  }
+
+  // "wo|rd  second", skip the rest of the current word
+  let class = class_at(r, idx);
+  if matches!(class, CharClass::Word | CharClass::Punctuation) {
+    while idx < len && class_at(r, idx) == class {
+      idx = next(r, idx);
+    }
+  }
+
+  // skip the whitespace up to the next word or empty line
+  while idx < len {
+    match class_at(r, idx) {
+      CharClass::Whitespace => idx = next(r, idx),
+      CharClass::LineEnding => {
+        idx = next(r, idx);
+        if idx < len && is_empty_line(r, idx) {
+          break;
+        }
       }
+      _ => break,
     }
+  }
+
+  position(r, idx)
+}
 
-    if c.is_alphanumeric() && whitespace {
+pub fn prev_word(r: &RopeSlice, pos: &(usize, usize)) -> (usize, usize) {
+  let mut idx = char_index(r, pos);
+
+  // skip the whitespace before the previous word or empty line
+  while idx > 0 {
+    let before = prev(r, idx);
+    let class = class_at(r, before);
+    if class == CharClass::LineEnding && is_empty_line(r, before) {
+      return position(r, before);
+    }
+    if !matches!(class, CharClass::Whitespace | CharClass::LineEnding) {
       break;
     }
-    pos_offset += 1;
+    idx = before;
   }
 
-  (line_offset, pos_offset)
+  // move to the start of the previous word
+  if idx > 0 {
+    let class = class_at(r, prev(r, idx));
+    while idx > 0 && class_at(r, prev(r, idx)) == class {
+      idx = prev(r, idx);
+    }
+  }
+
+  position(r, idx)
+}
+
+pub fn word_end(r: &RopeSlice, pos: &(usize, usize)) -> (usize, usize) {
+  let len = r.len_chars();
+  let mut idx = next(r, char_index(r, pos));
+
+  // skip the whitespace up to the next word
+  while idx < len
+    && matches!(
+      class_at(r, idx),
+      CharClass::Whitespace | CharClass::LineEnding
+    )
+  {
+    idx = next(r, idx);
+  }
+  if idx >= len {
+    return position(r, prev(r, len));
+  }
+
+  // move to the last grapheme of that word
+  let class = class_at(r, idx);
+  while next(r, idx) < len && class_at(r, next(r, idx)) == class {
+    idx = next(r, idx);
+  }
+
+  position(r, idx)
+}
+
+/// Position on the line of the `count`th `c` after the cursor, or before it
+/// when not `forward`. `None` when the line has fewer of them.
+pub fn find_char(
+  r: &RopeSlice,
+  &(line, pos): &(usize, usize),
+  c: char,
+  count: usize,
+  forward: bool,
+) -> Option<(usize, usize)> {
+  let text = r.line(line);
+  let len = line_len(r, line);
+  let is_c = |i: &usize| text.char(*i) == c;
+  let nth = count.saturating_sub(1);
+  let found = if forward {
+    (pos + 1..len).filter(is_c).nth(nth)
+  } else {
+    (0..pos.min(len)).rev().filter(is_c).nth(nth)
+  };
+  found.map(|found| (line, found))
 }
 
 #[test]
@@ -42,11 +161,74 @@ fn test_next_word() {
   assert_eq!(next_word(&buffer.slice(..), &(0, 12)), (0, 14));
 
   // end of line, it should continue on the next line
-  assert_eq!(next_word(&buffer.slice(..), &(0, 25)), (0, 30));
-  assert_eq!(next_word(&buffer.slice(..), &(0, 30)), (0, 34));
-  assert_eq!(next_word(&buffer.slice(..), &(0, 34)), (0, 39));
+  assert_eq!(next_word(&buffer.slice(..), &(0, 25)), (1, 0));
+  assert_eq!(next_word(&buffer.slice(..), &(1, 0)), (1, 4));
+
+  // end of the document, the cursor stops after the last char
+  assert_eq!(next_word(&buffer.slice(..), &(1, 4)), (1, 9));
+  assert_eq!(next_word(&buffer.slice(..), &(1, 9)), (1, 9));
+}
+
+#[test]
+fn test_next_word_punctuation_and_empty_lines() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("foo.bar(baz)\r\n\r\n  qux");
+
+  assert_eq!(next_word(&buffer.slice(..), &(0, 0)), (0, 3));
+  assert_eq!(next_word(&buffer.slice(..), &(0, 3)), (0, 4));
+  assert_eq!(next_word(&buffer.slice(..), &(0, 11)), (1, 0));
+  assert_eq!(next_word(&buffer.slice(..), &(1, 0)), (2, 2));
+}
+
+#[test]
+fn test_find_char() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("f(a, (b), c)\nx)");
+  let r = buffer.slice(..);
 
-  // TODO: out of checking / removing the unwraps()
+  assert_eq!(find_char(&r, &(0, 0), ')', 1, true), Some((0, 7)));
+  assert_eq!(find_char(&r, &(0, 0), ')', 2, true), Some((0, 11)));
+  // only on the line of the cursor
+  assert_eq!(find_char(&r, &(0, 0), ')', 3, true), None);
+  assert_eq!(find_char(&r, &(0, 11), '(', 1, false), Some((0, 5)));
+  assert_eq!(find_char(&r, &(0, 11), '(', 2, false), Some((0, 1)));
+  assert_eq!(find_char(&r, &(0, 1), '(', 1, false), None);
+}
+
+#[test]
+fn test_prev_word() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("one two\n\n  three(four)");
+
+  assert_eq!(prev_word(&buffer.slice(..), &(2, 8)), (2, 7));
+  assert_eq!(prev_word(&buffer.slice(..), &(2, 7)), (2, 2));
+  assert_eq!(prev_word(&buffer.slice(..), &(2, 2)), (1, 0));
+  assert_eq!(prev_word(&buffer.slice(..), &(1, 0)), (0, 4));
+  assert_eq!(prev_word(&buffer.slice(..), &(0, 5)), (0, 4));
+  assert_eq!(prev_word(&buffer.slice(..), &(0, 0)), (0, 0));
+}
+
+#[test]
+fn test_word_end() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("one two\n  three(four)");
+
+  assert_eq!(word_end(&buffer.slice(..), &(0, 0)), (0, 2));
+  assert_eq!(word_end(&buffer.slice(..), &(0, 2)), (0, 6));
+  assert_eq!(word_end(&buffer.slice(..), &(0, 6)), (1, 6));
+  assert_eq!(word_end(&buffer.slice(..), &(1, 6)), (1, 7));
+  assert_eq!(word_end(&buffer.slice(..), &(1, 11)), (1, 12));
+  assert_eq!(word_end(&buffer.slice(..), &(1, 12)), (1, 12));
+}
+
+#[test]
+fn test_words_with_combining_marks() {
+  use ropey::Rope;
+  // "café olé" with decomposed accents
+  let buffer = Rope::from_str("cafe\u{301} ole\u{301}");
 
-  dbg!(buffer.slice(39..));
+  assert_eq!(next_word(&buffer.slice(..), &(0, 0)), (0, 6));
+  assert_eq!(word_end(&buffer.slice(..), &(0, 0)), (0, 3));
+  assert_eq!(word_end(&buffer.slice(..), &(0, 3)), (0, 8));
+  assert_eq!(prev_word(&buffer.slice(..), &(0, 8)), (0, 6));
 }
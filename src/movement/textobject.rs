@@ -0,0 +1,632 @@
+//! Text objects, the ranges of chars `iw`, `a(` and the like select. The
+//! inner object leaves out the white space or delimiters around it, the
+//! around one takes them along.
+
+use {
+  super::{
+    jumps::{class_at, CharClass},
+    last_line,
+    line_len,
+  },
+  crate::util::{
+    char::{next_grapheme_boundary as next, prev_grapheme_boundary as prev},
+    line_endings::line_end_char_index,
+  },
+  ropey::RopeSlice,
+  std::ops::Range,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObject {
+  /// `iw` and `aw`, `iW` and `aW` for a big word of anything but white
+  /// space.
+  Word { big: bool },
+  /// Text between two of the quote on the line of the cursor, like `i"`.
+  Quote(char),
+  /// Text between an open and a close bracket around the cursor, nested
+  /// pairs are skipped, like `i(`.
+  Bracket(char, char),
+  /// Text up to a `.`, `!` or `?` followed by white space, `is` and `as`.
+  Sentence,
+  /// Lines up to a blank line, `ip` and `ap`.
+  Paragraph,
+  /// Content of an XML or HTML element, `it` and `at`.
+  Tag,
+}
+
+impl TextObject {
+  /// The chars of the object at `idx`, inner or `around`. A `count` takes
+  /// that many words, sentences or paragraphs, or the `count`th bracket or
+  /// tag pair out from the cursor.
+  pub fn range(
+    self,
+    r: &RopeSlice,
+    idx: usize,
+    around: bool,
+    count: usize,
+  ) -> Option<Range<usize>> {
+    let find = |idx| match self {
+      Self::Word { big } => word(r, idx, around, big),
+      Self::Sentence => sentence(r, idx, around),
+      Self::Paragraph => paragraph(r, idx, around),
+      Self::Quote(quote) => self::quote(r, idx, quote, around),
+      Self::Bracket(open, close) => bracket(r, idx, open, close, around, count),
+      Self::Tag => tag(r, idx, around, count),
+    };
+    let mut range = find(idx)?;
+    if matches!(self, Self::Word { .. } | Self::Sentence | Self::Paragraph) {
+      for _ in 1..count {
+        match find(range.end) {
+          Some(next) if next.end > range.end => range.end = next.end,
+          _ => break,
+        }
+      }
+    }
+    Some(range)
+  }
+
+  /// Paragraphs are made of whole lines.
+  pub fn is_linewise(self) -> bool {
+    self == Self::Paragraph
+  }
+}
+
+fn is_blank(c: char) -> bool {
+  c == ' ' || c == '\t'
+}
+
+/// A line of nothing but white space, it separates paragraphs.
+fn is_blank_line(r: &RopeSlice, line: usize) -> bool {
+  r.line(line).chars().take(line_len(r, line)).all(is_blank)
+}
+
+/// The run of graphemes of the class at `idx`, white space or a word. The
+/// around word takes the white space after it, or before it when there is
+/// none after, on white space the word after it.
+fn word(
+  r: &RopeSlice,
+  idx: usize,
+  around: bool,
+  big: bool,
+) -> Option<Range<usize>> {
+  let len = r.len_chars();
+  let idx = idx.min(len.checked_sub(1)?);
+  let class = |i| match class_at(r, i) {
+    CharClass::Punctuation if big => CharClass::Word,
+    class => class,
+  };
+  let run = |idx, of| {
+    let mut start = idx;
+    while start > 0 && class(prev(r, start)) == of {
+      start = prev(r, start);
+    }
+    let mut end = next(r, idx);
+    while end < len && class(end) == of {
+      end = next(r, end);
+    }
+    (start, end)
+  };
+
+  let at = class(idx);
+  if at == CharClass::LineEnding {
+    return None;
+  }
+  let (mut start, mut end) = run(idx, at);
+  if around {
+    let after = (end < len).then(|| class(end));
+    if at == CharClass::Whitespace {
+      if let Some(class @ (CharClass::Word | CharClass::Punctuation)) = after {
+        end = run(end, class).1;
+      }
+    } else if after == Some(CharClass::Whitespace) {
+      end = run(end, CharClass::Whitespace).1;
+    } else if start > 0 && class(prev(r, start)) == CharClass::Whitespace {
+      start = run(prev(r, start), CharClass::Whitespace).0;
+    }
+  }
+  Some(start..end)
+}
+
+/// Sentences of the paragraph at `idx`, they end after a `.`, `!` or `?`,
+/// and the brackets and quotes closing after it, followed by white space.
+/// The around sentence takes the white space after it, or before it for
+/// the last one.
+fn sentence(r: &RopeSlice, idx: usize, around: bool) -> Option<Range<usize>> {
+  let idx = idx.min(r.len_chars().checked_sub(1)?);
+  let line = r.char_to_line(idx);
+  if is_blank_line(r, line) {
+    return None;
+  }
+  let (first, last) = line_run(r, line);
+  let bounds = r.line_to_char(first)..line_end_char_index(r, last);
+
+  // `(start, end of its text, start of the next one)` of every sentence
+  let mut sentences = Vec::new();
+  let mut start = bounds.start;
+  let mut i = bounds.start;
+  while i < bounds.end {
+    let c = r.char(i);
+    i += 1;
+    if !matches!(c, '.' | '!' | '?') {
+      continue;
+    }
+    while i < bounds.end && matches!(r.char(i), ')' | ']' | '"' | '\'') {
+      i += 1;
+    }
+    let end = i;
+    while i < bounds.end && r.char(i).is_whitespace() {
+      i += 1;
+    }
+    if i > end || i == bounds.end {
+      sentences.push((start, end, i));
+      start = i;
+    }
+  }
+  if start < bounds.end {
+    sentences.push((start, bounds.end, bounds.end));
+  }
+
+  let index = sentences.iter().position(|(_, _, next)| idx < *next)?;
+  let (start, end, next) = sentences[index];
+  if !around {
+    return Some(start..end);
+  }
+  if next > end || index == 0 {
+    return Some(start..next);
+  }
+  Some(sentences[index - 1].1..end)
+}
+
+/// First and last line of the run of lines around `line` that are blank,
+/// or not, like it.
+fn line_run(r: &RopeSlice, line: usize) -> (usize, usize) {
+  let blank = is_blank_line(r, line);
+  let mut first = line;
+  while first > 0 && is_blank_line(r, first - 1) == blank {
+    first -= 1;
+  }
+  let mut last = line;
+  while last < last_line(r) && is_blank_line(r, last + 1) == blank {
+    last += 1;
+  }
+  (first, last)
+}
+
+/// The lines of the paragraph at `idx`, or of the blank lines there. The
+/// around paragraph takes the blank lines after it, or before it when
+/// there are none after, on blank lines the paragraph after them.
+fn paragraph(r: &RopeSlice, idx: usize, around: bool) -> Option<Range<usize>> {
+  let line = r.char_to_line(idx.min(r.len_chars()));
+  if line > last_line(r) {
+    return None;
+  }
+  let (mut first, mut last) = line_run(r, line);
+  if around {
+    if last < last_line(r) {
+      last = line_run(r, last + 1).1;
+    } else if first > 0 && !is_blank_line(r, line) {
+      first = line_run(r, first - 1).0;
+    }
+  }
+  Some(r.line_to_char(first)..r.line_to_char(last + 1))
+}
+
+/// Text between the pair of `quote` around `idx` on its line, or the first
+/// pair after it. Quotes escaped by a backslash are left out. The around
+/// object takes the quotes and the white space after them, or before them
+/// when there is none after.
+fn quote(
+  r: &RopeSlice,
+  idx: usize,
+  quote: char,
+  around: bool,
+) -> Option<Range<usize>> {
+  let line = r.char_to_line(idx.min(r.len_chars()));
+  let bounds = r.line_to_char(line)..line_end_char_index(r, line);
+  let quotes: Vec<_> = bounds
+    .clone()
+    .filter(|i| {
+      r.char(*i) == quote && (*i == bounds.start || r.char(i - 1) != '\\')
+    })
+    .collect();
+  let (open, close) = quotes
+    .chunks_exact(2)
+    .map(|pair| (pair[0], pair[1]))
+    .find(|(_, close)| idx <= *close)?;
+  if !around {
+    return Some(open + 1..close);
+  }
+
+  let mut end = close + 1;
+  while end < bounds.end && is_blank(r.char(end)) {
+    end += 1;
+  }
+  let mut start = open;
+  if end == close + 1 {
+    while start > bounds.start && is_blank(r.char(start - 1)) {
+      start -= 1;
+    }
+  }
+  Some(start..end)
+}
+
+/// The `open` before `idx` without a `close` for it in between.
+fn enclosing_open(
+  r: &RopeSlice,
+  idx: usize,
+  open: char,
+  close: char,
+) -> Option<usize> {
+  let mut depth = 0;
+  for i in (0..idx).rev() {
+    match r.char(i) {
+      c if c == close => depth += 1,
+      c if c == open && depth == 0 => return Some(i),
+      c if c == open => depth -= 1,
+      _ => {}
+    }
+  }
+  None
+}
+
+/// The `close` of the `open` at `idx`.
+fn matching_close(
+  r: &RopeSlice,
+  idx: usize,
+  open: char,
+  close: char,
+) -> Option<usize> {
+  let mut depth = 0;
+  for i in idx + 1..r.len_chars() {
+    match r.char(i) {
+      c if c == open => depth += 1,
+      c if c == close && depth == 0 => return Some(i),
+      c if c == close => depth -= 1,
+      _ => {}
+    }
+  }
+  None
+}
+
+/// Text between the `count`th pair of brackets out from `idx`, a bracket
+/// under the cursor belongs to the pair. The inner text of a block on
+/// lines of its own leaves the lines of its brackets alone, like vim.
+fn bracket(
+  r: &RopeSlice,
+  idx: usize,
+  open: char,
+  close: char,
+  around: bool,
+  count: usize,
+) -> Option<Range<usize>> {
+  let idx = idx.min(r.len_chars().checked_sub(1)?);
+  let mut left = if r.char(idx) == open {
+    idx
+  } else {
+    enclosing_open(r, idx, open, close)?
+  };
+  for _ in 1..count {
+    left = enclosing_open(r, left, open, close)?;
+  }
+  let right = matching_close(r, left, open, close)?;
+  if around {
+    return Some(left..right + 1);
+  }
+
+  let mut start = left + 1;
+  let open_line = r.char_to_line(left);
+  if start == line_end_char_index(r, open_line) && open_line < last_line(r) {
+    start = r.line_to_char(open_line + 1);
+  }
+  let close_line_start = r.line_to_char(r.char_to_line(right));
+  let end = if start <= close_line_start
+    && (close_line_start..right).all(|i| is_blank(r.char(i)))
+  {
+    close_line_start
+  } else {
+    right
+  };
+  Some(start..end.max(start))
+}
+
+/// A start or end tag of an element.
+struct Tag {
+  name: String,
+  closing: bool,
+  /// Index of its `<`.
+  start: usize,
+  /// Index after its `>`.
+  end: usize,
+}
+
+/// The tag whose `<` is at `start`, if there is one. Tags without an end,
+/// like `<br/>`, are left out.
+fn tag_at(r: &RopeSlice, start: usize) -> Option<Tag> {
+  let is_name =
+    |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.');
+  let mut name = String::new();
+  let mut closing = false;
+  let mut in_name = true;
+  let mut previous = '<';
+  for (i, c) in (start + 1..).zip(r.chars_at(start + 1)) {
+    match c {
+      '/' if i == start + 1 => closing = true,
+      '<' => return None,
+      '>' => {
+        return (!name.is_empty() && previous != '/').then_some(Tag {
+          name,
+          closing,
+          start,
+          end: i + 1,
+        })
+      }
+      _ if in_name && is_name(c) => name.push(c),
+      _ => in_name = false,
+    }
+    if name.is_empty() && !in_name {
+      return None;
+    }
+    previous = c;
+  }
+  None
+}
+
+/// The tag `idx` is on, if it is on one.
+fn tag_around(r: &RopeSlice, idx: usize) -> Option<Tag> {
+  let mut chars = r.chars_at(idx + 1);
+  let mut i = idx + 1;
+  while let Some(c) = chars.prev() {
+    i -= 1;
+    match c {
+      '<' => return tag_at(r, i).filter(|tag| tag.end > idx),
+      '>' if i < idx => return None,
+      _ => {}
+    }
+  }
+  None
+}
+
+/// The start tags before `split` whose element does not end before it,
+/// closest first. Elements ending before `split` are skipped along with the
+/// start tags without an end inside them.
+fn start_tags_before<'a>(
+  r: &RopeSlice<'a>,
+  split: usize,
+) -> impl Iterator<Item = Tag> + 'a {
+  let r = *r;
+  let mut chars = r.chars_at(split);
+  let mut i = split;
+  // names of the end tags whose start tag is still to come
+  let mut closed: Vec<String> = Vec::new();
+  std::iter::from_fn(move || {
+    while let Some(c) = chars.prev() {
+      i -= 1;
+      let Some(tag) = (c == '<').then(|| tag_at(&r, i)).flatten() else {
+        continue;
+      };
+      if tag.closing {
+        closed.push(tag.name);
+      } else if closed.is_empty() {
+        return Some(tag);
+      } else if closed.last() == Some(&tag.name) {
+        closed.pop();
+      }
+    }
+    None
+  })
+}
+
+/// The end tags from `split` on whose element does not start after it,
+/// closest first. Elements starting after `split` are skipped, start tags
+/// without an end inside them are dropped at the end tag around them.
+fn end_tags_after<'a>(
+  r: &RopeSlice<'a>,
+  split: usize,
+) -> impl Iterator<Item = Tag> + 'a {
+  let r = *r;
+  let mut chars = (split..).zip(r.chars_at(split));
+  // names of the start tags whose end tag is still to come
+  let mut opened: Vec<String> = Vec::new();
+  std::iter::from_fn(move || {
+    for (i, c) in chars.by_ref() {
+      let Some(tag) = (c == '<').then(|| tag_at(&r, i)).flatten() else {
+        continue;
+      };
+      if !tag.closing {
+        opened.push(tag.name);
+        continue;
+      }
+      match opened.iter().rposition(|name| *name == tag.name) {
+        Some(at) => opened.truncate(at),
+        None => return Some(tag),
+      }
+    }
+    None
+  })
+}
+
+/// Content of the `count`th element out from `idx`, between its start and
+/// end tag. The end tags after `idx` are paired with the start tags before
+/// it of the same name, start tags without an end are passed over. Both
+/// are found in a single scan out from `idx`.
+fn tag(
+  r: &RopeSlice,
+  idx: usize,
+  around: bool,
+  count: usize,
+) -> Option<Range<usize>> {
+  let idx = idx.min(r.len_chars().checked_sub(1)?);
+  // on a tag, the element it belongs to
+  let split = match tag_around(r, idx) {
+    Some(tag) if tag.closing => tag.start,
+    Some(tag) => tag.end,
+    None => idx + 1,
+  };
+  let mut starts = start_tags_before(r, split);
+  let mut found = 0;
+  for end in end_tags_after(r, split) {
+    let start = starts.find(|start| start.name == end.name)?;
+    found += 1;
+    if found == count.max(1) {
+      return Some(if around {
+        start.start..end.end
+      } else {
+        start.end..end.start
+      });
+    }
+  }
+  None
+}
+
+#[test]
+fn test_word_objects() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("one two.three  four\nfive");
+  let word = |idx, around, count| {
+    TextObject::Word { big: false }.range(&buffer.slice(..), idx, around, count)
+  };
+
+  assert_eq!(word(5, false, 1), Some(4..7));
+  assert_eq!(word(7, false, 1), Some(7..8));
+  // the white space after the word, or before it at the end of the line
+  assert_eq!(word(0, true, 1), Some(0..4));
+  assert_eq!(word(16, true, 1), Some(13..19));
+  // on white space, with the word after it
+  assert_eq!(word(13, false, 1), Some(13..15));
+  assert_eq!(word(13, true, 1), Some(13..19));
+  // white space counts as a word of its own
+  assert_eq!(word(0, false, 3), Some(0..7));
+  assert_eq!(word(0, true, 2), Some(0..7));
+  assert_eq!(word(19, false, 1), None);
+}
+
+#[test]
+fn test_big_word_objects() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("one two.three  four");
+  let word = |idx, around| {
+    TextObject::Word { big: true }.range(&buffer.slice(..), idx, around, 1)
+  };
+
+  assert_eq!(word(5, false), Some(4..13));
+  assert_eq!(word(8, true), Some(4..15));
+  assert_eq!(word(16, true), Some(13..19));
+}
+
+#[test]
+fn test_quote_objects() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("say \"a \\\"b\\\"\" and 'c'\n\"d\"");
+  let quote = |c, idx, around| {
+    TextObject::Quote(c).range(&buffer.slice(..), idx, around, 1)
+  };
+
+  // escaped quotes are part of the text
+  assert_eq!(quote('"', 6, false), Some(5..12));
+  assert_eq!(quote('"', 4, true), Some(4..14));
+  // the first pair after the cursor, with the white space before it
+  assert_eq!(quote('\'', 0, false), Some(19..20));
+  assert_eq!(quote('\'', 0, true), Some(17..21));
+  // only on the line of the cursor
+  assert_eq!(quote('"', 24, false), Some(23..24));
+  assert_eq!(quote('\'', 24, false), None);
+}
+
+#[test]
+fn test_bracket_objects() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("f(a, (b), c)\nif {\n  g();\n}\n");
+  let bracket = |open, close, idx, around, count| {
+    TextObject::Bracket(open, close).range(
+      &buffer.slice(..),
+      idx,
+      around,
+      count,
+    )
+  };
+
+  assert_eq!(bracket('(', ')', 6, false, 1), Some(6..7));
+  assert_eq!(bracket('(', ')', 6, true, 1), Some(5..8));
+  // nested pairs are skipped, a count goes further out
+  assert_eq!(bracket('(', ')', 9, false, 1), Some(2..11));
+  assert_eq!(bracket('(', ')', 6, false, 2), Some(2..11));
+  // on a bracket
+  assert_eq!(bracket('(', ')', 11, true, 1), Some(1..12));
+  // the lines of the block, without the lines of its braces
+  assert_eq!(bracket('{', '}', 20, false, 1), Some(18..25));
+  assert_eq!(bracket('{', '}', 20, true, 1), Some(16..26));
+  assert_eq!(bracket('[', ']', 6, false, 1), None);
+}
+
+#[test]
+fn test_paragraph_objects() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("one\ntwo\n\n  \nthree\n");
+  let paragraph = |idx, around| {
+    TextObject::Paragraph.range(&buffer.slice(..), idx, around, 1)
+  };
+
+  assert_eq!(paragraph(5, false), Some(0..8));
+  // the blank lines after it, white space only lines are blank too
+  assert_eq!(paragraph(5, true), Some(0..12));
+  // on blank lines, the paragraph after them
+  assert_eq!(paragraph(8, false), Some(8..12));
+  assert_eq!(paragraph(8, true), Some(8..18));
+  // the last paragraph takes the blank lines before it
+  assert_eq!(paragraph(13, true), Some(8..18));
+  assert_eq!(
+    TextObject::Paragraph.range(&buffer.slice(..), 0, false, 2),
+    Some(0..12)
+  );
+}
+
+#[test]
+fn test_sentence_objects() {
+  use ropey::Rope;
+  let buffer = Rope::from_str("One. Two (really!) three?\nFour\n\nFive.");
+  let sentence =
+    |idx, around| TextObject::Sentence.range(&buffer.slice(..), idx, around, 1);
+
+  assert_eq!(sentence(1, false), Some(0..4));
+  assert_eq!(sentence(1, true), Some(0..5));
+  // closing brackets end a sentence along with it
+  assert_eq!(sentence(6, false), Some(5..18));
+  // sentences go on over lines, up to the end of the paragraph
+  assert_eq!(sentence(28, false), Some(26..30));
+  // the last one takes the white space before it
+  assert_eq!(sentence(28, true), Some(25..30));
+  assert_eq!(sentence(33, false), Some(32..37));
+  assert_eq!(sentence(31, false), None);
+}
+
+#[test]
+fn test_tag_objects() {
+  use ropey::Rope;
+  let buffer =
+    Rope::from_str("<div id=\"a\"><p>one<br/>two</p> <b>x</b></div>");
+  let tag = |idx, around, count| {
+    TextObject::Tag.range(&buffer.slice(..), idx, around, count)
+  };
+
+  assert_eq!(tag(16, false, 1), Some(15..26));
+  assert_eq!(tag(16, true, 1), Some(12..30));
+  assert_eq!(tag(16, false, 2), Some(12..39));
+  // on a tag, the element it belongs to
+  assert_eq!(tag(1, true, 1), Some(0..45));
+  assert_eq!(tag(37, true, 1), Some(31..39));
+  assert_eq!(tag(16, false, 3), None);
+  // start tags without an end are passed over
+  let buffer = Rope::from_str("<T> <a>x</a> <ul><li>y<li>z</ul>");
+  let tag =
+    |idx, around| TextObject::Tag.range(&buffer.slice(..), idx, around, 1);
+  assert_eq!(tag(7, false), Some(7..8));
+  assert_eq!(tag(7, true), Some(4..12));
+  assert_eq!(tag(26, false), Some(17..27));
+  assert_eq!(tag(1, false), None);
+  // same names nest, a stray `<` is text
+  let buffer = Rope::from_str("<i>a < b <i>c</i></i>");
+  let tag =
+    |idx, around| TextObject::Tag.range(&buffer.slice(..), idx, around, 1);
+  assert_eq!(tag(4, false), Some(3..17));
+  assert_eq!(tag(12, true), Some(9..17));
+}
@@ -0,0 +1,254 @@
+//! Text kept by yanks and deletes, to put back later.
+
+use {
+  crate::selection::SelectionKind,
+  std::collections::HashMap,
+  thiserror::Error,
+};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RegisterError {
+  #[error("Invalid register name: {0}")]
+  InvalidName(char),
+  #[error("Register {0} is read-only")]
+  ReadOnly(char),
+  #[error("Nothing in register {0}")]
+  Empty(char),
+}
+
+pub type RegisterResult<T> = Result<T, RegisterError>;
+
+/// Name of the unnamed register, the one used without a name.
+pub const UNNAMED: char = '"';
+
+/// Text a register holds, with how it was selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+  pub kind: SelectionKind,
+  /// The text of every selection, or of every line of a block.
+  pub values: Vec<String>,
+}
+
+impl Register {
+  pub fn charwise(text: impl Into<String>) -> Self {
+    Self {
+      kind: SelectionKind::Charwise,
+      values: vec![text.into()],
+    }
+  }
+
+  /// True when the text spans lines, it then goes to the numbered
+  /// registers on a delete.
+  fn is_multiline(&self) -> bool {
+    self.kind == SelectionKind::Linewise
+      || self.values.len() > 1
+      || self.values.iter().any(|value| value.contains('\n'))
+  }
+
+  /// Add the text of `other` at the end, like an uppercase register name.
+  /// Charwise text joins the text of each selection, blocks get more lines
+  /// and anything else becomes whole lines.
+  fn append(&mut self, other: Register) {
+    match (self.kind, other.kind) {
+      (SelectionKind::Charwise, SelectionKind::Charwise)
+        if self.values.len() == other.values.len() =>
+      {
+        for (value, more) in self.values.iter_mut().zip(other.values) {
+          value.push_str(&more);
+        }
+      }
+      (SelectionKind::Blockwise, SelectionKind::Blockwise) => {
+        self.values.extend(other.values)
+      }
+      _ => {
+        let mut text = String::new();
+        for value in self.values.drain(..).chain(other.values) {
+          text.push_str(&value);
+          if !text.ends_with('\n') {
+            text.push('\n');
+          }
+        }
+        *self = Self {
+          kind: SelectionKind::Linewise,
+          values: vec![text],
+        };
+      }
+    }
+  }
+}
+
+/// The registers of the editor, as in vim: the unnamed `"`, `a` to `z`,
+/// the yank and delete history `0` to `9`, the small delete `-` and the
+/// black hole `_`. The last inserted text `.` and last search `/` can only
+/// be read, as can the file name `%` the editor fills in.
+#[derive(Debug, Default)]
+pub struct Registers {
+  registers: HashMap<char, Register>,
+}
+
+impl Registers {
+  /// Fail for names that cannot be written, before text is deleted for
+  /// them.
+  pub fn check_writable(name: char) -> RegisterResult<()> {
+    match name {
+      'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | UNNAMED => Ok(()),
+      '.' | '%' | '/' | ':' => Err(RegisterError::ReadOnly(name)),
+      _ => Err(RegisterError::InvalidName(name)),
+    }
+  }
+
+  /// What register `name` holds, an uppercase name reads the lowercase
+  /// register.
+  pub fn get(&self, name: char) -> Option<&Register> {
+    self.registers.get(&name.to_ascii_lowercase())
+  }
+
+  /// Keep yanked text in register `name`, in `0` without a name.
+  pub fn yank(
+    &mut self,
+    name: Option<char>,
+    register: Register,
+  ) -> RegisterResult<()> {
+    match name {
+      None | Some(UNNAMED) => {
+        self.registers.insert('0', register.clone());
+        self.set_unnamed(register);
+        Ok(())
+      }
+      Some(name) => self.write(name, register),
+    }
+  }
+
+  /// Keep deleted text in register `name`. Without a name, text spanning
+  /// lines goes to `1`, moving the older deletes up to `9`, and text within
+  /// a line goes to `-`.
+  pub fn delete(
+    &mut self,
+    name: Option<char>,
+    register: Register,
+  ) -> RegisterResult<()> {
+    if let Some(name) = name.filter(|name| *name != UNNAMED) {
+      return self.write(name, register);
+    }
+    if register.is_multiline() {
+      let digit = |n| char::from_digit(n, 10).expect("not a digit");
+      for number in (1..9).rev() {
+        if let Some(older) = self.registers.remove(&digit(number)) {
+          self.registers.insert(digit(number + 1), older);
+        }
+      }
+      self.registers.insert('1', register.clone());
+    } else {
+      self.registers.insert('-', register.clone());
+    }
+    self.set_unnamed(register);
+    Ok(())
+  }
+
+  /// Keep the text typed in the last insert session in `.`.
+  pub fn set_inserted(&mut self, text: String) {
+    self.registers.insert('.', Register::charwise(text));
+  }
+
+  /// Keep the last pattern searched for in `/`.
+  pub fn set_search(&mut self, pattern: String) {
+    self.registers.insert('/', Register::charwise(pattern));
+  }
+
+  /// Write `register` to the one named `name`, appending for an uppercase
+  /// name. The unnamed register gets the same text.
+  fn write(&mut self, name: char, register: Register) -> RegisterResult<()> {
+    Self::check_writable(name)?;
+    let register = match name {
+      '_' => return Ok(()),
+      'A'..='Z' => {
+        let name = name.to_ascii_lowercase();
+        let register = match self.registers.remove(&name) {
+          Some(mut old) => {
+            old.append(register);
+            old
+          }
+          None => register,
+        };
+        self.registers.insert(name, register.clone());
+        register
+      }
+      _ => {
+        self.registers.insert(name, register.clone());
+        register
+      }
+    };
+    self.set_unnamed(register);
+    Ok(())
+  }
+
+  fn set_unnamed(&mut self, register: Register) {
+    self.registers.insert(UNNAMED, register);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn linewise(text: &str) -> Register {
+    Register {
+      kind: SelectionKind::Linewise,
+      values: vec![text.into()],
+    }
+  }
+
+  #[test]
+  fn test_yank_and_delete_history() {
+    let mut registers = Registers::default();
+    registers.yank(None, Register::charwise("word")).unwrap();
+    registers.delete(None, linewise("one\n")).unwrap();
+    registers.delete(None, linewise("two\n")).unwrap();
+    registers.delete(None, Register::charwise("x")).unwrap();
+
+    let text = |registers: &Registers, name| {
+      registers.get(name).map(|register| register.values.concat())
+    };
+    assert_eq!(text(&registers, '0'), Some("word".into()));
+    assert_eq!(text(&registers, '1'), Some("two\n".into()));
+    assert_eq!(text(&registers, '2'), Some("one\n".into()));
+    assert_eq!(text(&registers, '-'), Some("x".into()));
+    assert_eq!(text(&registers, UNNAMED), Some("x".into()));
+
+    // the black hole keeps nothing, not even in the unnamed register
+    registers.delete(Some('_'), linewise("three\n")).unwrap();
+    assert_eq!(text(&registers, '1'), Some("two\n".into()));
+    assert_eq!(text(&registers, UNNAMED), Some("x".into()));
+    // nor does a delete into a named register touch the numbered ones
+    registers.delete(Some('a'), linewise("four\n")).unwrap();
+    assert_eq!(text(&registers, 'a'), Some("four\n".into()));
+    assert_eq!(text(&registers, '1'), Some("two\n".into()));
+    assert_eq!(text(&registers, '3'), None);
+    assert_eq!(
+      registers.yank(Some('.'), Register::charwise("a")),
+      Err(RegisterError::ReadOnly('.'))
+    );
+    assert_eq!(
+      registers.yank(Some('!'), Register::charwise("a")),
+      Err(RegisterError::InvalidName('!'))
+    );
+  }
+
+  #[test]
+  fn test_named_registers_append() {
+    let mut registers = Registers::default();
+    registers
+      .yank(Some('a'), Register::charwise("one"))
+      .unwrap();
+    registers
+      .yank(Some('A'), Register::charwise(" two"))
+      .unwrap();
+    assert_eq!(registers.get('a'), Some(&Register::charwise("one two")));
+    // a named yank leaves `0` alone
+    assert_eq!(registers.get('0'), None);
+    assert_eq!(registers.get(UNNAMED), registers.get('a'));
+
+    registers.yank(Some('A'), linewise("three\n")).unwrap();
+    assert_eq!(registers.get('A'), Some(&linewise("one two\nthree\n")));
+  }
+}
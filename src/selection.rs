@@ -0,0 +1,430 @@
+//! Selections of a view, ranges of text that motions and edits apply to
+//! all at once.
+//!
+//! Positions are `(line, pos)` cursors like everywhere else. A range covers
+//! the graphemes from its start up to and including its end, the way vim
+//! selects, so a single cursor is a range of one grapheme.
+//!
+//! Visual mode reads the same ranges as whole lines or as a block of screen
+//! columns, see [`SelectionKind`].
+
+use {
+  crate::{
+    movement::{self, cursor},
+    text_layout::{graphemes, visual_column, TextFormat},
+    util::char::next_grapheme_boundary,
+  },
+  ropey::RopeSlice,
+  std::ops,
+};
+
+pub type Position = (usize, usize);
+
+/// How the text between the ends of a range is selected, like vim's `v`,
+/// `V` and `ctrl-v`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKind {
+  /// Every grapheme from the start up to and including the end.
+  #[default]
+  Charwise,
+  /// The lines of both ends and every line between them.
+  Linewise,
+  /// The screen columns between both ends on each of their lines.
+  Blockwise,
+}
+
+/// A range from `anchor` to `head`, the head is where the cursor is.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+  pub anchor: Position,
+  pub head: Position,
+  /// Screen column the head wants to be on when moving vertically, kept
+  /// over short lines. `usize::MAX` sticks to the end of the line.
+  pub column: Option<usize>,
+}
+
+impl Range {
+  pub fn new(anchor: Position, head: Position) -> Self {
+    Self {
+      anchor,
+      head,
+      column: None,
+    }
+  }
+
+  /// A cursor without anything else selected.
+  pub fn point(pos: Position) -> Self {
+    Self::new(pos, pos)
+  }
+
+  pub fn from(&self) -> Position {
+    self.anchor.min(self.head)
+  }
+
+  pub fn to(&self) -> Position {
+    self.anchor.max(self.head)
+  }
+
+  pub fn is_point(&self) -> bool {
+    self.anchor == self.head
+  }
+
+  pub fn contains(&self, pos: Position) -> bool {
+    self.from() <= pos && pos <= self.to()
+  }
+
+  /// Move the head to `head`, the anchor follows unless `extend`.
+  pub fn put_head(self, head: Position, extend: bool) -> Self {
+    let anchor = if extend { self.anchor } else { head };
+    Self::new(anchor, head)
+  }
+
+  /// Both ends moved by `f`, keeping the wanted column.
+  pub fn map(self, mut f: impl FnMut(Position) -> Position) -> Self {
+    Self {
+      anchor: f(self.anchor),
+      head: f(self.head),
+      ..self
+    }
+  }
+
+  /// The range with anchor and head swapped.
+  pub fn flip(self) -> Self {
+    Self::new(self.head, self.anchor)
+  }
+
+  /// First and last screen column of the block between both ends. A head
+  /// stuck to the end of the line takes the block to the end of every
+  /// line, the last column is `usize::MAX` then.
+  pub fn block_columns(
+    &self,
+    r: &RopeSlice,
+    format: &TextFormat,
+  ) -> (usize, usize) {
+    let columns = |(line, pos): Position| {
+      let text = r.line(line);
+      let width = graphemes(text, format)
+        .find(|grapheme| grapheme.offset == pos)
+        .map_or(1, |grapheme| grapheme.width.max(1));
+      let column = visual_column(text, pos, format);
+      (column, column + width - 1)
+    };
+    let (anchor, head) = (columns(self.anchor), columns(self.head));
+    let right = if self.column == Some(usize::MAX) {
+      usize::MAX
+    } else {
+      anchor.1.max(head.1)
+    };
+    (anchor.0.min(head.0), right)
+  }
+
+  /// The chars selected as `kind`, a range per line for a block. Lines of a
+  /// block too short to reach it are left out.
+  pub fn regions(
+    &self,
+    r: &RopeSlice,
+    kind: SelectionKind,
+    format: &TextFormat,
+  ) -> Vec<ops::Range<usize>> {
+    let (from, to) = (self.from(), self.to());
+    match kind {
+      SelectionKind::Charwise => {
+        let start = movement::char_index(r, &from);
+        let end = next_grapheme_boundary(r, movement::char_index(r, &to));
+        let region = start..end.max(start);
+        vec![region]
+      }
+      SelectionKind::Linewise => {
+        let end = r.line_to_char((to.0 + 1).min(r.len_lines()));
+        let region = r.line_to_char(from.0)..end;
+        vec![region]
+      }
+      SelectionKind::Blockwise => {
+        let (left, right) = self.block_columns(r, format);
+        (from.0..=to.0)
+          .filter_map(|line| {
+            let start = r.line_to_char(line);
+            let mut covered = graphemes(r.line(line), format).filter(|g| {
+              g.column <= right && g.column + g.width.max(1) > left
+            });
+            let first = covered.next()?;
+            let end = covered
+              .last()
+              .map_or(first.offset + first.len, |last| last.offset + last.len);
+            Some(start + first.offset..start + end)
+          })
+          .collect()
+      }
+    }
+  }
+
+  /// One range covering both, facing the way `self` does.
+  fn merge(self, other: Self) -> Self {
+    let (from, to) = (self.from().min(other.from()), self.to().max(other.to()));
+    if self.head < self.anchor {
+      Self::new(to, from)
+    } else {
+      Self::new(from, to)
+    }
+  }
+}
+
+/// Ranges in text order that do not overlap, one of them primary. There is
+/// always at least one range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+  ranges: Vec<Range>,
+  primary: usize,
+}
+
+impl Default for Selection {
+  fn default() -> Self {
+    Self::point((0, 0))
+  }
+}
+
+impl Selection {
+  pub fn point(pos: Position) -> Self {
+    Self::single(Range::point(pos))
+  }
+
+  pub fn single(range: Range) -> Self {
+    Self {
+      ranges: vec![range],
+      primary: 0,
+    }
+  }
+
+  /// Sort `ranges` and merge the ones that overlap, the range `primary`
+  /// ends up in becomes the primary one.
+  pub fn new(ranges: Vec<Range>, primary: usize) -> Self {
+    assert!(!ranges.is_empty(), "a selection needs a range");
+    let mut ranges: Vec<_> = ranges.into_iter().enumerate().collect();
+    ranges.sort_by_key(|(_, range)| range.from());
+
+    let mut merged: Vec<Range> = Vec::with_capacity(ranges.len());
+    let mut new_primary = 0;
+    for (index, range) in ranges {
+      match merged.last_mut() {
+        Some(last) if range.from() <= last.to() => *last = last.merge(range),
+        _ => merged.push(range),
+      }
+      if index == primary {
+        new_primary = merged.len() - 1;
+      }
+    }
+    Self {
+      ranges: merged,
+      primary: new_primary,
+    }
+  }
+
+  pub fn primary(&self) -> Range {
+    self.ranges[self.primary]
+  }
+
+  pub fn primary_index(&self) -> usize {
+    self.primary
+  }
+
+  /// Where the cursor of the primary range is.
+  pub fn cursor(&self) -> Position {
+    self.primary().head
+  }
+
+  pub fn ranges(&self) -> &[Range] {
+    &self.ranges
+  }
+
+  pub fn len(&self) -> usize {
+    self.ranges.len()
+  }
+
+  /// Never true, a selection has at least one range.
+  pub fn is_empty(&self) -> bool {
+    self.ranges.is_empty()
+  }
+
+  pub fn iter(&self) -> std::slice::Iter<'_, Range> {
+    self.ranges.iter()
+  }
+
+  pub fn contains(&self, pos: Position) -> bool {
+    self.ranges.iter().any(|range| range.contains(pos))
+  }
+
+  /// Add `range` as the primary one.
+  pub fn push(mut self, range: Range) -> Self {
+    self.ranges.push(range);
+    let primary = self.ranges.len() - 1;
+    Self::new(self.ranges, primary)
+  }
+
+  /// Replace the primary range, the others stay.
+  pub fn with_primary(mut self, range: Range) -> Self {
+    self.ranges[self.primary] = range;
+    Self::new(self.ranges, self.primary)
+  }
+
+  /// Every range replaced by `f`, merging the ones that end up overlapping.
+  pub fn transform(self, f: impl FnMut(Range) -> Range) -> Self {
+    let ranges = self.ranges.into_iter().map(f).collect();
+    Self::new(ranges, self.primary)
+  }
+
+  /// Only the primary range.
+  pub fn keep_primary(self) -> Self {
+    Self::single(self.primary())
+  }
+
+  /// Make the range `offset` places after the primary one primary, counting
+  /// around.
+  pub fn rotate_primary(mut self, offset: isize) -> Self {
+    let len = self.ranges.len() as isize;
+    self.primary = (self.primary as isize + offset).rem_euclid(len) as usize;
+    self
+  }
+
+  /// Split every range into a range per line it covers, facing the way the
+  /// range did.
+  pub fn split_lines(self, r: &RopeSlice) -> Self {
+    let primary = self.primary;
+    let mut ranges = Vec::new();
+    let mut new_primary = 0;
+    for (index, range) in self.ranges.into_iter().enumerate() {
+      let (from, to) = (range.from(), range.to());
+      let start = ranges.len();
+      for line in from.0..=to.0 {
+        let first = if line == from.0 { from.1 } else { 0 };
+        let last = if line == to.0 {
+          to.1
+        } else {
+          cursor::line_end(r, &(line, 0)).1.max(first)
+        };
+        ranges.push(if range.head < range.anchor {
+          Range::new((line, last), (line, first))
+        } else {
+          Range::new((line, first), (line, last))
+        });
+      }
+      // the line of the cursor stays primary
+      if index == primary {
+        let offset = if range.head < range.anchor {
+          0
+        } else {
+          to.0 - from.0
+        };
+        new_primary = start + offset;
+      }
+    }
+    Self::new(ranges, new_primary)
+  }
+
+  /// The chars of every range selected as `kind`, in text order with the
+  /// ones that overlap merged.
+  pub fn regions(
+    &self,
+    r: &RopeSlice,
+    kind: SelectionKind,
+    format: &TextFormat,
+  ) -> Vec<ops::Range<usize>> {
+    merge_regions(
+      self
+        .ranges
+        .iter()
+        .flat_map(|range| range.regions(r, kind, format))
+        .collect(),
+    )
+  }
+
+  /// Clamp every range to the text of `r`.
+  pub fn clamp(self, r: &RopeSlice) -> Self {
+    self.transform(|range| {
+      range.map(|pos| movement::position(r, movement::char_index(r, &pos)))
+    })
+  }
+}
+
+/// `regions` of chars in text order, with the ones that overlap merged.
+pub fn merge_regions(
+  mut regions: Vec<ops::Range<usize>>,
+) -> Vec<ops::Range<usize>> {
+  regions.sort_by_key(|region| region.start);
+  let mut merged: Vec<ops::Range<usize>> = Vec::new();
+  for region in regions {
+    match merged.last_mut() {
+      Some(last) if region.start < last.end => {
+        last.end = last.end.max(region.end);
+      }
+      _ => merged.push(region),
+    }
+  }
+  merged
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, ropey::Rope};
+
+  #[test]
+  fn test_merge_and_rotate() {
+    let selection = Selection::new(
+      vec![
+        Range::point((2, 0)),
+        Range::new((0, 4), (0, 1)),
+        Range::point((0, 2)),
+        Range::point((1, 3)),
+      ],
+      2,
+    );
+    assert_eq!(selection.ranges(), [
+      Range::new((0, 4), (0, 1)),
+      Range::point((1, 3)),
+      Range::point((2, 0)),
+    ]);
+    // the primary cursor was merged into the first range
+    assert_eq!(selection.primary_index(), 0);
+    assert_eq!(selection.cursor(), (0, 1));
+
+    let selection = selection.rotate_primary(-1);
+    assert_eq!(selection.cursor(), (2, 0));
+    assert_eq!(selection.clone().rotate_primary(1).cursor(), (0, 1));
+    assert_eq!(selection.keep_primary(), Selection::point((2, 0)));
+  }
+
+  #[test]
+  fn test_split_lines() {
+    let text = Rope::from_str("one\n\nthree\n");
+    let r = text.slice(..);
+    let selection = Selection::single(Range::new((0, 1), (2, 2)));
+    let selection = selection.split_lines(&r);
+    assert_eq!(selection.ranges(), [
+      Range::new((0, 1), (0, 2)),
+      Range::point((1, 0)),
+      Range::new((2, 0), (2, 2)),
+    ]);
+    assert_eq!(selection.cursor(), (2, 2));
+  }
+
+  #[test]
+  fn test_regions() {
+    let text = Rope::from_str("one two\nx\n\tthree\n");
+    let r = text.slice(..);
+    let format = TextFormat { tab_width: 4 };
+    let range = Range::new((2, 2), (0, 4));
+    let regions = |range: Range, kind| range.regions(&r, kind, &format);
+
+    assert_eq!(regions(range, SelectionKind::Charwise), vec![(4..13)]);
+    assert_eq!(regions(range, SelectionKind::Linewise), vec![(0..17)]);
+    // columns 4 to 5, the tab covers columns 0 to 3 and line 1 is too short
+    assert_eq!(regions(range, SelectionKind::Blockwise), [4..6, 11..13]);
+    assert_eq!(range.block_columns(&r, &format), (4, 5));
+
+    // the tab overlaps the block, and `$` goes to the end of every line
+    let range = Range {
+      column: Some(usize::MAX),
+      ..Range::new((0, 2), (2, 5))
+    };
+    assert_eq!(regions(range, SelectionKind::Blockwise), [2..7, 10..16]);
+  }
+}
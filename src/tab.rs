@@ -0,0 +1,131 @@
+//! Tab pages, each with a layout of views of its own.
+
+use crate::{layout::Layout, view::ViewId};
+
+/// A tab page, the views it shows and the one with the cursor.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Tab {
+  pub layout: Layout,
+  pub active_view: Option<ViewId>,
+}
+
+/// The tab pages of the editor in order, there is always at least one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tabs {
+  tabs: Vec<Tab>,
+  active: usize,
+}
+
+impl Default for Tabs {
+  fn default() -> Self {
+    Self {
+      tabs: vec![Tab::default()],
+      active: 0,
+    }
+  }
+}
+
+impl Tabs {
+  pub fn count(&self) -> usize {
+    self.tabs.len()
+  }
+
+  /// Position of the active tab, `0` for the first.
+  pub fn index(&self) -> usize {
+    self.active
+  }
+
+  pub fn active(&self) -> &Tab {
+    &self.tabs[self.active]
+  }
+
+  pub fn active_mut(&mut self) -> &mut Tab {
+    &mut self.tabs[self.active]
+  }
+
+  pub fn iter(&self) -> std::slice::Iter<'_, Tab> {
+    self.tabs.iter()
+  }
+
+  /// Add an empty tab after the active one and make it active.
+  pub fn open(&mut self) -> &mut Tab {
+    self.active += 1;
+    self.tabs.insert(self.active, Tab::default());
+    &mut self.tabs[self.active]
+  }
+
+  /// Remove the active tab, the one after it becomes active. The last tab
+  /// stays.
+  pub fn close(&mut self) -> Option<Tab> {
+    if self.tabs.len() < 2 {
+      return None;
+    }
+    let tab = self.tabs.remove(self.active);
+    self.active = self.active.min(self.tabs.len() - 1);
+    Some(tab)
+  }
+
+  /// Make the tab at `index` active, counting around past the last tab.
+  pub fn select(&mut self, index: usize) {
+    self.active = index % self.tabs.len();
+  }
+
+  pub fn select_next(&mut self) {
+    self.select(self.active + 1);
+  }
+
+  pub fn select_previous(&mut self) {
+    self.select(self.active + self.tabs.len() - 1);
+  }
+
+  /// Move the active tab to `index`, or to the end when past it.
+  pub fn move_to(&mut self, index: usize) {
+    let tab = self.tabs.remove(self.active);
+    self.active = index.min(self.tabs.len());
+    self.tabs.insert(self.active, tab);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, slotmap::SlotMap};
+
+  #[test]
+  fn test_open_move_and_close() {
+    let mut views = SlotMap::<ViewId, ()>::with_key();
+    let mut tabs = Tabs::default();
+    // label every tab with a view to tell them apart
+    let mut open = |tabs: &mut Tabs| {
+      let view_id = views.insert(());
+      tabs.open().active_view = Some(view_id);
+      view_id
+    };
+    let first = tabs.active().active_view;
+    let a = Some(open(&mut tabs));
+    let b = Some(open(&mut tabs));
+    let order = |tabs: &Tabs| -> Vec<_> {
+      tabs.iter().map(|tab| tab.active_view).collect()
+    };
+    assert_eq!(order(&tabs), [first, a, b]);
+    assert_eq!(tabs.index(), 2);
+
+    tabs.select_next();
+    assert_eq!(tabs.index(), 0);
+    tabs.select_previous();
+    assert_eq!(tabs.index(), 2);
+
+    tabs.move_to(0);
+    assert_eq!(order(&tabs), [b, first, a]);
+    assert_eq!(tabs.index(), 0);
+    tabs.move_to(usize::MAX);
+    assert_eq!(order(&tabs), [first, a, b]);
+    assert_eq!(tabs.index(), 2);
+
+    assert_eq!(tabs.close().unwrap().active_view, b);
+    assert_eq!(tabs.index(), 1);
+    tabs.select(0);
+    tabs.close();
+    assert_eq!(order(&tabs), [a]);
+    assert_eq!(tabs.close(), None);
+  }
+}
@@ -0,0 +1,106 @@
+//! The tab pages of the editor along the top of the screen.
+
+use {
+  crate::{
+    application::{Application, Plugin, PluginError, ProcessEvent},
+    editor::Editor,
+  },
+  crossterm::event::Event as TuiEvent,
+  tui::{
+    buffer::Buffer as TuiBuffer,
+    layout::Rect,
+    style::{Modifier, Style},
+  },
+};
+
+/// Draws a label for every tab page on the top line, while the editor has
+/// more than one.
+#[derive(Default)]
+pub struct TabLine;
+
+/// Label of every tab: its number and the document of its active view,
+/// with a `+` when that has unsaved changes.
+pub fn labels(editor: &Editor) -> Vec<String> {
+  editor
+    .tabs
+    .iter()
+    .enumerate()
+    .map(|(i, tab)| {
+      let document = tab
+        .active_view
+        .and_then(|view_id| editor.views.get(view_id))
+        .and_then(|view| editor.documents.get(view.document_id));
+      let name = document.map_or_else(String::new, |document| {
+        let modified = if document.is_modified() { " +" } else { "" };
+        format!(" {}{modified}", document.display_name())
+      });
+      format!(" {}{name} ", i + 1)
+    })
+    .collect()
+}
+
+impl Plugin for TabLine {
+  fn id(&self) -> Option<&'static str> {
+    Some("tabline")
+  }
+
+  fn init(&self, _app: &Application) -> Result<(), PluginError> {
+    Ok(())
+  }
+
+  fn process_event(
+    &mut self,
+    _app: &mut Application,
+    _event: &TuiEvent,
+  ) -> Result<ProcessEvent, PluginError> {
+    Ok(ProcessEvent::Ignored)
+  }
+
+  fn render(
+    &mut self,
+    app: &mut Application,
+    area: &Rect,
+    frame: &mut TuiBuffer,
+  ) {
+    let editor = app.editor();
+    if !editor.tabline_visible() {
+      return;
+    }
+
+    let line = Style::default().add_modifier(Modifier::REVERSED);
+    frame.set_stringn(
+      area.x,
+      area.y,
+      " ".repeat(area.width as usize),
+      area.width as usize,
+      line,
+    );
+    let mut x = area.x;
+    for (i, label) in labels(editor).into_iter().enumerate() {
+      // the active tab stands out from the line
+      let style = if i == editor.tabs.index() {
+        Style::default().add_modifier(Modifier::BOLD)
+      } else {
+        line
+      };
+      let width = area.right().saturating_sub(x) as usize;
+      (x, _) = frame.set_stringn(x, area.y, label, width, style);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_labels() {
+    let mut editor = Editor::default();
+    let document_id = editor.create_document();
+    editor.create_view(document_id).unwrap();
+    assert_eq!(labels(&editor), [" 1 [No Name] "]);
+
+    editor.tabs.open();
+    assert_eq!(labels(&editor), [" 1 [No Name] ", " 2 "]);
+  }
+}
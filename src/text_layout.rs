@@ -0,0 +1,313 @@
+//! Where the text of a line ends up on the screen.
+//!
+//! Cursors are char offsets into a line, the screen works in columns. A
+//! tab stretches to the next tab stop, wide chars take two columns and
+//! control chars are drawn in caret notation, so the two drift apart.
+
+use {
+  crate::util::{
+    char::next_grapheme_boundary,
+    line_endings::rope_end_without_line_ending,
+  },
+  ropey::RopeSlice,
+  std::borrow::Cow,
+  unicode_width::UnicodeWidthStr,
+};
+
+/// How text is laid out on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextFormat {
+  /// Columns between tab stops.
+  pub tab_width: usize,
+}
+
+impl Default for TextFormat {
+  fn default() -> Self {
+    Self { tab_width: 4 }
+  }
+}
+
+/// A grapheme of a line as it is drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grapheme<'a> {
+  /// Char offset into the line.
+  pub offset: usize,
+  /// Chars of the line it covers.
+  pub len: usize,
+  /// Screen column it starts at.
+  pub column: usize,
+  /// Columns it takes up, at least one.
+  pub width: usize,
+  /// What is drawn, spaces for a tab and `^X` for a control char.
+  pub symbol: Cow<'a, str>,
+}
+
+/// How long lines are wrapped onto the next screen rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrapFormat<'a> {
+  /// Columns available on a row.
+  pub width: usize,
+  /// Drawn in front of every row after the first.
+  pub indicator: &'a str,
+  /// Start the rows after the first at the indent of the line.
+  pub preserve_indent: bool,
+}
+
+/// A screen row of a line, see [`wrap`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Row {
+  /// Char offset into the line of the first grapheme on the row.
+  pub start: usize,
+  /// Column of that grapheme in the unwrapped line.
+  pub column: usize,
+  /// Screen columns in front of it, for the indent and the wrap indicator.
+  pub indent: usize,
+}
+
+impl Row {
+  /// Screen column on this row of `column` of the unwrapped line.
+  pub fn screen_column(&self, column: usize) -> usize {
+    self.indent + column.saturating_sub(self.column)
+  }
+}
+
+/// Caret notation of a control char, `^@` up to `^_` and `^?` for delete.
+/// Other control chars show their code.
+fn caret_notation(c: char) -> String {
+  match c as u32 {
+    code @ 0..=0x1f => format!("^{}", char::from(code as u8 + 0x40)),
+    0x7f => "^?".to_string(),
+    code => format!("<{code:02x}>"),
+  }
+}
+
+/// Iterator over the graphemes of a line, see [`graphemes`].
+pub struct Graphemes<'a> {
+  line: RopeSlice<'a>,
+  end: usize,
+  offset: usize,
+  column: usize,
+  tab_width: usize,
+}
+
+impl<'a> Iterator for Graphemes<'a> {
+  type Item = Grapheme<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.offset >= self.end {
+      return None;
+    }
+
+    let next = next_grapheme_boundary(&self.line, self.offset).min(self.end);
+    let text = self.line.slice(self.offset..next);
+    let first = text.char(0);
+    let symbol = if first == '\t' {
+      let width = self.tab_width - self.column % self.tab_width;
+      Cow::Owned(" ".repeat(width))
+    } else if first.is_control() {
+      Cow::Owned(caret_notation(first))
+    } else {
+      text
+        .as_str()
+        .map_or_else(|| text.to_string().into(), Cow::Borrowed)
+    };
+    let width = symbol.width().max(1);
+
+    let grapheme = Grapheme {
+      offset: self.offset,
+      len: next - self.offset,
+      column: self.column,
+      width,
+      symbol,
+    };
+    self.offset = next;
+    self.column += width;
+    Some(grapheme)
+  }
+}
+
+/// The graphemes of `line` as they are drawn, without its line ending.
+pub fn graphemes<'a>(
+  line: RopeSlice<'a>,
+  format: &TextFormat,
+) -> Graphemes<'a> {
+  Graphemes {
+    end: rope_end_without_line_ending(&line),
+    line,
+    offset: 0,
+    column: 0,
+    tab_width: format.tab_width.max(1),
+  }
+}
+
+fn is_blank(grapheme: &Grapheme) -> bool {
+  grapheme.symbol.chars().all(char::is_whitespace)
+}
+
+/// Split `line` into screen rows no wider than `wrap.width`, breaking after
+/// whitespace when a row has any. A line has at least one row.
+pub fn wrap(
+  line: RopeSlice,
+  format: &TextFormat,
+  wrap: &WrapFormat,
+) -> Vec<Row> {
+  let graphemes: Vec<_> = graphemes(line, format).collect();
+  let indicator = wrap.indicator.width();
+  let mut indent = indicator;
+  if wrap.preserve_indent {
+    indent += graphemes
+      .iter()
+      .find(|grapheme| !is_blank(grapheme))
+      .map_or(0, |grapheme| grapheme.column);
+  }
+  // keep at least half of a row for the text
+  if indent > wrap.width / 2 {
+    indent = if indicator <= wrap.width / 2 {
+      indicator
+    } else {
+      0
+    };
+  }
+
+  let mut rows = vec![Row::default()];
+  let mut row_start = 0;
+  // a grapheme following whitespace, where the row can be broken nicely
+  let mut break_at = None;
+  let mut i = 0;
+  while let Some(grapheme) = graphemes.get(i) {
+    let row = rows[rows.len() - 1];
+    let available = wrap.width.saturating_sub(row.indent).max(1);
+    if i > row_start
+      && grapheme.column + grapheme.width - row.column > available
+    {
+      let at = break_at.filter(|at| *at > row_start).unwrap_or(i);
+      rows.push(Row {
+        start: graphemes[at].offset,
+        column: graphemes[at].column,
+        indent,
+      });
+      row_start = at;
+      break_at = None;
+      i = at;
+      continue;
+    }
+    if is_blank(grapheme) {
+      break_at = Some(i + 1);
+    }
+    i += 1;
+  }
+  rows
+}
+
+/// Index of the row of `rows` the char at `offset` is on.
+pub fn row_at(rows: &[Row], offset: usize) -> usize {
+  rows
+    .iter()
+    .rposition(|row| row.start <= offset)
+    .unwrap_or(0)
+}
+
+/// Screen column of the char at `offset` on `line`. Offsets past the end
+/// of the line land right after its last grapheme.
+pub fn visual_column(
+  line: RopeSlice,
+  offset: usize,
+  format: &TextFormat,
+) -> usize {
+  let mut column = 0;
+  for grapheme in graphemes(line, format) {
+    if grapheme.offset >= offset {
+      return grapheme.column;
+    }
+    column = grapheme.column + grapheme.width;
+  }
+  column
+}
+
+/// Char offset of the grapheme drawn at `column` on `line`. Columns past the
+/// end of the line give the end of the line.
+pub fn char_offset(
+  line: RopeSlice,
+  column: usize,
+  format: &TextFormat,
+) -> usize {
+  let mut offset = 0;
+  for grapheme in graphemes(line, format) {
+    if grapheme.column + grapheme.width > column {
+      return grapheme.offset;
+    }
+    offset = grapheme.offset + grapheme.len;
+  }
+  offset
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, ropey::Rope};
+
+  #[test]
+  fn test_tabs_wide_and_control_chars() {
+    let format = TextFormat { tab_width: 4 };
+    let text = Rope::from_str("a\tb漢\u{1b}c\n");
+    let line = text.slice(..);
+
+    let layout: Vec<_> = graphemes(line, &format)
+      .map(|g| (g.offset, g.column, g.symbol.into_owned()))
+      .collect();
+    assert_eq!(layout, [
+      (0, 0, "a".to_string()),
+      (1, 1, "   ".to_string()),
+      (2, 4, "b".to_string()),
+      (3, 5, "漢".to_string()),
+      (4, 7, "^[".to_string()),
+      (5, 9, "c".to_string()),
+    ]);
+
+    assert_eq!(visual_column(line, 2, &format), 4);
+    assert_eq!(visual_column(line, 6, &format), 10);
+    assert_eq!(visual_column(line, 10, &format), 10);
+    // a column inside a tab or a wide char is the grapheme drawn there
+    assert_eq!(char_offset(line, 2, &format), 1);
+    assert_eq!(char_offset(line, 6, &format), 3);
+    assert_eq!(char_offset(line, 9, &format), 5);
+    assert_eq!(char_offset(line, 42, &format), 6);
+  }
+
+  #[test]
+  fn test_wrap() {
+    let format = TextFormat::default();
+    let text = Rope::from_str("  one two three\n");
+    let line = text.slice(..);
+    let starts = |wrap: &WrapFormat| -> Vec<_> {
+      super::wrap(line, &format, wrap)
+        .iter()
+        .map(|row| (row.start, row.indent))
+        .collect()
+    };
+
+    let mut wrap = WrapFormat {
+      width: 20,
+      indicator: "",
+      preserve_indent: false,
+    };
+    assert_eq!(starts(&wrap), [(0, 0)]);
+
+    // breaks after the spaces, not inside a word
+    wrap.width = 8;
+    assert_eq!(starts(&wrap), [(0, 0), (6, 0), (10, 0)]);
+
+    wrap.indicator = "> ";
+    wrap.preserve_indent = true;
+    assert_eq!(starts(&wrap), [(0, 0), (6, 4), (10, 4), (14, 4)]);
+
+    // words longer than a row are broken anywhere
+    wrap.width = 6;
+    wrap.indicator = "";
+    assert_eq!(starts(&wrap), [(0, 0), (6, 2), (10, 2), (14, 2)]);
+
+    let rows = super::wrap(line, &format, &wrap);
+    assert_eq!(row_at(&rows, 8), 1);
+    assert_eq!(row_at(&rows, 15), 3);
+    assert_eq!(rows[1].screen_column(7), 3);
+  }
+}
@@ -0,0 +1,449 @@
+use {ropey::Rope, std::ops::Range};
+
+/// A single step of a [`ChangeSet`], counted in chars.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+  /// Keep the next n chars of the original text.
+  Retain(usize),
+  /// Remove the next n chars of the original text.
+  Delete(usize),
+  /// Insert text at the current position.
+  Insert(String),
+}
+
+/// Which side of an insertion a position at the same offset ends up on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+  Before,
+  After,
+}
+
+/// A change to a text: `(from, to, replacement)` in chars of the original.
+pub type Change = (usize, usize, Option<String>);
+
+/// A list of operations covering the whole text, turning a text of `len`
+/// chars into one of `len_after` chars.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+  changes: Vec<Operation>,
+  len: usize,
+  len_after: usize,
+}
+
+impl ChangeSet {
+  /// The identity change of `text`.
+  pub fn new(text: &Rope) -> Self {
+    let mut changes = Self::default();
+    changes.retain(text.len_chars());
+    changes
+  }
+
+  fn retain(&mut self, n: usize) {
+    if n == 0 {
+      return;
+    }
+    self.len += n;
+    self.len_after += n;
+    if let Some(Operation::Retain(count)) = self.changes.last_mut() {
+      *count += n;
+    } else {
+      self.changes.push(Operation::Retain(n));
+    }
+  }
+
+  fn delete(&mut self, n: usize) {
+    if n == 0 {
+      return;
+    }
+    self.len += n;
+    if let Some(Operation::Delete(count)) = self.changes.last_mut() {
+      *count += n;
+    } else {
+      self.changes.push(Operation::Delete(n));
+    }
+  }
+
+  fn insert(&mut self, text: String) {
+    if text.is_empty() {
+      return;
+    }
+    self.len_after += text.chars().count();
+    // keep inserts in front of deletes so equal changes look the same
+    match self.changes.as_mut_slice() {
+      [.., Operation::Insert(prev)]
+      | [.., Operation::Insert(prev), Operation::Delete(_)] => {
+        prev.push_str(&text);
+      }
+      [.., last @ Operation::Delete(_)] => {
+        let delete = std::mem::replace(last, Operation::Insert(text));
+        self.changes.push(delete);
+      }
+      _ => self.changes.push(Operation::Insert(text)),
+    }
+  }
+
+  /// Build a change set from sorted, non overlapping changes.
+  pub fn change<I>(text: &Rope, changes: I) -> Self
+  where
+    I: IntoIterator<Item = Change>,
+  {
+    let len = text.len_chars();
+    let mut result = Self::default();
+    let mut last = 0;
+    for (from, to, replacement) in changes {
+      debug_assert!(last <= from && from <= to && to <= len);
+      result.retain(from - last);
+      result.delete(to - from);
+      if let Some(replacement) = replacement {
+        result.insert(replacement);
+      }
+      last = to;
+    }
+    result.retain(len - last);
+    result
+  }
+
+  /// Rebuild a change set from its operations.
+  pub fn from_operations(
+    operations: impl IntoIterator<Item = Operation>,
+  ) -> Self {
+    let mut result = Self::default();
+    for op in operations {
+      match op {
+        Operation::Retain(n) => result.retain(n),
+        Operation::Delete(n) => result.delete(n),
+        Operation::Insert(text) => result.insert(text),
+      }
+    }
+    result
+  }
+
+  pub fn operations(&self) -> &[Operation] {
+    &self.changes
+  }
+
+  /// Length of the text this change set applies to.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Length of the text after applying this change set.
+  pub fn len_after(&self) -> usize {
+    self.len_after
+  }
+
+  /// True when applying the change set leaves the text as is.
+  pub fn is_empty(&self) -> bool {
+    self
+      .changes
+      .iter()
+      .all(|op| matches!(op, Operation::Retain(_)))
+  }
+
+  /// Combine with a change set that applies to the result of this one,
+  /// giving a single change set doing both.
+  pub fn compose(self, other: ChangeSet) -> ChangeSet {
+    assert_eq!(self.len_after, other.len, "change sets do not line up");
+
+    let mut result = ChangeSet::default();
+    let mut changes_a = self.changes.into_iter();
+    let mut changes_b = other.changes.into_iter();
+    let mut head_a = changes_a.next();
+    let mut head_b = changes_b.next();
+
+    loop {
+      use Operation::*;
+      match (head_a.take(), head_b.take()) {
+        (None, None) => break,
+        // deletes of the first change set are not seen by the second
+        (Some(Delete(n)), b) => {
+          result.delete(n);
+          head_a = changes_a.next();
+          head_b = b;
+        }
+        // inserts of the second change set are not seen by the first
+        (a, Some(Insert(text))) => {
+          result.insert(text);
+          head_a = a;
+          head_b = changes_b.next();
+        }
+        (None, _) | (_, None) => unreachable!("change sets do not line up"),
+        (Some(Retain(i)), Some(Retain(j))) => {
+          result.retain(i.min(j));
+          (head_a, head_b) = remainders(i, j, Retain, Retain);
+          head_a = head_a.or_else(|| changes_a.next());
+          head_b = head_b.or_else(|| changes_b.next());
+        }
+        (Some(Retain(i)), Some(Delete(j))) => {
+          result.delete(i.min(j));
+          (head_a, head_b) = remainders(i, j, Retain, Delete);
+          head_a = head_a.or_else(|| changes_a.next());
+          head_b = head_b.or_else(|| changes_b.next());
+        }
+        (Some(Insert(text)), Some(Delete(j))) => {
+          let len = text.chars().count();
+          head_a = (len > j).then(|| Insert(text.chars().skip(j).collect()));
+          head_b = (j > len).then(|| Delete(j - len));
+          head_a = head_a.or_else(|| changes_a.next());
+          head_b = head_b.or_else(|| changes_b.next());
+        }
+        (Some(Insert(text)), Some(Retain(j))) => {
+          let len = text.chars().count();
+          if len > j {
+            result.insert(text.chars().take(j).collect());
+            head_a = Some(Insert(text.chars().skip(j).collect()));
+          } else {
+            result.insert(text);
+          }
+          head_b = (j > len).then(|| Retain(j - len));
+          head_a = head_a.or_else(|| changes_a.next());
+          head_b = head_b.or_else(|| changes_b.next());
+        }
+      }
+    }
+
+    result
+  }
+
+  /// The change set undoing this one, `original` is the text before it was
+  /// applied.
+  pub fn invert(&self, original: &Rope) -> ChangeSet {
+    let mut result = ChangeSet::default();
+    let mut pos = 0;
+    for op in &self.changes {
+      match op {
+        Operation::Retain(n) => {
+          result.retain(*n);
+          pos += n;
+        }
+        Operation::Delete(n) => {
+          result.insert(original.slice(pos..pos + n).to_string());
+          pos += n;
+        }
+        Operation::Insert(text) => result.delete(text.chars().count()),
+      }
+    }
+    result
+  }
+
+  /// Apply to `text`, returns false and leaves the text alone when the
+  /// change set was made for a text of a different length.
+  pub fn apply(&self, text: &mut Rope) -> bool {
+    if text.len_chars() != self.len {
+      return false;
+    }
+
+    let mut pos = 0;
+    for op in &self.changes {
+      match op {
+        Operation::Retain(n) => pos += n,
+        Operation::Delete(n) => text.remove(pos..pos + n),
+        Operation::Insert(s) => {
+          text.insert(pos, s);
+          pos += s.chars().count();
+        }
+      }
+    }
+    true
+  }
+
+  /// Map a char position of the original text to the changed text.
+  pub fn map_pos(&self, pos: usize, assoc: Assoc) -> usize {
+    let mut old_pos = 0;
+    let mut new_pos = 0;
+    for op in &self.changes {
+      match op {
+        Operation::Retain(n) => {
+          if pos < old_pos + n {
+            return new_pos + pos - old_pos;
+          }
+          old_pos += n;
+          new_pos += n;
+        }
+        Operation::Delete(n) => {
+          if pos < old_pos + n {
+            return new_pos;
+          }
+          old_pos += n;
+        }
+        Operation::Insert(text) => {
+          if pos == old_pos && assoc == Assoc::Before {
+            return new_pos;
+          }
+          new_pos += text.chars().count();
+        }
+      }
+    }
+    new_pos
+  }
+}
+
+/// What is left of two operations after consuming the shorter one.
+fn remainders(
+  i: usize,
+  j: usize,
+  a: impl Fn(usize) -> Operation,
+  b: impl Fn(usize) -> Operation,
+) -> (Option<Operation>, Option<Operation>) {
+  ((i > j).then(|| a(i - j)), (j > i).then(|| b(j - i)))
+}
+
+/// A change set together with where the editing view's cursor ends up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transaction {
+  changes: ChangeSet,
+  cursor: Option<usize>,
+}
+
+impl Transaction {
+  pub fn new(text: &Rope) -> Self {
+    Self {
+      changes: ChangeSet::new(text),
+      cursor: None,
+    }
+  }
+
+  pub fn change<I>(text: &Rope, changes: I) -> Self
+  where
+    I: IntoIterator<Item = Change>,
+  {
+    Self {
+      changes: ChangeSet::change(text, changes),
+      cursor: None,
+    }
+  }
+
+  pub fn insert(text: &Rope, at: usize, insertion: &str) -> Self {
+    Self::change(text, [(at, at, Some(insertion.to_string()))])
+  }
+
+  pub fn delete(text: &Rope, range: Range<usize>) -> Self {
+    Self::change(text, [(range.start, range.end, None)])
+  }
+
+  /// Place the editing view's cursor on a char index of the changed text
+  /// instead of mapping it through the changes.
+  pub fn with_cursor(mut self, cursor: usize) -> Self {
+    self.cursor = Some(cursor);
+    self
+  }
+
+  pub fn changes(&self) -> &ChangeSet {
+    &self.changes
+  }
+
+  pub fn cursor(&self) -> Option<usize> {
+    self.cursor
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+
+  /// Combine with a transaction following this one.
+  pub fn compose(self, other: Transaction) -> Transaction {
+    let cursor = match other.cursor {
+      Some(cursor) => Some(cursor),
+      None => self
+        .cursor
+        .map(|cursor| other.changes.map_pos(cursor, Assoc::After)),
+    };
+    Transaction {
+      changes: self.changes.compose(other.changes),
+      cursor,
+    }
+  }
+
+  /// The transaction undoing this one, `original` is the text before it was
+  /// applied.
+  pub fn invert(&self, original: &Rope) -> Transaction {
+    Transaction {
+      changes: self.changes.invert(original),
+      cursor: None,
+    }
+  }
+
+  pub fn apply(&self, text: &mut Rope) -> bool {
+    self.changes.apply(text)
+  }
+}
+
+impl From<ChangeSet> for Transaction {
+  fn from(changes: ChangeSet) -> Self {
+    Self {
+      changes,
+      cursor: None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_apply_and_invert() {
+    let original = Rope::from_str("hello world");
+    let changes = ChangeSet::change(&original, [
+      (0, 5, Some("goodbye".into())),
+      (6, 6, Some("cruel ".into())),
+    ]);
+    assert_eq!(changes.len(), 11);
+    assert_eq!(changes.len_after(), 19);
+
+    let mut text = original.clone();
+    assert!(changes.apply(&mut text));
+    assert_eq!(text, "goodbye cruel world");
+
+    assert!(changes.invert(&original).apply(&mut text));
+    assert_eq!(text, original);
+
+    // a change set made for another text is refused
+    assert!(!changes.apply(&mut Rope::from_str("hi")));
+  }
+
+  #[test]
+  fn test_compose() {
+    let mut text = Rope::from_str("one two three");
+    let a = ChangeSet::change(&text, [(4, 7, Some("2".into()))]);
+    let mut after_a = text.clone();
+    a.apply(&mut after_a);
+    assert_eq!(after_a, "one 2 three");
+
+    let b = ChangeSet::change(&after_a, [
+      (0, 4, None),
+      (5, 5, Some("!".into())),
+      (8, 11, Some("EE".into())),
+    ]);
+    let composed = a.compose(b);
+    assert!(composed.apply(&mut text));
+    assert_eq!(text, "2! thEE");
+  }
+
+  #[test]
+  fn test_map_pos() {
+    let text = Rope::from_str("abcdef");
+    let changes =
+      ChangeSet::change(&text, [(1, 1, Some("XY".into())), (3, 5, None)]);
+
+    assert_eq!(changes.map_pos(0, Assoc::Before), 0);
+    assert_eq!(changes.map_pos(1, Assoc::Before), 1);
+    assert_eq!(changes.map_pos(1, Assoc::After), 3);
+    assert_eq!(changes.map_pos(2, Assoc::Before), 4);
+    // positions inside a deletion collapse onto it
+    assert_eq!(changes.map_pos(4, Assoc::Before), 5);
+    assert_eq!(changes.map_pos(5, Assoc::Before), 5);
+    assert_eq!(changes.map_pos(6, Assoc::Before), 6);
+  }
+
+  #[test]
+  fn test_transaction_cursor() {
+    let text = Rope::from_str("abc");
+    let insert = Transaction::insert(&text, 1, "xy").with_cursor(3);
+    let mut after = text.clone();
+    insert.apply(&mut after);
+
+    let composed = insert.compose(Transaction::insert(&after, 0, "__"));
+    assert_eq!(composed.cursor(), Some(5));
+  }
+}
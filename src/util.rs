@@ -1,4 +1,4 @@
-
-pub mod line_endings;
 pub mod char;
-
+pub mod encoding;
+pub mod fs;
+pub mod line_endings;
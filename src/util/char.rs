@@ -1,4 +1,8 @@
-use super::line_endings::LineEnding;
+use {
+  super::line_endings::LineEnding,
+  ropey::RopeSlice,
+  unicode_segmentation::{GraphemeCursor, GraphemeIncomplete},
+};
 
 pub trait CharExt {
   fn is_line_ending(&self) -> bool;
@@ -9,3 +13,114 @@ impl CharExt for char {
     LineEnding::from_char(*self).is_some()
   }
 }
+
+/// Char index of the grapheme boundary before `char_idx`, `0` at the start.
+pub fn prev_grapheme_boundary(slice: &RopeSlice, char_idx: usize) -> usize {
+  let char_idx = char_idx.min(slice.len_chars());
+  let byte_idx = slice.char_to_byte(char_idx);
+  let (mut chunk, mut chunk_byte_idx, mut chunk_char_idx, _) =
+    slice.chunk_at_byte(byte_idx);
+  let mut cursor = GraphemeCursor::new(byte_idx, slice.len_bytes(), true);
+
+  loop {
+    match cursor.prev_boundary(chunk, chunk_byte_idx) {
+      Ok(None) => return 0,
+      Ok(Some(n)) => {
+        let chars = chunk[..n - chunk_byte_idx].chars().count();
+        return chunk_char_idx + chars;
+      }
+      Err(GraphemeIncomplete::PrevChunk) => {
+        (chunk, chunk_byte_idx, chunk_char_idx, _) =
+          slice.chunk_at_byte(chunk_byte_idx - 1);
+      }
+      Err(GraphemeIncomplete::PreContext(n)) => {
+        let (context, context_byte_idx, _, _) = slice.chunk_at_byte(n - 1);
+        cursor.provide_context(context, context_byte_idx);
+      }
+      Err(e) => unreachable!("unexpected grapheme state {:?}", e),
+    }
+  }
+}
+
+/// Char index of the grapheme boundary after `char_idx`, the length of the
+/// slice at the end.
+pub fn next_grapheme_boundary(slice: &RopeSlice, char_idx: usize) -> usize {
+  let char_idx = char_idx.min(slice.len_chars());
+  let byte_idx = slice.char_to_byte(char_idx);
+  let (mut chunk, mut chunk_byte_idx, mut chunk_char_idx, _) =
+    slice.chunk_at_byte(byte_idx);
+  let mut cursor = GraphemeCursor::new(byte_idx, slice.len_bytes(), true);
+
+  loop {
+    match cursor.next_boundary(chunk, chunk_byte_idx) {
+      Ok(None) => return slice.len_chars(),
+      Ok(Some(n)) => {
+        let chars = chunk[..n - chunk_byte_idx].chars().count();
+        return chunk_char_idx + chars;
+      }
+      Err(GraphemeIncomplete::NextChunk) => {
+        chunk_byte_idx += chunk.len();
+        (chunk, _, chunk_char_idx, _) = slice.chunk_at_byte(chunk_byte_idx);
+      }
+      Err(GraphemeIncomplete::PreContext(n)) => {
+        let (context, context_byte_idx, _, _) = slice.chunk_at_byte(n - 1);
+        cursor.provide_context(context, context_byte_idx);
+      }
+      Err(e) => unreachable!("unexpected grapheme state {:?}", e),
+    }
+  }
+}
+
+pub fn is_grapheme_boundary(slice: &RopeSlice, char_idx: usize) -> bool {
+  let char_idx = char_idx.min(slice.len_chars());
+  let byte_idx = slice.char_to_byte(char_idx);
+  let (chunk, chunk_byte_idx, _, _) = slice.chunk_at_byte(byte_idx);
+  let mut cursor = GraphemeCursor::new(byte_idx, slice.len_bytes(), true);
+
+  loop {
+    match cursor.is_boundary(chunk, chunk_byte_idx) {
+      Ok(is_boundary) => return is_boundary,
+      Err(GraphemeIncomplete::PreContext(n)) => {
+        let (context, context_byte_idx, _, _) = slice.chunk_at_byte(n - 1);
+        cursor.provide_context(context, context_byte_idx);
+      }
+      Err(e) => unreachable!("unexpected grapheme state {:?}", e),
+    }
+  }
+}
+
+/// `char_idx` moved back to the start of the grapheme it is in.
+pub fn floor_grapheme_boundary(slice: &RopeSlice, char_idx: usize) -> usize {
+  if is_grapheme_boundary(slice, char_idx) {
+    char_idx.min(slice.len_chars())
+  } else {
+    prev_grapheme_boundary(slice, char_idx)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, ropey::Rope};
+
+  #[test]
+  fn test_grapheme_boundaries() {
+    // e + combining acute, a family emoji joined by ZWJ and a flag
+    let text = Rope::from_str("e\u{301}👨\u{200d}👩\u{200d}👧🇳🇱\r\n");
+    let r = text.slice(..);
+
+    let mut boundaries = vec![0];
+    while *boundaries.last().unwrap() < r.len_chars() {
+      boundaries.push(next_grapheme_boundary(&r, *boundaries.last().unwrap()));
+    }
+    assert_eq!(boundaries, [0, 2, 7, 9, 11]);
+
+    assert_eq!(prev_grapheme_boundary(&r, 11), 9);
+    assert_eq!(prev_grapheme_boundary(&r, 9), 7);
+    assert_eq!(prev_grapheme_boundary(&r, 5), 2);
+    assert_eq!(prev_grapheme_boundary(&r, 0), 0);
+    assert!(!is_grapheme_boundary(&r, 1));
+    assert!(!is_grapheme_boundary(&r, 10));
+    assert_eq!(floor_grapheme_boundary(&r, 8), 7);
+    assert_eq!(floor_grapheme_boundary(&r, 9), 9);
+  }
+}
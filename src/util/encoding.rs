@@ -0,0 +1,189 @@
+pub use encoding_rs::Encoding;
+use {
+  chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection},
+  encoding_rs::{UTF_16BE, UTF_16LE, UTF_8},
+  ropey::Rope,
+  std::io,
+};
+
+/// Bytes looked at to recognise UTF-16 without a byte order mark.
+const UTF_16_SAMPLE: usize = 1024;
+
+/// Character encoding of a file, used again when the file is written back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileEncoding {
+  pub encoding: &'static Encoding,
+  /// The file starts with a byte order mark.
+  pub bom: bool,
+}
+
+impl Default for FileEncoding {
+  fn default() -> Self {
+    Self {
+      encoding: UTF_8,
+      bom: false,
+    }
+  }
+}
+
+/// UTF-16 without a byte order mark, recognised by the zero high bytes of
+/// ASCII text.
+fn detect_utf_16(bytes: &[u8]) -> Option<&'static Encoding> {
+  let sample = &bytes[..bytes.len().min(UTF_16_SAMPLE) & !1];
+  if sample.is_empty() {
+    return None;
+  }
+  let zeros = |offset: usize| {
+    sample
+      .iter()
+      .skip(offset)
+      .step_by(2)
+      .filter(|b| **b == 0)
+      .count()
+  };
+  let (even, odd) = (zeros(0), zeros(1));
+  let pairs = sample.len() / 2;
+  if odd > pairs / 2 && even == 0 {
+    Some(UTF_16LE)
+  } else if even > pairs / 2 && odd == 0 {
+    Some(UTF_16BE)
+  } else {
+    None
+  }
+}
+
+impl FileEncoding {
+  /// Write `encoding`, UTF-16 gets a byte order mark so it can be read back.
+  pub fn new(encoding: &'static Encoding) -> Self {
+    Self {
+      encoding,
+      bom: encoding == UTF_16LE || encoding == UTF_16BE,
+    }
+  }
+
+  /// Guess the encoding of `bytes`, a byte order mark wins over anything
+  /// else and valid UTF-8 is taken as UTF-8.
+  pub fn detect(bytes: &[u8]) -> Self {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+      return Self {
+        encoding,
+        bom: true,
+      };
+    }
+    // NUL bytes are valid UTF-8, so this goes first
+    if let Some(encoding) = detect_utf_16(bytes) {
+      return Self {
+        encoding,
+        bom: false,
+      };
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+      return Self::default();
+    }
+
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    detector.feed(bytes, true);
+    Self::new(detector.guess(None, Utf8Detection::Allow))
+  }
+
+  pub fn name(&self) -> &'static str {
+    self.encoding.name()
+  }
+
+  /// Decode `bytes`, skipping a byte order mark of this encoding. The flag
+  /// tells whether malformed bytes were replaced.
+  pub fn decode(&self, bytes: &[u8]) -> (Rope, bool) {
+    let bytes = match Encoding::for_bom(bytes) {
+      Some((encoding, len)) if encoding == self.encoding => &bytes[len..],
+      _ => bytes,
+    };
+    let (text, malformed) = self.encoding.decode_without_bom_handling(bytes);
+    (Rope::from_str(&text), malformed)
+  }
+
+  /// Encode `text`, failing on chars this encoding has no bytes for.
+  pub fn encode(&self, text: &Rope) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len_bytes());
+    if self.bom {
+      bytes.extend_from_slice(match self.encoding.name() {
+        "UTF-16LE" => b"\xFF\xFE",
+        "UTF-16BE" => b"\xFE\xFF",
+        _ => b"\xEF\xBB\xBF",
+      });
+    }
+
+    // encoding_rs only decodes UTF-16, its encoder writes UTF-8 instead
+    if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+      let big_endian = self.encoding == UTF_16BE;
+      for unit in text.chunks().flat_map(str::encode_utf16) {
+        bytes.extend_from_slice(&if big_endian {
+          unit.to_be_bytes()
+        } else {
+          unit.to_le_bytes()
+        });
+      }
+      return Ok(bytes);
+    }
+
+    let text = text.to_string();
+    let (encoded, _, unmappable) = self.encoding.encode(&text);
+    if unmappable {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("text can not be written as {}", self.name()),
+      ));
+    }
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, encoding_rs::WINDOWS_1252};
+
+  fn round_trip(bytes: &[u8]) -> (FileEncoding, Rope) {
+    let encoding = FileEncoding::detect(bytes);
+    let (text, malformed) = encoding.decode(bytes);
+    assert!(!malformed);
+    assert_eq!(encoding.encode(&text).unwrap(), bytes);
+    (encoding, text)
+  }
+
+  #[test]
+  fn test_detect_and_round_trip() {
+    let (encoding, text) = round_trip("grüße\n".as_bytes());
+    assert_eq!(encoding, FileEncoding::default());
+    assert_eq!(text, "grüße\n");
+
+    let (encoding, text) = round_trip(b"\xEF\xBB\xBFbom\n");
+    assert_eq!((encoding.encoding, encoding.bom), (UTF_8, true));
+    assert_eq!(text, "bom\n");
+
+    let (encoding, text) = round_trip(b"\xFF\xFEh\0i\0\n\0");
+    assert_eq!((encoding.encoding, encoding.bom), (UTF_16LE, true));
+    assert_eq!(text, "hi\n");
+
+    let (encoding, text) = round_trip(b"\0h\0i\0\n");
+    assert_eq!((encoding.encoding, encoding.bom), (UTF_16BE, false));
+    assert_eq!(text, "hi\n");
+
+    let (encoding, text) =
+      round_trip(b"Le caf\xE9 est tr\xE8s bon, d\xE9j\xE0 servi.\n");
+    assert_eq!(encoding.encoding, WINDOWS_1252);
+    assert_eq!(text, "Le café est très bon, déjà servi.\n");
+  }
+
+  #[test]
+  fn test_encode_unmappable() {
+    let latin = FileEncoding::new(WINDOWS_1252);
+    assert!(!latin.bom);
+    assert!(latin.encode(&Rope::from_str("snow ☃")).is_err());
+
+    let utf_16 = FileEncoding::new(UTF_16BE);
+    assert_eq!(
+      utf_16.encode(&Rope::from_str("☃")).unwrap(),
+      b"\xFE\xFF\x26\x03"
+    );
+  }
+}
@@ -0,0 +1,161 @@
+use std::{
+  fs::{self, File},
+  io::{self, Write},
+  path::{Path, PathBuf},
+};
+
+/// Follow `path` through symlinks to the file it ends up at. The file
+/// itself does not need to exist, so saving through a dangling link creates
+/// its target.
+pub fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+  let mut path = path.to_path_buf();
+  // same limit as the kernel, so link loops end in an error
+  for _ in 0..40 {
+    match fs::symlink_metadata(&path) {
+      Ok(metadata) if metadata.file_type().is_symlink() => {
+        let target = fs::read_link(&path)?;
+        path = match path.parent() {
+          Some(parent) => parent.join(target),
+          None => target,
+        };
+      }
+      Ok(_) => return Ok(path),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(path),
+      Err(e) => return Err(e),
+    }
+  }
+  Err(io::Error::other("too many levels of symbolic links"))
+}
+
+/// Replace the file at `path` with `bytes` without ever leaving it half
+/// written: the bytes go to a temporary file next to it which is renamed
+/// over the original. Symlinks are followed and the permissions of the
+/// original are kept.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+  replace(path, bytes, None)
+}
+
+/// Like [`write_atomic`], but the file gets `permissions` instead of those
+/// of the original.
+pub fn write_atomic_with_permissions(
+  path: &Path,
+  bytes: &[u8],
+  permissions: fs::Permissions,
+) -> io::Result<()> {
+  replace(path, bytes, Some(permissions))
+}
+
+fn replace(
+  path: &Path,
+  bytes: &[u8],
+  permissions: Option<fs::Permissions>,
+) -> io::Result<()> {
+  let path = resolve_symlinks(path)?;
+  let file_name = path.file_name().ok_or_else(|| {
+    io::Error::new(io::ErrorKind::InvalidInput, "path has no file name")
+  })?;
+  let dir = match path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => dir,
+    _ => Path::new("."),
+  };
+  let mut temp_name = std::ffi::OsString::from(".");
+  temp_name.push(file_name);
+  temp_name.push(format!(".{}.tmp", std::process::id()));
+  let temp = dir.join(temp_name);
+
+  let permissions = match (permissions, fs::metadata(&path)) {
+    (Some(permissions), _) => Some(permissions),
+    (None, Ok(metadata)) => Some(metadata.permissions()),
+    (None, Err(e)) if e.kind() == io::ErrorKind::NotFound => None,
+    (None, Err(e)) => return Err(e),
+  };
+
+  let write = || -> io::Result<()> {
+    let mut file = File::create(&temp)?;
+    // before the bytes go in, so they are never readable by more users
+    if let Some(permissions) = permissions {
+      fs::set_permissions(&temp, permissions)?;
+    }
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp, &path)
+  };
+
+  write().inspect_err(|_| {
+    let _ = fs::remove_file(&temp);
+  })
+}
+
+/// An empty directory for a test, removed with everything in it when
+/// dropped, also when the test panics.
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+  /// `name` tells the directories of tests running at once apart.
+  pub fn new(name: &str) -> Self {
+    let dir = std::env::temp_dir().join(format!(
+      "blasted-{}-{}",
+      name,
+      std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("failed to create a test directory");
+    Self(dir)
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.0
+  }
+
+  pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+    self.0.join(path)
+  }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.0);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_write_atomic_replaces_contents() {
+    let dir = TempDir::new("fs-replace");
+    let path = dir.join("file.txt");
+
+    write_atomic(&path, b"first").unwrap();
+    write_atomic(&path, b"second").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+    // no temporary files are left behind
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_write_atomic_keeps_permissions_and_follows_symlinks() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let dir = TempDir::new("fs-symlink");
+    let target = dir.join("target.sh");
+    let link = dir.join("link.sh");
+    fs::write(&target, "old").unwrap();
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o750)).unwrap();
+    symlink("target.sh", &link).unwrap();
+
+    write_atomic(&link, b"new").unwrap();
+    assert!(fs::symlink_metadata(&link)
+      .unwrap()
+      .file_type()
+      .is_symlink());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+    let mode = fs::metadata(&target).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o750);
+  }
+}
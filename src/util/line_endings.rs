@@ -10,6 +10,24 @@ pub const DEFAULT_LINE_ENDING: LineEnding = LineEnding::LF;
 pub enum LineEnding {
   Crlf, // CarriageReturn followed by LineFeed
   LF,   // U+000A -- LineFeed
+  #[cfg(feature = "unicode-lines")]
+  VT, // U+000B -- VerticalTab
+  #[cfg(feature = "unicode-lines")]
+  FF, // U+000C -- FormFeed
+  #[cfg(feature = "unicode-lines")]
+  CR, // U+000D -- CarriageReturn
+  #[cfg(feature = "unicode-lines")]
+  Nel, // U+0085 -- NextLine
+  #[cfg(feature = "unicode-lines")]
+  LS, // U+2028 -- Line Separator
+  #[cfg(feature = "unicode-lines")]
+  PS, // U+2029 -- ParagraphSeparator
+}
+
+impl Default for LineEnding {
+  fn default() -> Self {
+    DEFAULT_LINE_ENDING
+  }
 }
 
 impl LineEnding {
@@ -26,13 +44,48 @@ impl LineEnding {
     match self {
       Self::Crlf => "\u{000D}\u{000A}",
       Self::LF => "\u{000A}",
+      #[cfg(feature = "unicode-lines")]
+      Self::VT => "\u{000B}",
+      #[cfg(feature = "unicode-lines")]
+      Self::FF => "\u{000C}",
+      #[cfg(feature = "unicode-lines")]
+      Self::CR => "\u{000D}",
+      #[cfg(feature = "unicode-lines")]
+      Self::Nel => "\u{0085}",
+      #[cfg(feature = "unicode-lines")]
+      Self::LS => "\u{2028}",
+      #[cfg(feature = "unicode-lines")]
+      Self::PS => "\u{2029}",
+    }
+  }
+
+  /// Breaks a page or paragraph rather than ending a line of text, such
+  /// line endings are left alone when detecting or converting line endings.
+  #[inline]
+  pub const fn is_special_use(&self) -> bool {
+    #[cfg(feature = "unicode-lines")]
+    if matches!(self, Self::VT | Self::FF | Self::PS) {
+      return true;
     }
+    false
   }
 
   #[inline]
   pub const fn from_char(ch: char) -> Option<LineEnding> {
     match ch {
       '\u{000A}' => Some(LineEnding::LF),
+      #[cfg(feature = "unicode-lines")]
+      '\u{000B}' => Some(LineEnding::VT),
+      #[cfg(feature = "unicode-lines")]
+      '\u{000C}' => Some(LineEnding::FF),
+      #[cfg(feature = "unicode-lines")]
+      '\u{000D}' => Some(LineEnding::CR),
+      #[cfg(feature = "unicode-lines")]
+      '\u{0085}' => Some(LineEnding::Nel),
+      #[cfg(feature = "unicode-lines")]
+      '\u{2028}' => Some(LineEnding::LS),
+      #[cfg(feature = "unicode-lines")]
+      '\u{2029}' => Some(LineEnding::PS),
       // Not a line ending
       _ => None,
     }
@@ -47,6 +100,18 @@ impl LineEnding {
     match g {
       "\u{000D}\u{000A}" => Some(LineEnding::Crlf),
       "\u{000A}" => Some(LineEnding::LF),
+      #[cfg(feature = "unicode-lines")]
+      "\u{000B}" => Some(LineEnding::VT),
+      #[cfg(feature = "unicode-lines")]
+      "\u{000C}" => Some(LineEnding::FF),
+      #[cfg(feature = "unicode-lines")]
+      "\u{000D}" => Some(LineEnding::CR),
+      #[cfg(feature = "unicode-lines")]
+      "\u{0085}" => Some(LineEnding::Nel),
+      #[cfg(feature = "unicode-lines")]
+      "\u{2028}" => Some(LineEnding::LS),
+      #[cfg(feature = "unicode-lines")]
+      "\u{2029}" => Some(LineEnding::PS),
       // Not a line ending
       _ => None,
     }
@@ -82,13 +147,42 @@ pub fn auto_detect_line_ending(doc: &Rope) -> Option<LineEnding> {
   // are being matched, as they might be special-use only
   for line in doc.lines().take(100) {
     match get_line_ending(&line) {
-      None => {}
-      ending => return ending,
+      Some(ending) if !ending.is_special_use() => return Some(ending),
+      _ => {}
     }
   }
   None
 }
 
+/// Whether the passed document uses more than one kind of line ending.
+pub fn has_mixed_line_endings(doc: &Rope) -> bool {
+  let mut endings = doc
+    .lines()
+    .filter_map(|line| get_line_ending(&line))
+    .filter(|ending| !ending.is_special_use());
+  match endings.next() {
+    Some(first) => endings.any(|ending| ending != first),
+    None => false,
+  }
+}
+
+/// Replaces every line ending in the passed text with `ending`.
+pub fn normalize_line_endings(text: &str, ending: LineEnding) -> String {
+  let text = Rope::from_str(text);
+  let mut normalized = String::with_capacity(text.len_bytes());
+  for line in text.lines() {
+    match get_line_ending(&line).filter(|e| !e.is_special_use()) {
+      Some(old) => {
+        let end = line.len_chars() - old.len_chars();
+        normalized.extend(line.slice(..end).chunks());
+        normalized.push_str(ending.as_str());
+      }
+      None => normalized.extend(line.chunks()),
+    }
+  }
+  normalized
+}
+
 /// Returns the passed line's line ending, if any.
 pub fn get_line_ending(line: &RopeSlice) -> Option<LineEnding> {
   // Last character as str.
@@ -110,6 +204,16 @@ pub fn get_line_ending(line: &RopeSlice) -> Option<LineEnding> {
   LineEnding::from_str(g2).or_else(|| LineEnding::from_str(g1))
 }
 
+#[cfg(feature = "unicode-lines")]
+/// Returns the passed line's line ending, if any.
+pub fn get_line_ending_of_str(line: &str) -> Option<LineEnding> {
+  if line.ends_with("\u{000D}\u{000A}") {
+    Some(LineEnding::Crlf)
+  } else {
+    line.chars().next_back().and_then(LineEnding::from_char)
+  }
+}
+
 #[cfg(not(feature = "unicode-lines"))]
 /// Returns the passed line's line ending, if any.
 pub fn get_line_ending_of_str(line: &str) -> Option<LineEnding> {
@@ -191,6 +295,35 @@ mod line_ending_tests {
     );
   }
 
+  #[test]
+  fn mixed_line_endings() {
+    assert!(!has_mixed_line_endings(&Rope::from_str("")));
+    assert!(!has_mixed_line_endings(&Rope::from_str("a\r\nb\r\nc")));
+    assert!(has_mixed_line_endings(&Rope::from_str("a\r\nb\nc")));
+  }
+
+  #[test]
+  fn normalize_to_line_ending() {
+    assert_eq!(
+      normalize_line_endings("a\r\nb\nc", LineEnding::LF),
+      "a\nb\nc"
+    );
+    assert_eq!(
+      normalize_line_endings("a\r\nb\n\n", LineEnding::Crlf),
+      "a\r\nb\r\n\r\n"
+    );
+    #[cfg(not(feature = "unicode-lines"))]
+    assert_eq!(normalize_line_endings("a\rb", LineEnding::LF), "a\rb");
+    #[cfg(feature = "unicode-lines")]
+    {
+      assert_eq!(normalize_line_endings("a\rb", LineEnding::LF), "a\nb");
+      assert_eq!(
+        normalize_line_endings("a\u{000C}b", LineEnding::LF),
+        "a\u{000C}b"
+      );
+    }
+  }
+
   #[test]
   fn str_to_line_ending() {
     assert_eq!(LineEnding::from_str("\n"), Some(LineEnding::LF));
@@ -223,6 +356,10 @@ mod line_ending_tests {
   #[test]
   fn get_line_ending_str() {
     let text = "Hello\rworld\nhow\r\nare you?";
+    #[cfg(feature = "unicode-lines")]
+    assert_eq!(get_line_ending_of_str(&text[..6]), Some(LineEnding::CR));
+    #[cfg(not(feature = "unicode-lines"))]
+    assert_eq!(get_line_ending_of_str(&text[..6]), None);
     assert_eq!(get_line_ending_of_str(&text[..12]), Some(LineEnding::LF));
     assert_eq!(get_line_ending_of_str(&text[..17]), Some(LineEnding::Crlf));
     assert_eq!(get_line_ending_of_str(text), None);
@@ -238,5 +375,12 @@ mod line_ending_tests {
       assert_eq!(line_end_char_index(s, 1), 15);
       assert_eq!(line_end_char_index(s, 2), 25);
     }
+    #[cfg(feature = "unicode-lines")]
+    {
+      assert_eq!(line_end_char_index(s, 0), 5);
+      assert_eq!(line_end_char_index(s, 1), 11);
+      assert_eq!(line_end_char_index(s, 2), 15);
+      assert_eq!(line_end_char_index(s, 3), 25);
+    }
   }
 }
@@ -1,14 +1,195 @@
-use {crate::document::DocumentId, slotmap::new_key_type};
+use {crate::document::DocumentId, slotmap::new_key_type, tui::layout::Rect};
 
 new_key_type! { pub struct ViewId; }
 
 pub struct View {
   pub document_id: DocumentId,
-  // offset: (usize, usize),
+  /// First line and first screen column shown.
+  pub offset: (usize, usize),
+  /// Row of the first line shown at the top, when lines are wrapped.
+  pub first_row: usize,
+  /// Where the view was last drawn.
+  pub area: Rect,
+  /// Cursor position when the view was last scrolled sideways by hand, the
+  /// columns shown stay put until the cursor moves away from it.
+  pub scrolled_at: Option<(usize, usize)>,
+}
+
+/// Move `first` just enough to show `pos` with `margin` around it, in a
+/// window `size` long.
+fn scroll_to(first: &mut usize, pos: usize, size: usize, margin: usize) {
+  // a margin over half the window would make it jump back and forth
+  let margin = margin.min(size.saturating_sub(1) / 2);
+  if pos < *first + margin {
+    *first = pos.saturating_sub(margin);
+  } else if pos + margin >= *first + size {
+    *first = (pos + margin + 1).saturating_sub(size);
+  }
 }
 
 impl View {
   pub fn new(document_id: DocumentId) -> Self {
-    Self { document_id }
+    Self {
+      document_id,
+      offset: (0, 0),
+      first_row: 0,
+      area: Rect::default(),
+      scrolled_at: None,
+    }
+  }
+
+  /// Scroll just enough to show `line` with `scrolloff` lines around it,
+  /// in a view `height` lines high.
+  pub fn scroll_to_line(
+    &mut self,
+    line: usize,
+    height: usize,
+    scrolloff: usize,
+  ) {
+    self.scroll_to_row((line, 0), height, scrolloff, |_| 1);
+  }
+
+  /// Screen rows from the top of the view down to `row` of `line`, `None`
+  /// when it is above the view. `rows` gives the rows a line takes up.
+  /// Counting stops once past `limit`.
+  fn rows_from_top(
+    &self,
+    (line, row): (usize, usize),
+    rows: &impl Fn(usize) -> usize,
+    limit: usize,
+  ) -> Option<usize> {
+    if (line, row) < (self.offset.0, self.first_row) {
+      return None;
+    }
+    let limit = limit.saturating_add(self.first_row);
+    let mut count = row;
+    for line in self.offset.0..line {
+      count += rows(line);
+      if count > limit {
+        break;
+      }
+    }
+    Some(count - self.first_row)
+  }
+
+  /// Screen row of `row` of `line`, `None` when it is above the view.
+  pub fn screen_row(
+    &self,
+    pos: (usize, usize),
+    rows: impl Fn(usize) -> usize,
+  ) -> Option<usize> {
+    self.rows_from_top(pos, &rows, usize::MAX)
+  }
+
+  /// Scroll just enough to show `row` of `line` with `scrolloff` rows
+  /// around it, in a view `height` rows high. `rows` gives the rows a line
+  /// takes up, more than one when it is wrapped.
+  pub fn scroll_to_row(
+    &mut self,
+    pos: (usize, usize),
+    height: usize,
+    scrolloff: usize,
+    rows: impl Fn(usize) -> usize,
+  ) {
+    // a margin over half the view would make it jump back and forth
+    let margin = scrolloff.min(height.saturating_sub(1) / 2);
+    // the first line may have lost rows since it was scrolled to
+    self.first_row = self.first_row.min(rows(self.offset.0).saturating_sub(1));
+
+    let above = match self.rows_from_top(pos, &rows, height) {
+      None => margin,
+      Some(below) if below < margin => margin,
+      Some(below) if below + margin >= height => {
+        height.saturating_sub(margin + 1)
+      }
+      Some(_) => return,
+    };
+
+    // the top is `above` rows up from the row to show
+    let (mut line, mut row) = pos;
+    for _ in 0..above {
+      if row > 0 {
+        row -= 1;
+      } else if line > 0 {
+        line -= 1;
+        row = rows(line).saturating_sub(1);
+      } else {
+        break;
+      }
+    }
+    self.offset.0 = line;
+    self.first_row = row;
+  }
+
+  /// Scroll sideways just enough to show screen `column` with
+  /// `sidescrolloff` columns around it, in a view `width` columns wide.
+  pub fn scroll_to_column(
+    &mut self,
+    column: usize,
+    width: usize,
+    sidescrolloff: usize,
+  ) {
+    scroll_to(&mut self.offset.1, column, width, sidescrolloff);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_scroll_to_line() {
+    let mut view = View::new(DocumentId::default());
+
+    view.scroll_to_line(5, 10, 3);
+    assert_eq!(view.offset.0, 0);
+    view.scroll_to_line(7, 10, 3);
+    assert_eq!(view.offset.0, 1);
+    view.scroll_to_line(30, 10, 3);
+    assert_eq!(view.offset.0, 24);
+    view.scroll_to_line(26, 10, 3);
+    assert_eq!(view.offset.0, 23);
+    view.scroll_to_line(1, 10, 3);
+    assert_eq!(view.offset.0, 0);
+
+    // the margin shrinks in views too small for it
+    view.scroll_to_line(10, 3, 5);
+    assert_eq!(view.offset.0, 9);
+  }
+
+  #[test]
+  fn test_scroll_to_wrapped_row() {
+    let mut view = View::new(DocumentId::default());
+    // every third line takes up three rows
+    let rows = |line: usize| if line.is_multiple_of(3) { 3 } else { 1 };
+
+    view.scroll_to_row((1, 0), 6, 1, rows);
+    assert_eq!((view.offset.0, view.first_row), (0, 0));
+    view.scroll_to_row((4, 0), 6, 1, rows);
+    assert_eq!((view.offset.0, view.first_row), (2, 0));
+    assert_eq!(view.screen_row((4, 0), rows), Some(4));
+
+    // the top can be in the middle of a wrapped line
+    view.scroll_to_row((6, 1), 6, 1, rows);
+    assert_eq!((view.offset.0, view.first_row), (3, 2));
+    view.scroll_to_row((3, 2), 6, 1, rows);
+    assert_eq!((view.offset.0, view.first_row), (3, 1));
+    view.scroll_to_row((2, 0), 6, 1, rows);
+    assert_eq!((view.offset.0, view.first_row), (1, 0));
+    assert_eq!(view.screen_row((0, 2), rows), None);
+  }
+
+  #[test]
+  fn test_scroll_to_column() {
+    let mut view = View::new(DocumentId::default());
+
+    view.scroll_to_column(75, 80, 5);
+    assert_eq!(view.offset.1, 1);
+    view.scroll_to_column(120, 80, 5);
+    assert_eq!(view.offset.1, 46);
+    view.scroll_to_column(60, 80, 5);
+    assert_eq!(view.offset.1, 46);
+    view.scroll_to_column(3, 80, 5);
+    assert_eq!(view.offset.1, 0);
   }
 }
@@ -0,0 +1,148 @@
+use {
+  notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher},
+  std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+  },
+};
+
+/// Watches files for changes made by other programs.
+///
+/// Files are watched through their directory, tools like `cargo fmt` or
+/// `git checkout` replace a file instead of writing into it and a watch on
+/// the old file would go silent.
+pub struct FileWatcher {
+  watcher: RecommendedWatcher,
+  changes: Receiver<PathBuf>,
+  /// Watched files, by the directory they are watched through.
+  files: HashMap<PathBuf, HashSet<PathBuf>>,
+}
+
+impl FileWatcher {
+  /// `wake` is called from the watcher thread whenever something changed,
+  /// the changes can then be picked up with [`Self::changed_files`].
+  pub fn new(wake: impl Fn() + Send + 'static) -> notify::Result<Self> {
+    let (sender, changes) = channel();
+    let watcher =
+      notify::recommended_watcher(move |event: notify::Result<Event>| {
+        match event {
+          Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+            for path in event.paths {
+              let _ = sender.send(path);
+            }
+            wake();
+          }
+          Ok(_) => {}
+          Err(e) => tracing::warn!("File watcher error: {}", e),
+        }
+      })?;
+
+    Ok(Self {
+      watcher,
+      changes,
+      files: HashMap::new(),
+    })
+  }
+
+  /// Split a path into its canonical directory and its file.
+  fn locate(path: &Path) -> io::Result<(PathBuf, PathBuf)> {
+    let path = path.canonicalize()?;
+    let dir = path
+      .parent()
+      .ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "path has no directory")
+      })?
+      .to_path_buf();
+    Ok((dir, path))
+  }
+
+  pub fn watch(&mut self, path: &Path) -> notify::Result<()> {
+    let (dir, path) = Self::locate(path)?;
+    if !self.files.contains_key(&dir) {
+      self.watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    }
+    self.files.entry(dir).or_default().insert(path);
+    Ok(())
+  }
+
+  pub fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+    let (dir, path) = Self::locate(path)?;
+    if let Some(files) = self.files.get_mut(&dir) {
+      files.remove(&path);
+      if files.is_empty() {
+        self.files.remove(&dir);
+        self.watcher.unwatch(&dir)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Canonical paths of the watched files that changed since the last call.
+  pub fn changed_files(&self) -> HashSet<PathBuf> {
+    self
+      .changes
+      .try_iter()
+      .filter(|path| {
+        path
+          .parent()
+          .and_then(|dir| self.files.get(dir))
+          .is_some_and(|files| files.contains(path))
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::util::fs::TempDir,
+    std::{
+      sync::{Arc, Condvar, Mutex},
+      time::Duration,
+    },
+  };
+
+  #[test]
+  fn test_reports_replaced_files() {
+    let dir = TempDir::new("watcher");
+    let watched = dir.join("watched.txt");
+    let other = dir.join("other.txt");
+    std::fs::write(&watched, "one").unwrap();
+
+    let woken = Arc::new((Mutex::new(false), Condvar::new()));
+    let waker = woken.clone();
+    let mut watcher = FileWatcher::new(move || {
+      *waker.0.lock().unwrap() = true;
+      waker.1.notify_all();
+    })
+    .unwrap();
+    watcher.watch(&watched).unwrap();
+
+    // replace the file the way atomic writers do
+    std::fs::write(&other, "two").unwrap();
+    std::fs::rename(&other, &watched).unwrap();
+
+    let (lock, condvar) = &*woken;
+    let (woken, timeout) = condvar
+      .wait_timeout_while(lock.lock().unwrap(), Duration::from_secs(5), |w| !*w)
+      .unwrap();
+    assert!(!timeout.timed_out());
+    // the watcher thread needs the lock for the wake ups still to come
+    drop(woken);
+
+    // the rename may arrive after the wake up for creating `other`
+    let watched = watched.canonicalize().unwrap();
+    let mut changed = HashSet::new();
+    for _ in 0..50 {
+      changed.extend(watcher.changed_files());
+      if changed.contains(&watched) {
+        break;
+      }
+      std::thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(changed, HashSet::from([watched]));
+  }
+}